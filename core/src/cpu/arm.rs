@@ -9,10 +9,10 @@ use crate::io::AccessType;
 impl CPU {
     pub(super) fn fill_arm_instr_buffer(&mut self, io: &mut IO) {
         self.regs.pc &= !0x3;
-        self.instr_buffer[0] = self.read::<u32>(io, AccessType::S, self.regs.pc & !0x3);
+        self.instr_buffer[0] = self.fetch::<u32>(io, AccessType::S, self.regs.pc & !0x3);
         self.regs.pc = self.regs.pc.wrapping_add(4);
 
-        self.instr_buffer[1] = self.read::<u32>(io, AccessType::S, self.regs.pc & !0x3);
+        self.instr_buffer[1] = self.fetch::<u32>(io, AccessType::S, self.regs.pc & !0x3);
     }
 
     pub(super) fn emulate_arm_instr(&mut self, io: &mut IO) {
@@ -432,6 +432,10 @@ impl CPU {
     // ARM.13: Software Interrupt (SWI)
     fn arm_software_interrupt(&mut self, io: &mut IO, instr: u32) {
         assert_eq!(instr >> 24 & 0xF, 0b1111);
+        if self.hle_bios {
+            self.instruction_prefetch::<u32>(io, AccessType::S);
+            return self.hle_software_interrupt(io, (instr >> 16) as u8)
+        }
         self.instruction_prefetch::<u32>(io, AccessType::N);
         self.regs.change_mode(Mode::SVC);
         self.regs.set_reg(Reg::R14, self.regs.pc.wrapping_sub(4));
@@ -0,0 +1,212 @@
+// Decodes in the same order as the instruction LUTs, so every handler has a matching format
+
+const CONDITIONS: [&str; 16] = ["eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "", "nv"];
+const SHIFTS: [&str; 4] = ["lsl", "lsr", "asr", "ror"];
+
+fn reg(reg: u32) -> String {
+    match reg {
+        13 => "sp".to_string(),
+        14 => "lr".to_string(),
+        15 => "pc".to_string(),
+        _ => format!("r{}", reg),
+    }
+}
+
+// Consecutive registers are merged into ranges, like {r0-r3, lr}
+fn reg_list(r_list: u32) -> String {
+    let mut ranges = Vec::new();
+    let mut reg_i = 0;
+    while reg_i < 16 {
+        if r_list & 1 << reg_i == 0 { reg_i += 1; continue }
+        let start = reg_i;
+        while reg_i < 16 && r_list & 1 << reg_i != 0 { reg_i += 1 }
+        ranges.push(match reg_i - start {
+            1 => reg(start),
+            2 => format!("{}, {}", reg(start), reg(start + 1)),
+            _ => format!("{}-{}", reg(start), reg(reg_i - 1)),
+        });
+    }
+    format!("{{{}}}", ranges.join(", "))
+}
+
+fn signed_imm(add: bool, offset: u32) -> String {
+    format!("#{}0x{:X}", if add { "" } else { "-" }, offset)
+}
+
+// Address is that of the instruction itself
+pub fn disassemble_arm(instr: u32, addr: u32) -> String {
+    let cond = CONDITIONS[(instr >> 28) as usize];
+    let rn = instr >> 16 & 0xF;
+    let rd = instr >> 12 & 0xF;
+    let rs = instr >> 8 & 0xF;
+    let rm = instr & 0xF;
+    let bit = |bit: u32| instr >> bit & 0x1 != 0;
+
+    if instr & 0x0FF0_00F0 == 0x0120_0010 {
+        format!("bx{} {}", cond, reg(rm))
+    } else if instr & 0x0FC0_00F0 == 0x0000_0090 {
+        let s = if bit(20) { "s" } else { "" };
+        if bit(21) { format!("mla{}{} {}, {}, {}, {}", cond, s, reg(rn), reg(rm), reg(rs), reg(rd)) }
+        else { format!("mul{}{} {}, {}, {}", cond, s, reg(rn), reg(rm), reg(rs)) }
+    } else if instr & 0x0F80_00F0 == 0x0080_0090 {
+        let op = match (bit(22), bit(21)) {
+            (false, false) => "umull",
+            (false, true) => "umlal",
+            (true, false) => "smull",
+            (true, true) => "smlal",
+        };
+        format!("{}{}{} {}, {}, {}, {}", op, cond, if bit(20) { "s" } else { "" }, reg(rd), reg(rn), reg(rm), reg(rs))
+    } else if instr & 0x0F80_00F0 == 0x0100_0090 {
+        format!("swp{}{} {}, {}, [{}]", cond, if bit(22) { "b" } else { "" }, reg(rd), reg(rm), reg(rn))
+    } else if instr & 0x0E00_0090 == 0x0000_0090 {
+        let op = match (bit(20), instr >> 5 & 0x3) {
+            (false, 1) => "strh",
+            (true, 1) => "ldrh",
+            (true, 2) => "ldrsb",
+            (true, 3) => "ldrsh",
+            _ => return "undefined".to_string(),
+        };
+        let offset = if bit(22) { signed_imm(bit(23), (instr >> 4 & 0xF0) | rm) }
+        else { format!("{}{}", if bit(23) { "" } else { "-" }, reg(rm)) };
+        let address = if bit(24) { format!("[{}, {}]{}", reg(rn), offset, if bit(21) { "!" } else { "" }) }
+        else { format!("[{}], {}", reg(rn), offset) };
+        format!("{}{} {}, {}", op, cond, reg(rd), address)
+    } else if instr & 0x0D90_0000 == 0x0100_0000 {
+        let psr = if bit(22) { "spsr" } else { "cpsr" };
+        if !bit(21) { return format!("mrs{} {}, {}", cond, reg(rd), psr) }
+        let fields: String = ["c", "x", "s", "f"].iter().enumerate()
+            .filter(|(i, _)| bit(16 + *i as u32)).map(|(_, field)| *field).collect();
+        let operand = if bit(25) { format!("#0x{:X}", (instr & 0xFF).rotate_right((instr >> 8 & 0xF) * 2)) }
+        else { reg(rm) };
+        format!("msr{} {}_{}, {}", cond, psr, fields, operand)
+    } else if instr & 0x0C00_0000 == 0x0000_0000 {
+        let opcode = instr >> 21 & 0xF;
+        let op = ["and", "eor", "sub", "rsb", "add", "adc", "sbc", "rsc",
+            "tst", "teq", "cmp", "cmn", "orr", "mov", "bic", "mvn"][opcode as usize];
+        let operand = if bit(25) { format!("#0x{:X}", (instr & 0xFF).rotate_right((instr >> 8 & 0xF) * 2)) }
+        else { shifted_reg(instr) };
+        match opcode {
+            0x8 ..= 0xB => format!("{}{} {}, {}", op, cond, reg(rn), operand),
+            0xD | 0xF => format!("{}{}{} {}, {}", op, cond, if bit(20) { "s" } else { "" }, reg(rd), operand),
+            _ => format!("{}{}{} {}, {}, {}", op, cond, if bit(20) { "s" } else { "" }, reg(rd), reg(rn), operand),
+        }
+    } else if instr & 0x0C00_0000 == 0x0400_0000 {
+        let op = if bit(20) { "ldr" } else { "str" };
+        let size = if bit(22) { "b" } else { "" };
+        // Post-indexing with writeback forces user mode access
+        let user = if !bit(24) && bit(21) { "t" } else { "" };
+        let offset = if bit(25) { format!("{}{}", if bit(23) { "" } else { "-" }, shifted_reg(instr)) }
+        else { signed_imm(bit(23), instr & 0xFFF) };
+        let address = if bit(24) { format!("[{}, {}]{}", reg(rn), offset, if bit(21) { "!" } else { "" }) }
+        else { format!("[{}], {}", reg(rn), offset) };
+        format!("{}{}{}{} {}, {}", op, cond, size, user, reg(rd), address)
+    } else if instr & 0x0E00_0000 == 0x0800_0000 {
+        let op = if bit(20) { "ldm" } else { "stm" };
+        let mode = ["da", "ia", "db", "ib"][(instr >> 23 & 0x3) as usize];
+        format!("{}{}{} {}{}, {}{}", op, cond, mode, reg(rn), if bit(21) { "!" } else { "" },
+            reg_list(instr & 0xFFFF), if bit(22) { "^" } else { "" })
+    } else if instr & 0x0E00_0000 == 0x0A00_0000 {
+        let offset = ((instr << 8) as i32 >> 6) as u32;
+        format!("b{}{} 0x{:08X}", if bit(24) { "l" } else { "" }, cond, addr.wrapping_add(8).wrapping_add(offset))
+    } else if instr & 0x0F00_0000 == 0x0F00_0000 {
+        format!("swi{} #0x{:X}", cond, instr & 0xFF_FFFF)
+    } else if instr & 0x0E00_0000 == 0x0C00_0000 {
+        format!("{}{}", if bit(20) { "ldc" } else { "stc" }, cond)
+    } else if instr & 0x0F00_0000 == 0x0E00_0000 {
+        format!("{}{}", if !bit(4) { "cdp" } else if bit(20) { "mrc" } else { "mcr" }, cond)
+    } else { "undefined".to_string() }
+}
+
+// Register operand of data processing and single data transfer
+fn shifted_reg(instr: u32) -> String {
+    let rm = reg(instr & 0xF);
+    let shift_type = instr >> 5 & 0x3;
+    if instr & 0x10 != 0 {
+        return format!("{}, {} {}", rm, SHIFTS[shift_type as usize], reg(instr >> 8 & 0xF))
+    }
+    match (shift_type, instr >> 7 & 0x1F) {
+        (0, 0) => rm,
+        (3, 0) => format!("{}, rrx", rm),
+        (1, 0) | (2, 0) => format!("{}, {} #32", rm, SHIFTS[shift_type as usize]),
+        (_, shift) => format!("{}, {} #{}", rm, SHIFTS[shift_type as usize], shift),
+    }
+}
+
+// The halfword after is needed to show where a long branch with link goes
+pub fn disassemble_thumb(instr: u16, next: u16, addr: u32) -> String {
+    let instr = instr as u32;
+    let opcode = instr >> 8;
+    let low_reg = |bit: u32| reg(instr >> bit & 0x7);
+    let bit = |bit: u32| instr >> bit & 0x1 != 0;
+
+    if opcode & 0b1111_1000 == 0b0001_1000 {
+        let op = if bit(9) { "sub" } else { "add" };
+        let operand = if bit(10) { format!("#0x{:X}", instr >> 6 & 0x7) } else { low_reg(6) };
+        format!("{} {}, {}, {}", op, low_reg(0), low_reg(3), operand)
+    } else if opcode & 0b1110_0000 == 0b0000_0000 {
+        let shift = match (instr >> 6 & 0x1F, instr >> 11 & 0x3) {
+            (0, 1) | (0, 2) => 32,
+            (shift, _) => shift,
+        };
+        format!("{} {}, {}, #{}", SHIFTS[(instr >> 11 & 0x3) as usize], low_reg(0), low_reg(3), shift)
+    } else if opcode & 0b1110_0000 == 0b0010_0000 {
+        let op = ["mov", "cmp", "add", "sub"][(instr >> 11 & 0x3) as usize];
+        format!("{} {}, #0x{:X}", op, low_reg(8), instr & 0xFF)
+    } else if opcode & 0b1111_1100 == 0b0100_0000 {
+        let op = ["and", "eor", "lsl", "lsr", "asr", "adc", "sbc", "ror",
+            "tst", "neg", "cmp", "cmn", "orr", "mul", "bic", "mvn"][(instr >> 6 & 0xF) as usize];
+        format!("{} {}, {}", op, low_reg(0), low_reg(3))
+    } else if opcode & 0b1111_1100 == 0b0100_0100 {
+        let rd = instr & 0x7 | (instr >> 4 & 0x8);
+        let rs = instr >> 3 & 0xF;
+        match instr >> 8 & 0x3 {
+            0 => format!("add {}, {}", reg(rd), reg(rs)),
+            1 => format!("cmp {}, {}", reg(rd), reg(rs)),
+            2 => format!("mov {}, {}", reg(rd), reg(rs)),
+            _ => format!("bx {}", reg(rs)),
+        }
+    } else if opcode & 0b1111_1000 == 0b0100_1000 {
+        format!("ldr {}, [pc, #0x{:X}]", low_reg(8), (instr & 0xFF) * 4)
+    } else if opcode & 0b1111_0010 == 0b0101_0000 {
+        let op = ["str", "strb", "ldr", "ldrb"][(instr >> 10 & 0x3) as usize];
+        format!("{} {}, [{}, {}]", op, low_reg(0), low_reg(3), low_reg(6))
+    } else if opcode & 0b1111_0010 == 0b0101_0010 {
+        let op = ["strh", "ldrsb", "ldrh", "ldrsh"][(instr >> 10 & 0x3) as usize];
+        format!("{} {}, [{}, {}]", op, low_reg(0), low_reg(3), low_reg(6))
+    } else if opcode & 0b1110_0000 == 0b0110_0000 {
+        let op = if bit(11) { "ldr" } else { "str" };
+        let (size, offset) = if bit(12) { ("b", instr >> 6 & 0x1F) } else { ("", (instr >> 6 & 0x1F) * 4) };
+        format!("{}{} {}, [{}, #0x{:X}]", op, size, low_reg(0), low_reg(3), offset)
+    } else if opcode & 0b1111_0000 == 0b1000_0000 {
+        let op = if bit(11) { "ldrh" } else { "strh" };
+        format!("{} {}, [{}, #0x{:X}]", op, low_reg(0), low_reg(3), (instr >> 6 & 0x1F) * 2)
+    } else if opcode & 0b1111_0000 == 0b1001_0000 {
+        let op = if bit(11) { "ldr" } else { "str" };
+        format!("{} {}, [sp, #0x{:X}]", op, low_reg(8), (instr & 0xFF) * 4)
+    } else if opcode & 0b1111_0000 == 0b1010_0000 {
+        format!("add {}, {}, #0x{:X}", low_reg(8), if bit(11) { "sp" } else { "pc" }, (instr & 0xFF) * 4)
+    } else if opcode & 0b1111_1111 == 0b1011_0000 {
+        format!("add sp, {}", signed_imm(!bit(7), (instr & 0x7F) * 4))
+    } else if opcode & 0b1111_0110 == 0b1011_0100 {
+        let (op, extra_reg) = if bit(11) { ("pop", 15) } else { ("push", 14) };
+        let r_list = instr & 0xFF | if bit(8) { 1 << extra_reg } else { 0 };
+        format!("{} {}", op, reg_list(r_list))
+    } else if opcode & 0b1111_0000 == 0b1100_0000 {
+        let op = if bit(11) { "ldmia" } else { "stmia" };
+        format!("{} {}!, {}", op, low_reg(8), reg_list(instr & 0xFF))
+    } else if opcode & 0b1111_1111 == 0b1101_1111 {
+        format!("swi #0x{:X}", instr & 0xFF)
+    } else if opcode & 0b1111_0000 == 0b1101_0000 {
+        let offset = ((instr << 24) as i32 >> 23) as u32;
+        format!("b{} 0x{:08X}", CONDITIONS[(instr >> 8 & 0xF) as usize], addr.wrapping_add(4).wrapping_add(offset))
+    } else if opcode & 0b1111_1000 == 0b1110_0000 {
+        let offset = ((instr << 21) as i32 >> 20) as u32;
+        format!("b 0x{:08X}", addr.wrapping_add(4).wrapping_add(offset))
+    } else if opcode & 0b1111_0000 == 0b1111_0000 {
+        if bit(11) { return format!("bl lr + #0x{:X}", (instr & 0x7FF) << 1) }
+        let next = next as u32;
+        if next & 0xF800 != 0xF800 { return format!("bl #0x{:X}", (instr & 0x7FF) << 12) }
+        let offset = (((instr & 0x7FF) << 21) as i32 >> 9) as u32 | (next & 0x7FF) << 1;
+        format!("bl 0x{:08X}", addr.wrapping_add(4).wrapping_add(offset))
+    } else { "undefined".to_string() }
+}
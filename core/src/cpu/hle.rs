@@ -0,0 +1,416 @@
+use std::f32::consts::PI;
+
+use crate::io::{AccessType, IO};
+use super::CPU;
+use super::registers::{Reg, RegValues};
+
+// Only the IRQ vector is needed, since SWIs never enter the BIOS
+pub fn gen_hle_bios() -> Vec<u8> {
+    let mut bios = vec![0; 0x4000];
+    let mut write = |addr: usize, instr: u32| bios[addr..addr + 4].copy_from_slice(&instr.to_le_bytes());
+    write(0x18, 0xEA000042); // b 0x128
+    // Same IRQ handler as the real BIOS
+    write(0x128, 0xE92D500F); // stmfd sp!, {r0-r3, r12, lr}
+    write(0x12C, 0xE3A00301); // mov r0, #0x04000000
+    write(0x130, 0xE28FE000); // add lr, pc, #0
+    write(0x134, 0xE510F004); // ldr pc, [r0, #-4]
+    write(0x138, 0xE8BD500F); // ldmfd sp!, {r0-r3, r12, lr}
+    write(0x13C, 0xE25EF004); // subs pc, lr, #4
+    bios
+}
+
+impl CPU {
+    // Interrupt flags acknowledged by the game's IRQ handler
+    const BIOS_IF: u32 = 0x03007FF8;
+    const BIOS_CHECKSUM: u32 = 0xBAAE187F;
+
+    pub(super) fn hle_software_interrupt(&mut self, io: &mut IO, function: u8) {
+        match function {
+            0x00 => self.hle_soft_reset(io),
+            0x01 => self.hle_register_ram_reset(io),
+            0x02 => self.write::<u8>(io, AccessType::S, 0x04000301, 0x00),
+            0x03 => self.write::<u8>(io, AccessType::S, 0x04000301, 0x80),
+            0x04 => {
+                let (discard_old, flags) = (self.regs.get_reg(Reg::R0) != 0, self.regs.get_reg(Reg::R1) as u16);
+                self.hle_intr_wait(io, discard_old, flags)
+            },
+            0x05 => self.hle_intr_wait(io, true, 0x1),
+            0x06 => self.hle_div(self.regs.get_reg(Reg::R0) as i32, self.regs.get_reg(Reg::R1) as i32),
+            0x07 => self.hle_div(self.regs.get_reg(Reg::R1) as i32, self.regs.get_reg(Reg::R0) as i32),
+            0x08 => self.regs.set_reg(Reg::R0, (self.regs.get_reg(Reg::R0) as f64).sqrt() as u32),
+            0x09 => {
+                let angle = CPU::arc_tan(self.regs.get_reg(Reg::R0) as i16 as i32);
+                self.regs.set_reg(Reg::R0, angle as u32)
+            },
+            0x0A => {
+                let (x, y) = (self.regs.get_reg(Reg::R0) as i16 as i32, self.regs.get_reg(Reg::R1) as i16 as i32);
+                self.regs.set_reg(Reg::R0, CPU::arc_tan2(x, y) as u16 as u32)
+            },
+            0x0B => self.hle_cpu_set(io),
+            0x0C => self.hle_cpu_fast_set(io),
+            0x0D => self.regs.set_reg(Reg::R0, CPU::BIOS_CHECKSUM),
+            0x0E => self.hle_bg_affine_set(io),
+            0x0F => self.hle_obj_affine_set(io),
+            0x10 => self.hle_bit_unpack(io),
+            0x11 => self.hle_decompress(io, CPU::lz77_uncomp, false),
+            0x12 => self.hle_decompress(io, CPU::lz77_uncomp, true),
+            0x13 => self.hle_decompress(io, CPU::huff_uncomp, true),
+            0x14 => self.hle_decompress(io, CPU::rl_uncomp, false),
+            0x15 => self.hle_decompress(io, CPU::rl_uncomp, true),
+            0x16 => self.hle_decompress(io, CPU::diff_8bit_unfilter, false),
+            0x17 => self.hle_decompress(io, CPU::diff_8bit_unfilter, true),
+            0x18 => self.hle_decompress(io, CPU::diff_16bit_unfilter, true),
+            0x19 => {
+                let bias = self.read::<u16>(io, AccessType::N, 0x04000088);
+                let level = if self.regs.get_reg(Reg::R0) != 0 { 0x200 } else { 0x000 };
+                self.write::<u16>(io, AccessType::N, 0x04000088, bias & !0x3FF | level);
+            },
+            _ => warn!("Unimplemented HLE SWI 0x{:02X}", function),
+        }
+        // Value left on the bus by the real BIOS when returning from a SWI
+        io.set_bios_latch(0xE3A02004);
+    }
+
+    fn hle_repeat_instr(&mut self, io: &mut IO) {
+        if self.regs.get_t() {
+            self.regs.pc = self.regs.pc.wrapping_sub(4);
+            self.fill_thumb_instr_buffer(io);
+        } else {
+            self.regs.pc = self.regs.pc.wrapping_sub(8);
+            self.fill_arm_instr_buffer(io);
+        }
+    }
+
+    fn hle_soft_reset(&mut self, io: &mut IO) {
+        let boot_from_ewram = self.read::<u8>(io, AccessType::N, 0x03007FFA) != 0;
+        for addr in (0x03007E00..0x03008000).step_by(4) { self.write::<u32>(io, AccessType::S, addr, 0) }
+        self.regs = RegValues::_no_bios();
+        if boot_from_ewram { self.regs.pc = 0x02000000 }
+        self.fill_arm_instr_buffer(io);
+    }
+
+    fn hle_register_ram_reset(&mut self, io: &mut IO) {
+        let flags = self.regs.get_reg(Reg::R0);
+        // Forced blank
+        self.write::<u16>(io, AccessType::N, 0x04000000, 0x80);
+        let regions: [&[(u32, u32)]; 8] = [
+            &[(0x02000000, 0x40000)], // EWRAM
+            &[(0x03000000, 0x7E00)], // IWRAM except the stacks
+            &[(0x05000000, 0x400)], // Palette
+            &[(0x06000000, 0x18000)], // VRAM
+            &[(0x07000000, 0x400)], // OAM
+            &[(0x04000120, 0x10), (0x04000134, 0x2C)], // Serial
+            &[(0x04000060, 0x28), (0x04000090, 0x18)], // Sound
+            &[(0x04000004, 0x5C), (0x040000B0, 0x60), (0x04000132, 0x2), (0x04000200, 0x2), (0x04000204, 0x6)], // Other
+        ];
+        for (i, ranges) in regions.iter().enumerate() {
+            if flags >> i & 0x1 == 0 { continue }
+            for (start, len) in ranges.iter() {
+                for addr in (*start..*start + *len).step_by(2) { self.write::<u16>(io, AccessType::S, addr, 0) }
+            }
+        }
+    }
+
+    fn hle_intr_wait(&mut self, io: &mut IO, discard_old: bool, flags: u16) {
+        // No interrupt could ever end the wait
+        if flags == 0 {
+            warn!("HLE IntrWait with no interrupt flags");
+            self.hle_intr_waiting = false;
+            return
+        }
+        let bios_if = self.read::<u16>(io, AccessType::N, CPU::BIOS_IF);
+        if discard_old && !self.hle_intr_waiting {
+            self.write::<u16>(io, AccessType::N, CPU::BIOS_IF, bios_if & !flags);
+        } else if bios_if & flags != 0 {
+            self.write::<u16>(io, AccessType::N, CPU::BIOS_IF, bios_if & !flags);
+            self.hle_intr_waiting = false;
+            return
+        }
+        // Halt until an IRQ, then run the SWI again to check whether it was one of the flags
+        self.hle_intr_waiting = true;
+        self.write::<u16>(io, AccessType::N, 0x04000208, 1);
+        self.write::<u8>(io, AccessType::N, 0x04000301, 0);
+        self.hle_repeat_instr(io);
+    }
+
+    fn hle_div(&mut self, numerator: i32, denominator: i32) {
+        if denominator == 0 {
+            warn!("HLE Div by zero: {} / 0", numerator);
+            self.regs.set_reg(Reg::R0, if numerator < 0 { -1i32 as u32 } else { 1 });
+            self.regs.set_reg(Reg::R1, numerator as u32);
+            self.regs.set_reg(Reg::R3, 1);
+        } else {
+            let quotient = numerator.wrapping_div(denominator);
+            self.regs.set_reg(Reg::R0, quotient as u32);
+            self.regs.set_reg(Reg::R1, numerator.wrapping_rem(denominator) as u32);
+            self.regs.set_reg(Reg::R3, quotient.wrapping_abs() as u32);
+        }
+    }
+
+    // Same polynomial as the real BIOS so results match bit for bit
+    fn arc_tan(tan: i32) -> i32 {
+        let a = -(tan.wrapping_mul(tan) >> 14);
+        let mut b = (0xA9i32.wrapping_mul(a) >> 14) + 0x390;
+        for constant in [0x91C, 0xFB6, 0x16AA, 0x2081, 0x3651, 0xA2F9].iter() {
+            b = (b.wrapping_mul(a) >> 14) + constant;
+        }
+        tan.wrapping_mul(b) >> 16
+    }
+
+    fn arc_tan2(x: i32, y: i32) -> i32 {
+        if y == 0 { return if x >= 0 { 0x0000 } else { 0x8000 } }
+        if x == 0 { return if y >= 0 { 0x4000 } else { 0xC000 } }
+        if y >= 0 {
+            if x >= 0 && x >= y { CPU::arc_tan((y << 14) / x) }
+            else if x < 0 && -x >= y { CPU::arc_tan((y << 14) / x) + 0x8000 }
+            else { 0x4000 - CPU::arc_tan((x << 14) / y) }
+        } else if x <= 0 && -x > -y { CPU::arc_tan((y << 14) / x) + 0x8000 }
+        else if x > 0 && x >= -y { CPU::arc_tan((y << 14) / x) + 0x10000 }
+        else { 0xC000 - CPU::arc_tan((x << 14) / y) }
+    }
+
+    fn hle_cpu_set(&mut self, io: &mut IO) {
+        let (mut src, mut dest) = (self.regs.get_reg(Reg::R0), self.regs.get_reg(Reg::R1));
+        let control = self.regs.get_reg(Reg::R2);
+        let (count, fill, transfer_32) = (control & 0x1F_FFFF, control >> 24 & 0x1 != 0, control >> 26 & 0x1 != 0);
+        // The BIOS refuses to copy from itself
+        if src >> 25 == 0 { return }
+        if transfer_32 {
+            src &= !0x3;
+            dest &= !0x3;
+            let fill_value = self.read::<u32>(io, AccessType::N, src);
+            for _ in 0..count {
+                let value = if fill { fill_value } else { self.read::<u32>(io, AccessType::S, src) };
+                self.write::<u32>(io, AccessType::S, dest, value);
+                if !fill { src = src.wrapping_add(4) }
+                dest = dest.wrapping_add(4);
+            }
+        } else {
+            src &= !0x1;
+            dest &= !0x1;
+            let fill_value = self.read::<u16>(io, AccessType::N, src);
+            for _ in 0..count {
+                let value = if fill { fill_value } else { self.read::<u16>(io, AccessType::S, src) };
+                self.write::<u16>(io, AccessType::S, dest, value);
+                if !fill { src = src.wrapping_add(2) }
+                dest = dest.wrapping_add(2);
+            }
+        }
+    }
+
+    fn hle_cpu_fast_set(&mut self, io: &mut IO) {
+        let (mut src, mut dest) = (self.regs.get_reg(Reg::R0) & !0x3, self.regs.get_reg(Reg::R1) & !0x3);
+        let control = self.regs.get_reg(Reg::R2);
+        // Always transfers blocks of 8 words
+        let (count, fill) = (((control & 0x1F_FFFF) + 7) & !0x7, control >> 24 & 0x1 != 0);
+        if src >> 25 == 0 { return }
+        let fill_value = self.read::<u32>(io, AccessType::N, src);
+        for _ in 0..count {
+            let value = if fill { fill_value } else { self.read::<u32>(io, AccessType::S, src) };
+            self.write::<u32>(io, AccessType::S, dest, value);
+            if !fill { src = src.wrapping_add(4) }
+            dest = dest.wrapping_add(4);
+        }
+    }
+
+    fn hle_bg_affine_set(&mut self, io: &mut IO) {
+        let (mut src, mut dest) = (self.regs.get_reg(Reg::R0), self.regs.get_reg(Reg::R1));
+        for _ in 0..self.regs.get_reg(Reg::R2) {
+            let origin_x = self.read::<u32>(io, AccessType::N, src) as i32 as f32 / 256.0;
+            let origin_y = self.read::<u32>(io, AccessType::S, src.wrapping_add(4)) as i32 as f32 / 256.0;
+            let display_x = self.read::<u16>(io, AccessType::S, src.wrapping_add(8)) as i16 as f32;
+            let display_y = self.read::<u16>(io, AccessType::S, src.wrapping_add(10)) as i16 as f32;
+            let scale_x = self.read::<u16>(io, AccessType::S, src.wrapping_add(12)) as i16 as f32 / 256.0;
+            let scale_y = self.read::<u16>(io, AccessType::S, src.wrapping_add(14)) as i16 as f32 / 256.0;
+            let theta = (self.read::<u16>(io, AccessType::S, src.wrapping_add(16)) >> 8) as f32 / 128.0 * PI;
+            src = src.wrapping_add(20);
+
+            let (pa, pb) = (theta.cos() * scale_x, -theta.sin() * scale_x);
+            let (pc, pd) = (theta.sin() * scale_y, theta.cos() * scale_y);
+            let x = origin_x - (pa * display_x + pb * display_y);
+            let y = origin_y - (pc * display_x + pd * display_y);
+            for (i, param) in [pa, pb, pc, pd].iter().enumerate() {
+                self.write::<u16>(io, AccessType::S, dest.wrapping_add(i as u32 * 2), (param * 256.0) as i16 as u16);
+            }
+            self.write::<u32>(io, AccessType::S, dest.wrapping_add(8), (x * 256.0) as i32 as u32);
+            self.write::<u32>(io, AccessType::S, dest.wrapping_add(12), (y * 256.0) as i32 as u32);
+            dest = dest.wrapping_add(16);
+        }
+    }
+
+    fn hle_obj_affine_set(&mut self, io: &mut IO) {
+        let (mut src, mut dest) = (self.regs.get_reg(Reg::R0), self.regs.get_reg(Reg::R1));
+        // 2 for a packed array of parameters, 8 when writing straight into OAM
+        let stride = self.regs.get_reg(Reg::R3);
+        for _ in 0..self.regs.get_reg(Reg::R2) {
+            let scale_x = self.read::<u16>(io, AccessType::N, src) as i16 as f32 / 256.0;
+            let scale_y = self.read::<u16>(io, AccessType::S, src.wrapping_add(2)) as i16 as f32 / 256.0;
+            let theta = (self.read::<u16>(io, AccessType::S, src.wrapping_add(4)) >> 8) as f32 / 128.0 * PI;
+            src = src.wrapping_add(8);
+
+            let (pa, pb) = (theta.cos() * scale_x, -theta.sin() * scale_x);
+            let (pc, pd) = (theta.sin() * scale_y, theta.cos() * scale_y);
+            for param in [pa, pb, pc, pd].iter() {
+                self.write::<u16>(io, AccessType::S, dest, (param * 256.0) as i16 as u16);
+                dest = dest.wrapping_add(stride);
+            }
+        }
+    }
+
+    fn hle_bit_unpack(&mut self, io: &mut IO) {
+        let (src, mut dest, info) = (self.regs.get_reg(Reg::R0), self.regs.get_reg(Reg::R1), self.regs.get_reg(Reg::R2));
+        let len = self.read::<u16>(io, AccessType::N, info) as u32;
+        let src_width = self.read::<u8>(io, AccessType::S, info.wrapping_add(2)) as u32;
+        let dest_width = self.read::<u8>(io, AccessType::S, info.wrapping_add(3)) as u32;
+        let offset = self.read::<u32>(io, AccessType::S, info.wrapping_add(4));
+        if ![1, 2, 4, 8].contains(&src_width) || ![1, 2, 4, 8, 16, 32].contains(&dest_width) {
+            warn!("Invalid BitUnPack widths: {} -> {}", src_width, dest_width);
+            return
+        }
+        let (data_offset, offset_zero) = (offset & 0x7FFF_FFFF, offset >> 31 != 0);
+        let dest_mask = ((1u64 << dest_width) - 1) as u32;
+
+        let mut value = 0;
+        let mut value_bits = 0;
+        for i in 0..len {
+            let byte = self.read::<u8>(io, AccessType::S, src.wrapping_add(i)) as u32;
+            for shift in (0..8).step_by(src_width as usize) {
+                let mut unit = byte >> shift & ((1 << src_width) - 1);
+                if unit != 0 || offset_zero { unit = unit.wrapping_add(data_offset) }
+                value |= (unit & dest_mask) << value_bits;
+                value_bits += dest_width;
+                if value_bits == 32 {
+                    self.write::<u32>(io, AccessType::S, dest, value);
+                    dest = dest.wrapping_add(4);
+                    value = 0;
+                    value_bits = 0;
+                }
+            }
+        }
+    }
+
+    // Decompresses the whole stream first, then writes bytes to WRAM or halfwords to VRAM
+    fn hle_decompress(&mut self, io: &mut IO, uncomp: fn(&mut CPU, &mut IO, u32, usize) -> Vec<u8>, vram: bool) {
+        let (src, dest) = (self.regs.get_reg(Reg::R0), self.regs.get_reg(Reg::R1));
+        let size = (self.read::<u32>(io, AccessType::N, src) >> 8) as usize;
+        let mut data = uncomp(self, io, src, size);
+        data.truncate(size);
+        if vram {
+            for (i, halfword) in data.chunks(2).enumerate() {
+                let value = halfword[0] as u16 | (halfword.get(1).copied().unwrap_or(0) as u16) << 8;
+                self.write::<u16>(io, AccessType::S, dest.wrapping_add(i as u32 * 2), value);
+            }
+        } else {
+            for (i, byte) in data.iter().enumerate() {
+                self.write::<u8>(io, AccessType::S, dest.wrapping_add(i as u32), *byte)
+            }
+        }
+    }
+
+    fn lz77_uncomp(&mut self, io: &mut IO, mut src: u32, size: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(size);
+        src = src.wrapping_add(4);
+        while data.len() < size {
+            let flags = self.read::<u8>(io, AccessType::S, src);
+            src = src.wrapping_add(1);
+            for bit in (0..8).rev() {
+                if data.len() >= size { break }
+                if flags >> bit & 0x1 == 0 {
+                    data.push(self.read::<u8>(io, AccessType::S, src));
+                    src = src.wrapping_add(1);
+                } else {
+                    let byte0 = self.read::<u8>(io, AccessType::S, src);
+                    let byte1 = self.read::<u8>(io, AccessType::S, src.wrapping_add(1));
+                    src = src.wrapping_add(2);
+                    let disp = ((byte0 as usize & 0xF) << 8 | byte1 as usize) + 1;
+                    let len = ((byte0 >> 4) as usize + 3).min(size - data.len());
+                    for _ in 0..len {
+                        data.push(data.get(data.len().wrapping_sub(disp)).copied().unwrap_or(0));
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    fn huff_uncomp(&mut self, io: &mut IO, src: u32, size: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(size);
+        let data_bits = self.read::<u8>(io, AccessType::S, src) as u32 & 0xF;
+        if data_bits != 4 && data_bits != 8 {
+            warn!("Invalid Huffman data size: {}", data_bits);
+            return data
+        }
+        let tree_size = (self.read::<u8>(io, AccessType::S, src.wrapping_add(4)) as u32 + 1) * 2;
+        let (root_addr, tree_end) = (src.wrapping_add(5), src.wrapping_add(4 + tree_size));
+        let mut bitstream_addr = tree_end;
+
+        let mut node_addr = root_addr;
+        let mut node = self.read::<u8>(io, AccessType::S, node_addr);
+        let mut value = 0u32;
+        let mut value_bits = 0;
+        while data.len() < size {
+            let bits = self.read::<u32>(io, AccessType::S, bitstream_addr);
+            bitstream_addr = bitstream_addr.wrapping_add(4);
+            for bit in (0..32).rev() {
+                let right = bits >> bit & 0x1 != 0;
+                let child_addr = (node_addr & !0x1).wrapping_add((node as u32 & 0x3F) * 2 + 2 + right as u32);
+                let end_flag = if right { 0x40 } else { 0x80 };
+                // Children always come after their parent, so staying inside the tree means every walk ends
+                if child_addr >= tree_end {
+                    warn!("Invalid Huffman tree node at 0x{:08X}", node_addr);
+                    return data
+                }
+                if node & end_flag != 0 {
+                    value |= (self.read::<u8>(io, AccessType::S, child_addr) as u32 & ((1 << data_bits) - 1)) << value_bits;
+                    value_bits += data_bits;
+                    if value_bits == 32 {
+                        data.extend_from_slice(&value.to_le_bytes());
+                        value = 0;
+                        value_bits = 0;
+                        if data.len() >= size { break }
+                    }
+                    node_addr = root_addr;
+                } else { node_addr = child_addr }
+                node = self.read::<u8>(io, AccessType::S, node_addr);
+            }
+        }
+        data
+    }
+
+    fn rl_uncomp(&mut self, io: &mut IO, mut src: u32, size: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(size);
+        src = src.wrapping_add(4);
+        while data.len() < size {
+            let flag = self.read::<u8>(io, AccessType::S, src);
+            src = src.wrapping_add(1);
+            if flag & 0x80 != 0 {
+                let byte = self.read::<u8>(io, AccessType::S, src);
+                src = src.wrapping_add(1);
+                let len = ((flag & 0x7F) as usize + 3).min(size - data.len());
+                data.resize(data.len() + len, byte);
+            } else {
+                for _ in 0..((flag & 0x7F) as usize + 1).min(size - data.len()) {
+                    data.push(self.read::<u8>(io, AccessType::S, src));
+                    src = src.wrapping_add(1);
+                }
+            }
+        }
+        data
+    }
+
+    fn diff_8bit_unfilter(&mut self, io: &mut IO, src: u32, size: usize) -> Vec<u8> {
+        let mut value = 0u8;
+        (0..size as u32).map(|i| {
+            value = value.wrapping_add(self.read::<u8>(io, AccessType::S, src.wrapping_add(4 + i)));
+            value
+        }).collect()
+    }
+
+    fn diff_16bit_unfilter(&mut self, io: &mut IO, src: u32, size: usize) -> Vec<u8> {
+        let mut value = 0u16;
+        (0..size as u32).step_by(2).flat_map(|i| {
+            value = value.wrapping_add(self.read::<u16>(io, AccessType::S, src.wrapping_add(4 + i)));
+            value.to_le_bytes().to_vec()
+        }).collect()
+    }
+}
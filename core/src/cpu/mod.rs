@@ -4,15 +4,22 @@ mod arm;
 mod thumb;
 mod registers;
 mod luts;
+mod hle;
+mod disasm;
 
 use crate::io::{AccessType, Cycle, IO, MemoryHandler, MemoryValue};
-use registers::{Mode, Reg, RegValues};
+use registers::{Reg, RegValues};
+pub use registers::Mode;
+pub use hle::gen_hle_bios;
+pub use disasm::{disassemble_arm, disassemble_thumb};
 
 pub struct CPU {
     regs: RegValues,
     instr_buffer: [u32; 2],
     next_access_type: AccessType,
     do_internal: bool,
+    hle_bios: bool,
+    hle_intr_waiting: bool,
 
     condition_lut: [bool; 256],
     arm_lut: [instructions::InstructionHandler<u32>; 4096],
@@ -20,12 +27,14 @@ pub struct CPU {
 }
 
 impl CPU {
-    pub fn new(bios: bool, io: &mut IO) -> CPU {
+    pub fn new(bios: bool, hle_bios: bool, io: &mut IO) -> CPU {
         let mut cpu = CPU {
             regs: if bios { RegValues::new() } else { RegValues::_no_bios() },
             instr_buffer: [0; 2],
             next_access_type: AccessType::N,
             do_internal: false,
+            hle_bios,
+            hle_intr_waiting: false,
 
             condition_lut: luts::gen_condition_table(),
             arm_lut: arm::gen_lut(),
@@ -40,9 +49,65 @@ impl CPU {
         else { self.emulate_arm_instr(io) }
     }
 
+    // Address of the instruction that executes next, since pc points at the one after it in between instructions
+    pub fn next_instr_addr(&self) -> u32 {
+        self.regs.pc.wrapping_sub(if self.regs.get_t() { 2 } else { 4 })
+    }
+
+    pub fn in_thumb(&self) -> bool { self.regs.get_t() }
+
+    // Opcode and disassembly of the instruction that executes next, already in the pipeline
+    pub fn next_instr(&self) -> (u32, String) {
+        let addr = self.next_instr_addr();
+        let instr = self.instr_buffer[0];
+        if self.regs.get_t() {
+            (instr, disassemble_thumb(instr as u16, self.instr_buffer[1] as u16, addr))
+        } else { (instr, disassemble_arm(instr, addr)) }
+    }
+
+    // R15 is the address of the next instruction
+    pub fn get_reg(&self, reg: usize) -> u32 {
+        if reg == 15 { self.next_instr_addr() } else { self.regs.get_reg_i(reg as u32) }
+    }
+
+    pub fn set_reg(&mut self, io: &mut IO, reg: usize, value: u32) {
+        if reg == 15 {
+            self.regs.pc = value;
+            self.refill_instr_buffer(io);
+        } else { self.regs.set_reg_i(reg as u32, value) }
+    }
+
+    pub fn get_cpsr(&self) -> u32 { self.regs.get_reg(Reg::CPSR) }
+
+    pub fn get_banked_reg(&self, mode: Mode, reg: usize) -> u32 { self.regs.get_banked_reg(mode, reg) }
+
+    // Values with an invalid mode are ignored
+    pub fn set_cpsr(&mut self, io: &mut IO, value: u32) {
+        let next_instr_addr = self.next_instr_addr();
+        if self.regs.set_cpsr(value) {
+            self.regs.pc = next_instr_addr;
+            self.refill_instr_buffer(io);
+        }
+    }
+
+    pub fn get_spsr(&self, mode: Mode) -> Option<u32> { self.regs.get_spsr(mode) }
+
+    pub fn set_spsr(&mut self, mode: Mode, value: u32) { self.regs.set_spsr(mode, value) }
+
+    fn refill_instr_buffer(&mut self, io: &mut IO) {
+        if self.regs.get_t() { self.fill_thumb_instr_buffer(io) } else { self.fill_arm_instr_buffer(io) }
+    }
+
     pub fn read<T>(&mut self, io: &mut IO, access_type: AccessType, addr: u32) -> T where T: MemoryValue {
+        io.check_watchpoints(addr, std::mem::size_of::<T>() as u32, false);
+        self.fetch(io, access_type, addr)
+    }
+
+    // Instruction fetches don't trigger watchpoints
+    pub fn fetch<T>(&mut self, io: &mut IO, access_type: AccessType, addr: u32) -> T where T: MemoryValue {
         io.setup_openbus(self.regs.pc, self.regs.get_t(), &self.instr_buffer);
         let value = io.read::<T>(addr);
+        io.finish_read(addr, std::mem::size_of::<T>() as u32);
         io.inc_clock(self.next_access_type.into(), addr, match std::mem::size_of::<T>() {
             1 => 0,
             2 => 1,
@@ -54,6 +119,7 @@ impl CPU {
     }
 
     pub fn write<T>(&mut self, io: &mut IO, access_type: AccessType, addr: u32, value: T) where T: MemoryValue {
+        io.check_watchpoints(addr, std::mem::size_of::<T>() as u32, true);
         io.setup_openbus(self.regs.pc, self.regs.get_t(), &self.instr_buffer);
         io.inc_clock(self.next_access_type.into(), addr, match std::mem::size_of::<T>() {
             1 => 0,
@@ -67,7 +133,7 @@ impl CPU {
 
     pub fn instruction_prefetch<T>(&mut self, io: &mut IO, access_type: AccessType) where T: MemoryValue {
         // Internal Cycle merges with instruction prefetch
-        self.instr_buffer[1] = num::cast::<T, u32>(self.read::<T>(io, access_type, self.regs.pc)).unwrap();
+        self.instr_buffer[1] = num::cast::<T, u32>(self.fetch::<T>(io, access_type, self.regs.pc)).unwrap();
         self.do_internal = false;
     }
 
@@ -79,12 +145,13 @@ impl CPU {
 
     pub fn handle_irq(&mut self, io: &mut IO) {
         if self.regs.get_i() || !io.interrupts_requested() { return }
+        io.record_irq();
         self.regs.change_mode(Mode::IRQ);
         let lr = if self.regs.get_t() {
-            self.read::<u16>(io, AccessType::N, self.regs.pc);
+            self.fetch::<u16>(io, AccessType::N, self.regs.pc);
             self.regs.pc.wrapping_sub(2).wrapping_add(4)
         } else {
-            self.read::<u32>(io, AccessType::N, self.regs.pc);
+            self.fetch::<u32>(io, AccessType::N, self.regs.pc);
             self.regs.pc.wrapping_sub(4).wrapping_add(4)
         };
         self.regs.set_reg(Reg::R14, lr);
@@ -220,3 +287,5 @@ impl CPU {
         }
     }
 }
+
+impl_save_state!(CPU { regs, instr_buffer, next_access_type, do_internal, hle_intr_waiting });
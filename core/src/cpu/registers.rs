@@ -19,7 +19,7 @@ pub enum Reg {
     CPSR,
     SPSR,
 }
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Mode {
     USR = 0b10000,
     FIQ = 0b10001,
@@ -30,6 +30,13 @@ pub enum Mode {
     UND = 0b11011,
 }
 
+impl Mode {
+    pub fn from_bits(bits: u32) -> Option<Mode> {
+        [Mode::USR, Mode::FIQ, Mode::IRQ, Mode::SVC, Mode::ABT, Mode::SYS, Mode::UND].iter()
+            .copied().find(|mode| *mode as u32 == bits & 0x1F)
+    }
+}
+
 bitflags! {
     struct StatusReg: u32 {
         const N =  0x80000000;
@@ -169,6 +176,45 @@ impl RegValues {
         }
     }
 
+    // Only takes values with a valid mode
+    pub fn set_cpsr(&mut self, value: u32) -> bool {
+        if Mode::from_bits(value).is_none() { return false }
+        self.cpsr.bits = value;
+        true
+    }
+
+    // Banked SPSR of any mode, not just the current one
+    pub fn get_spsr(&self, mode: Mode) -> Option<u32> {
+        RegValues::spsr_index(mode).map(|i| self.spsr[i].bits())
+    }
+
+    pub fn set_spsr(&mut self, mode: Mode, value: u32) {
+        if let Some(i) = RegValues::spsr_index(mode) { self.spsr[i] = StatusReg::from_bits_truncate(value) }
+    }
+
+    // R8-R14 as seen from any mode, not just the current one
+    pub fn get_banked_reg(&self, mode: Mode, reg: usize) -> u32 {
+        match (mode, reg) {
+            (Mode::FIQ, 8 ..= 14) => self.fiq[reg - 8],
+            (Mode::SVC, 13 ..= 14) => self.svc[reg - 13],
+            (Mode::ABT, 13 ..= 14) => self.abt[reg - 13],
+            (Mode::IRQ, 13 ..= 14) => self.irq[reg - 13],
+            (Mode::UND, 13 ..= 14) => self.und[reg - 13],
+            _ => self.usr[reg],
+        }
+    }
+
+    fn spsr_index(mode: Mode) -> Option<usize> {
+        match mode {
+            Mode::FIQ => Some(0),
+            Mode::SVC => Some(1),
+            Mode::ABT => Some(2),
+            Mode::IRQ => Some(3),
+            Mode::UND => Some(4),
+            Mode::USR | Mode::SYS => None,
+        }
+    }
+
     pub fn restore_cpsr(&mut self) {
         self.cpsr.bits = self.get_reg(Reg::SPSR);
     }
@@ -228,3 +274,6 @@ impl RegValues {
     pub fn set_t(&mut self, value: bool) { self.cpsr.set(StatusReg::T, value) }
     pub fn set_mode(&mut self, mode: Mode) { self.cpsr.set_mode(mode) }
 }
+
+impl_save_state_bitflags!(StatusReg);
+impl_save_state!(RegValues { usr, fiq, svc, abt, irq, und, pc, cpsr, spsr } where |regs| Mode::from_bits(regs.cpsr.bits).is_some());
@@ -9,10 +9,10 @@ use crate::io::AccessType;
 impl CPU {
     pub(super) fn fill_thumb_instr_buffer(&mut self, io: &mut IO) {
         self.regs.pc &= !0x1;
-        self.instr_buffer[0] = self.read::<u16>(io, AccessType::S, self.regs.pc & !0x1) as u32;
+        self.instr_buffer[0] = self.fetch::<u16>(io, AccessType::S, self.regs.pc & !0x1) as u32;
         self.regs.pc = self.regs.pc.wrapping_add(2);
 
-        self.instr_buffer[1] = self.read::<u16>(io, AccessType::S, self.regs.pc & !0x1) as u32;
+        self.instr_buffer[1] = self.fetch::<u16>(io, AccessType::S, self.regs.pc & !0x1) as u32;
     }
 
     pub(super) fn emulate_thumb_instr(&mut self, io: &mut IO) {
@@ -447,6 +447,10 @@ impl CPU {
     // THUMB.17: software interrupt
     fn thumb_software_interrupt(&mut self, io: &mut IO, instr: u16) {
         assert_eq!(instr >> 8 & 0xFF, 0b11011111);
+        if self.hle_bios {
+            self.instruction_prefetch::<u16>(io, AccessType::S);
+            return self.hle_software_interrupt(io, instr as u8)
+        }
         self.instruction_prefetch::<u16>(io, AccessType::N);
         self.regs.change_mode(Mode::SVC);
         self.regs.set_reg(Reg::R14, self.regs.pc.wrapping_sub(2));
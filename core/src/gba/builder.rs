@@ -0,0 +1,186 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use flume::{Receiver, Sender};
+
+use crate::cpu::{self, CPU};
+use crate::io::IO;
+use crate::savestate;
+use super::movie::ActiveMovie;
+use super::{GBA, AudioSink, Cheat, Movie, NullSink, DebugSpecification, DebugWindows, KEYINPUT, LinkTransport};
+
+// The emulator along with the pixels it renders into and the spec for the debug windows it sends
+pub type Built = (GBA, Arc<Mutex<Vec<u16>>>, Arc<Mutex<DebugSpecification>>);
+
+pub struct GBABuilder<'a> {
+    rom: &'a [u8],
+    bios: Option<&'a [u8]>,
+    save_data: Option<&'a [u8]>,
+    save_file: Option<PathBuf>,
+    render_tx: Option<Sender<DebugWindows>>,
+    keypad_rx: Option<Receiver<(KEYINPUT, bool)>>,
+    audio: Box<dyn AudioSink>,
+    link: Option<Box<dyn LinkTransport>>,
+    capture_debug_messages: bool,
+    trace: Option<PathBuf>,
+    record_audio: Option<(PathBuf, bool)>,
+    cheats: Vec<Cheat>,
+    cheat_file: Option<PathBuf>,
+    record_movie: bool,
+    movie: Option<Movie>,
+}
+
+impl<'a> GBABuilder<'a> {
+    const BIOS_SIZE: usize = 0x4000;
+    const MAX_ROM_SIZE: usize = 0x0200_0000;
+
+    pub fn new(rom: &'a [u8]) -> GBABuilder<'a> {
+        GBABuilder {
+            rom,
+            bios: None,
+            save_data: None,
+            save_file: None,
+            render_tx: None,
+            keypad_rx: None,
+            audio: Box::new(NullSink),
+            link: None,
+            capture_debug_messages: false,
+            trace: None,
+            record_audio: None,
+            cheats: Vec::new(),
+            cheat_file: None,
+            record_movie: false,
+            movie: None,
+        }
+    }
+
+    // Without a BIOS, SWIs are emulated at a high level
+    pub fn bios(mut self, bios: &'a [u8]) -> Self { self.bios = Some(bios); self }
+
+    pub fn save_data(mut self, save_data: &'a [u8]) -> Self { self.save_data = Some(save_data); self }
+
+    // Cart backup is written back to this file whenever it changes
+    pub fn save_file(mut self, save_file: PathBuf) -> Self { self.save_file = Some(save_file); self }
+
+    pub fn render_tx(mut self, render_tx: Sender<DebugWindows>) -> Self { self.render_tx = Some(render_tx); self }
+
+    pub fn keypad_rx(mut self, keypad_rx: Receiver<(KEYINPUT, bool)>) -> Self { self.keypad_rx = Some(keypad_rx); self }
+
+    // Defaults to discarding all samples
+    pub fn audio_sink(mut self, audio: Box<dyn AudioSink>) -> Self { self.audio = audio; self }
+
+    pub fn link(mut self, link: Box<dyn LinkTransport>) -> Self { self.link = Some(link); self }
+
+    // Keeps mGBA debug messages around for GBA::take_debug_messages
+    pub fn capture_debug_messages(mut self) -> Self { self.capture_debug_messages = true; self }
+
+    // Traces from the first instruction, see GBA::start_trace
+    pub fn trace(mut self, path: PathBuf) -> Self { self.trace = Some(path); self }
+
+    // Records audio from power on, see GBA::start_audio_recording
+    pub fn record_audio(mut self, path: PathBuf, channels: bool) -> Self { self.record_audio = Some((path, channels)); self }
+
+    pub fn cheats(mut self, cheats: Vec<Cheat>) -> Self { self.cheats = cheats; self }
+
+    // Adds the cheats listed in this file, if it exists, see Cheat::parse_list
+    pub fn cheat_file(mut self, cheat_file: PathBuf) -> Self { self.cheat_file = Some(cheat_file); self }
+
+    // Records inputs from power on, see GBA::finish_movie
+    pub fn record_movie(mut self) -> Self { self.record_movie = true; self }
+
+    // Starts with the movie's save data instead, which isn't written back to the save file
+    pub fn play_movie(mut self, movie: Movie) -> Self { self.movie = Some(movie); self }
+
+    pub fn build(self) -> Result<Built, GBAError> {
+        let (bios, hle_bios) = match self.bios {
+            Some(bios) if bios.len() != GBABuilder::BIOS_SIZE => return Err(GBAError::InvalidBiosSize(bios.len())),
+            Some(bios) => (bios.to_vec(), false),
+            None => (cpu::gen_hle_bios(), true),
+        };
+        if self.rom.is_empty() || self.rom.len() > GBABuilder::MAX_ROM_SIZE {
+            return Err(GBAError::InvalidRomSize(self.rom.len()))
+        }
+        let rom_checksum = savestate::crc32(self.rom);
+        let (save_data, save_file) = match &self.movie {
+            Some(movie) if movie.rom_checksum() != rom_checksum => return Err(GBAError::MovieRomMismatch),
+            Some(movie) if movie.hle_bios() != hle_bios => return Err(GBAError::MovieBiosMismatch(movie.hle_bios())),
+            Some(movie) if movie.save_data().is_empty() => (None, None),
+            Some(movie) => (Some(movie.save_data().to_vec()), None),
+            None => (self.save_data.map(|save_data| save_data.to_vec()), self.save_file),
+        };
+
+        let (mut io, pixels, debug_windows_spec) = IO::new(
+            bios, self.rom.to_vec(), save_data.clone(), save_file, self.render_tx, self.keypad_rx, self.audio,
+        );
+        if let Some(link) = self.link { io.set_link(link) }
+        if self.capture_debug_messages { io.capture_debug_messages() }
+        let mut cheats = self.cheats;
+        if let Some(text) = self.cheat_file.and_then(|path| fs::read_to_string(path).ok()) {
+            cheats.extend(Cheat::parse_list(&text).unwrap_or_else(|err| { warn!("Ignoring cheats: {}", err); Vec::new() }));
+        }
+        io.set_cheats(cheats);
+        let movie = match self.movie {
+            Some(movie) => {
+                io.set_rtc_date_time(movie.rtc_date_time());
+                Some(ActiveMovie::play(movie))
+            },
+            None if self.record_movie =>
+                Some(ActiveMovie::record(Movie::new(rom_checksum, hle_bios, save_data.unwrap_or_default(), io.get_rtc_date_time()))),
+            None => None,
+        };
+        let mut gba = GBA {
+            cpu: CPU::new(false, hle_bios, &mut io),
+            io,
+            next_frame_cycle: 0,
+            rom_checksum,
+            rewind_buffer: None,
+            breakpoints: BTreeSet::new(),
+            trace: None,
+            timeline: None,
+            movie,
+        };
+        if let Some(path) = self.trace {
+            gba.start_trace(&path).map_err(|err| GBAError::FileAccess(path, err))?;
+        }
+        if let Some((path, channels)) = self.record_audio {
+            gba.start_audio_recording(&path, channels).map_err(|err| GBAError::FileAccess(path, err))?;
+        }
+        Ok((gba, pixels, debug_windows_spec))
+    }
+}
+
+#[derive(Debug)]
+pub enum GBAError {
+    FileAccess(PathBuf, std::io::Error),
+    InvalidRomExtension(PathBuf),
+    InvalidBiosSize(usize),
+    InvalidRomSize(usize),
+    MovieRomMismatch,
+    // Whether the movie was recorded without a BIOS dump
+    MovieBiosMismatch(bool),
+}
+
+impl fmt::Display for GBAError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GBAError::FileAccess(path, err) => write!(f, "Unable to access {}: {}", path.display(), err),
+            GBAError::InvalidRomExtension(path) => write!(f, "{} is not a .gba file", path.display()),
+            GBAError::InvalidBiosSize(size) => write!(f, "BIOS must be 0x4000 bytes, got 0x{:X}", size),
+            GBAError::InvalidRomSize(size) => write!(f, "ROM must be between 1 and 0x2000000 bytes, got 0x{:X}", size),
+            GBAError::MovieRomMismatch => write!(f, "Movie was recorded with a different ROM"),
+            GBAError::MovieBiosMismatch(true) => write!(f, "Movie was recorded without a BIOS"),
+            GBAError::MovieBiosMismatch(false) => write!(f, "Movie was recorded with a BIOS"),
+        }
+    }
+}
+
+impl std::error::Error for GBAError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GBAError::FileAccess(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
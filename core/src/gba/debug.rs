@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+
+use crate::io::MemoryHandler;
+use super::{GBA, CPUMode, Watchpoint, disassemble_arm, disassemble_thumb};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    Step,
+    Breakpoint(u32),
+    // Address that was accessed, which can be anywhere in the watched range
+    Watchpoint(Watchpoint, u32),
+}
+
+impl GBA {
+    // Runs one instruction, or until the next event when halted
+    pub fn step(&mut self) -> StopReason {
+        self.debug_step().unwrap_or(StopReason::Step)
+    }
+
+    // Returns None if the frame finished without hitting a breakpoint or watchpoint, otherwise the
+    // next call continues the same frame
+    pub fn emulate_frame_debug(&mut self) -> Option<StopReason> {
+        loop {
+            if let Some(stop_reason) = self.debug_step() { return Some(stop_reason) }
+            if self.io.get_cycle() >= self.next_frame_cycle { return None }
+        }
+    }
+
+    // Breakpoints are checked after stepping so that continuing from one doesn't hit it again
+    fn debug_step(&mut self) -> Option<StopReason> {
+        if self.io.get_cycle() >= self.next_frame_cycle { self.start_frame() }
+        self.emulate_step();
+        if self.io.get_cycle() >= self.next_frame_cycle { self.end_frame() }
+
+        if let Some((watchpoint, addr)) = self.io.take_watchpoint_hit() {
+            return Some(StopReason::Watchpoint(watchpoint, addr))
+        }
+        let addr = self.cpu.next_instr_addr();
+        if self.breakpoints.contains(&addr) { Some(StopReason::Breakpoint(addr)) } else { None }
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u32) { self.breakpoints.insert(addr); }
+
+    pub fn remove_breakpoint(&mut self, addr: u32) -> bool { self.breakpoints.remove(&addr) }
+
+    pub fn get_breakpoints(&self) -> &BTreeSet<u32> { &self.breakpoints }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) { self.io.add_watchpoint(watchpoint) }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool { self.io.remove_watchpoint(watchpoint) }
+
+    pub fn get_watchpoints(&self) -> &[Watchpoint] { self.io.get_watchpoints() }
+
+    // Registers of the current mode, with R15 being the address of the next instruction
+    pub fn get_reg(&self, reg: usize) -> u32 { self.cpu.get_reg(reg) }
+
+    pub fn set_reg(&mut self, reg: usize, value: u32) { self.cpu.set_reg(&mut self.io, reg, value) }
+
+    pub fn get_cpsr(&self) -> u32 { self.cpu.get_cpsr() }
+
+    // Registers below R8 and R15 aren't banked
+    pub fn get_banked_reg(&self, mode: CPUMode, reg: usize) -> u32 { self.cpu.get_banked_reg(mode, reg) }
+
+    pub fn set_cpsr(&mut self, value: u32) { self.cpu.set_cpsr(&mut self.io, value) }
+
+    // None for modes without an SPSR
+    pub fn get_spsr(&self, mode: CPUMode) -> Option<u32> { self.cpu.get_spsr(mode) }
+
+    pub fn set_spsr(&mut self, mode: CPUMode, value: u32) { self.cpu.set_spsr(mode, value) }
+
+    pub fn in_thumb(&self) -> bool { self.cpu.in_thumb() }
+
+    // Decodes whatever is in memory at addr, which may not be code
+    pub fn disassemble(&self, addr: u32, thumb: bool) -> String {
+        if thumb {
+            disassemble_thumb(self.read_mem_u16(addr), self.read_mem_u16(addr.wrapping_add(2)), addr)
+        } else { disassemble_arm(self.read_mem_u32(addr), addr) }
+    }
+
+    // Where execution continues after the next instruction if it's a BL, for stepping over calls
+    pub fn call_return_addr(&self) -> Option<u32> {
+        let addr = self.cpu.next_instr_addr();
+        if self.is_call(addr, self.in_thumb()) { Some(addr.wrapping_add(4)) } else { None }
+    }
+
+    fn is_call(&self, addr: u32, thumb: bool) -> bool {
+        if thumb {
+            self.read_mem_u16(addr) & 0xF800 == 0xF000 && self.read_mem_u16(addr.wrapping_add(2)) & 0xF800 == 0xF800
+        } else { self.read_mem_u32(addr) & 0x0F00_0000 == 0x0B00_0000 }
+    }
+
+    // Addresses of the BLs that led here, innermost first. LR and the words on the stack that
+    // point right after a BL are taken to be return addresses, so this is only a best guess.
+    pub fn call_stack(&self) -> Vec<u32> {
+        const MAX_STACK_WORDS: u32 = 0x100;
+        let return_addrs = std::iter::once(self.get_reg(14)).chain((0..MAX_STACK_WORDS)
+            .map(|i| self.get_reg(13).wrapping_add(i * 4))
+            // The stack can't go past the end of IWRAM
+            .take_while(|addr| *addr >= 0x0300_0000 && *addr < 0x0300_8000)
+            .map(|addr| self.read_mem_u32(addr)));
+        let mut call_stack: Vec<u32> = return_addrs.filter_map(|return_addr| {
+            let thumb = return_addr & 0x1 != 0;
+            let call_addr = (return_addr & !0x1).wrapping_sub(4);
+            if call_addr >= 0x1000_0000 || !self.is_call(call_addr, thumb) { return None }
+            Some(call_addr)
+        }).collect();
+        // LR is usually on the stack too once it's been pushed
+        call_stack.dedup();
+        call_stack
+    }
+
+    // Goes through the same memory map as the CPU, without taking any cycles
+    pub fn read_mem(&self, addr: u32) -> u8 { self.io.read::<u8>(addr) }
+
+    fn read_mem_u16(&self, addr: u32) -> u16 {
+        u16::from_le_bytes([self.read_mem(addr), self.read_mem(addr.wrapping_add(1))])
+    }
+
+    fn read_mem_u32(&self, addr: u32) -> u32 {
+        self.read_mem_u16(addr) as u32 | (self.read_mem_u16(addr.wrapping_add(2)) as u32) << 16
+    }
+
+    pub fn write_mem(&mut self, addr: u32, value: u8) { self.io.write::<u8>(addr, value) }
+}
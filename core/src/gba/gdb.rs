@@ -0,0 +1,325 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::{GBA, CPUMode, StopReason, Watchpoint, WatchKind};
+
+// GDB remote serial protocol over TCP
+pub struct GDBStub {
+    stream: TcpStream,
+    input: Vec<u8>,
+    no_ack: bool,
+}
+
+impl GDBStub {
+    const TARGET_XML: &'static str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>armv4t</architecture>
+  <feature name="org.gnu.gdb.arm.core">
+    <reg name="r0" bitsize="32"/>
+    <reg name="r1" bitsize="32"/>
+    <reg name="r2" bitsize="32"/>
+    <reg name="r3" bitsize="32"/>
+    <reg name="r4" bitsize="32"/>
+    <reg name="r5" bitsize="32"/>
+    <reg name="r6" bitsize="32"/>
+    <reg name="r7" bitsize="32"/>
+    <reg name="r8" bitsize="32"/>
+    <reg name="r9" bitsize="32"/>
+    <reg name="r10" bitsize="32"/>
+    <reg name="r11" bitsize="32"/>
+    <reg name="r12" bitsize="32"/>
+    <reg name="sp" bitsize="32" type="data_ptr"/>
+    <reg name="lr" bitsize="32"/>
+    <reg name="pc" bitsize="32" type="code_ptr"/>
+    <reg name="cpsr" bitsize="32" regnum="25"/>
+  </feature>
+  <feature name="gba.banked">
+    <reg name="spsr_fiq" bitsize="32" regnum="26"/>
+    <reg name="spsr_svc" bitsize="32" regnum="27"/>
+    <reg name="spsr_abt" bitsize="32" regnum="28"/>
+    <reg name="spsr_irq" bitsize="32" regnum="29"/>
+    <reg name="spsr_und" bitsize="32" regnum="30"/>
+  </feature>
+</target>
+"#;
+    const CPSR: usize = 25;
+    // Register numbers following CPSR
+    const SPSR_MODES: [CPUMode; 5] = [CPUMode::FIQ, CPUMode::SVC, CPUMode::ABT, CPUMode::IRQ, CPUMode::UND];
+
+    // Blocks until GDB connects
+    pub fn listen<A: ToSocketAddrs>(addr: A) -> io::Result<GDBStub> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        GDBStub::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<GDBStub> {
+        stream.set_nodelay(true)?;
+        Ok(GDBStub {
+            stream,
+            input: Vec::new(),
+            no_ack: false,
+        })
+    }
+
+    // Serves GDB until it detaches or kills the session, the GBA is stopped in between commands
+    pub fn run(&mut self, gba: &mut GBA) -> io::Result<()> {
+        loop {
+            let packet = self.read_packet()?;
+            if !packet.is_ascii() { self.write_packet("")?; continue }
+            let (command, args) = packet.split_at(1.min(packet.len()));
+            let reply = match command {
+                "?" => "S05".to_string(),
+                "g" => {
+                    let mut reply: String = (0..16).map(|reg| GDBStub::encode_u32(gba.get_reg(reg))).collect();
+                    reply += &GDBStub::encode_u32(gba.get_cpsr());
+                    for mode in GDBStub::SPSR_MODES.iter() { reply += &GDBStub::encode_u32(gba.get_spsr(*mode).unwrap()) }
+                    reply
+                },
+                "G" => {
+                    let values: Option<Vec<u32>> = (0..args.len() / 8).map(|i| GDBStub::decode_u32(&args[i * 8..i * 8 + 8])).collect();
+                    match values {
+                        Some(values) if values.len() >= 17 => {
+                            // CPSR first since it decides which registers are banked in
+                            gba.set_cpsr(values[16]);
+                            for (reg, value) in values.iter().take(16).enumerate() { gba.set_reg(reg, *value) }
+                            for (mode, value) in GDBStub::SPSR_MODES.iter().zip(values.iter().skip(17)) {
+                                gba.set_spsr(*mode, *value)
+                            }
+                            "OK".to_string()
+                        },
+                        _ => "E01".to_string(),
+                    }
+                },
+                "p" => match usize::from_str_radix(args, 16).ok().and_then(|reg| GDBStub::get_reg(gba, reg)) {
+                    Some(value) => GDBStub::encode_u32(value),
+                    None => "E01".to_string(),
+                },
+                "P" => {
+                    let mut parts = args.splitn(2, '=');
+                    let reg = parts.next().and_then(|reg| usize::from_str_radix(reg, 16).ok());
+                    let value = parts.next().and_then(GDBStub::decode_u32);
+                    match (reg, value) {
+                        (Some(reg), Some(value)) if GDBStub::set_reg(gba, reg, value) => "OK".to_string(),
+                        _ => "E01".to_string(),
+                    }
+                },
+                "m" => match GDBStub::parse_range(args) {
+                    Some((addr, len)) => (0..len).map(|i| format!("{:02x}", gba.read_mem(addr.wrapping_add(i)))).collect(),
+                    None => "E01".to_string(),
+                },
+                "M" => {
+                    let mut parts = args.splitn(2, ':');
+                    let range = parts.next().and_then(GDBStub::parse_range);
+                    let data = parts.next().and_then(GDBStub::decode_bytes);
+                    match (range, data) {
+                        (Some((addr, len)), Some(data)) if data.len() == len as usize => {
+                            for (i, byte) in data.iter().enumerate() { gba.write_mem(addr.wrapping_add(i as u32), *byte) }
+                            "OK".to_string()
+                        },
+                        _ => "E01".to_string(),
+                    }
+                },
+                "c" => self.resume(gba, false)?,
+                "s" => self.resume(gba, true)?,
+                "Z" | "z" => GDBStub::update_breakpoint(gba, command == "Z", args),
+                "H" | "T" => "OK".to_string(),
+                "D" => { self.write_packet("OK")?; return Ok(()) },
+                "k" => return Ok(()),
+                "v" => match args {
+                    "Cont?" => "vCont;c;C;s;S".to_string(),
+                    _ if args.starts_with("Cont;c") || args.starts_with("Cont;C") => self.resume(gba, false)?,
+                    _ if args.starts_with("Cont;s") || args.starts_with("Cont;S") => self.resume(gba, true)?,
+                    _ => String::new(),
+                },
+                "q" | "Q" => self.query(&packet),
+                _ => String::new(),
+            };
+            self.write_packet(&reply)?;
+        }
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        match packet {
+            _ if packet.starts_with("qSupported") =>
+                "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+".to_string(),
+            "QStartNoAckMode" => {
+                // GDB still acknowledges the reply to this one
+                self.no_ack = true;
+                "OK".to_string()
+            },
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ if packet.starts_with("qXfer:features:read:target.xml:") => {
+                match GDBStub::parse_range(&packet["qXfer:features:read:target.xml:".len()..]) {
+                    Some((offset, len)) => {
+                        let xml = GDBStub::TARGET_XML.as_bytes();
+                        let start = (offset as usize).min(xml.len());
+                        let end = (start + len as usize).min(xml.len());
+                        let more = if end < xml.len() { "m" } else { "l" };
+                        more.to_string() + &String::from_utf8_lossy(&xml[start..end])
+                    },
+                    None => "E01".to_string(),
+                }
+            },
+            _ => String::new(),
+        }
+    }
+
+    // Returns the stop reply
+    fn resume(&mut self, gba: &mut GBA, step: bool) -> io::Result<String> {
+        let stop_reason = if step { Some(gba.step()) } else {
+            loop {
+                if let Some(stop_reason) = gba.emulate_frame_debug() { break Some(stop_reason) }
+                // GDB sends a lone 0x03 to interrupt
+                if self.poll_byte()? == Some(0x03) { break None }
+            }
+        };
+        Ok(match stop_reason {
+            Some(StopReason::Watchpoint(watchpoint, addr)) => {
+                let kind = match watchpoint.kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Access => "awatch",
+                };
+                format!("T05{}:{:08x};", kind, addr)
+            },
+            Some(StopReason::Step) | Some(StopReason::Breakpoint(_)) => "S05".to_string(),
+            None => "S02".to_string(),
+        })
+    }
+
+    fn update_breakpoint(gba: &mut GBA, insert: bool, args: &str) -> String {
+        let mut parts = args.splitn(2, ',');
+        let kind = parts.next();
+        let range = parts.next().and_then(GDBStub::parse_range);
+        let (addr, len) = match range {
+            Some(range) => range,
+            None => return "E01".to_string(),
+        };
+        let watch_kind = match kind {
+            // Breakpoints never modify memory, so software and hardware ones are the same
+            Some("0") | Some("1") => {
+                if insert { gba.add_breakpoint(addr) } else { gba.remove_breakpoint(addr); }
+                return "OK".to_string()
+            },
+            Some("2") => WatchKind::Write,
+            Some("3") => WatchKind::Read,
+            Some("4") => WatchKind::Access,
+            _ => return String::new(),
+        };
+        let watchpoint = Watchpoint { addr, len, kind: watch_kind };
+        if insert { gba.add_watchpoint(watchpoint) } else { gba.remove_watchpoint(watchpoint); }
+        "OK".to_string()
+    }
+
+    fn get_reg(gba: &GBA, reg: usize) -> Option<u32> {
+        match reg {
+            0 ..= 15 => Some(gba.get_reg(reg)),
+            GDBStub::CPSR => Some(gba.get_cpsr()),
+            _ => GDBStub::SPSR_MODES.get(reg.wrapping_sub(GDBStub::CPSR + 1)).and_then(|mode| gba.get_spsr(*mode)),
+        }
+    }
+
+    fn set_reg(gba: &mut GBA, reg: usize, value: u32) -> bool {
+        match reg {
+            0 ..= 15 => gba.set_reg(reg, value),
+            GDBStub::CPSR => gba.set_cpsr(value),
+            _ => match GDBStub::SPSR_MODES.get(reg.wrapping_sub(GDBStub::CPSR + 1)) {
+                Some(mode) => gba.set_spsr(*mode, value),
+                None => return false,
+            },
+        }
+        true
+    }
+
+    // Registers are sent in target byte order
+    fn encode_u32(value: u32) -> String { format!("{:08x}", value.swap_bytes()) }
+
+    fn decode_u32(hex: &str) -> Option<u32> {
+        if hex.len() != 8 { return None }
+        u32::from_str_radix(hex, 16).ok().map(u32::swap_bytes)
+    }
+
+    fn decode_bytes(hex: &str) -> Option<Vec<u8>> {
+        hex.as_bytes().chunks(2).map(|byte| match byte {
+            [high, low] => Some((GDBStub::hex_digit(*high)? << 4) | GDBStub::hex_digit(*low)?),
+            _ => None,
+        }).collect()
+    }
+
+    fn hex_digit(digit: u8) -> Option<u8> {
+        (digit as char).to_digit(16).map(|digit| digit as u8)
+    }
+
+    // addr,len
+    fn parse_range(range: &str) -> Option<(u32, u32)> {
+        let mut parts = range.splitn(2, ',');
+        let addr = u32::from_str_radix(parts.next()?, 16).ok()?;
+        let len = u32::from_str_radix(parts.next()?, 16).ok()?;
+        Some((addr, len))
+    }
+
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            // Anything outside of a packet, like acks and interrupts while already stopped, is skipped
+            while self.read_byte()? != b'$' {}
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            let checksum = [self.read_byte()?, self.read_byte()?];
+            let valid = std::str::from_utf8(&checksum).ok().and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(GDBStub::checksum(&data));
+            if !self.no_ack { self.stream.write_all(if valid { b"+" } else { b"-" })? }
+            if valid { return Ok(String::from_utf8_lossy(&data).into_owned()) }
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, GDBStub::checksum(data.as_bytes()));
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            if self.no_ack { return Ok(()) }
+            loop {
+                match self.read_byte()? {
+                    b'+' => return Ok(()),
+                    b'-' => break,
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    fn checksum(data: &[u8]) -> u8 { data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte)) }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        while self.input.is_empty() { self.fill_input(true)?; }
+        Ok(self.input.remove(0))
+    }
+
+    // Doesn't block
+    fn poll_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.input.is_empty() && !self.fill_input(false)? { return Ok(None) }
+        Ok(Some(self.input.remove(0)))
+    }
+
+    // Returns whether anything was read
+    fn fill_input(&mut self, block: bool) -> io::Result<bool> {
+        let mut buffer = [0; 0x1000];
+        self.stream.set_nonblocking(!block)?;
+        let result = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Err(io::Error::new(ErrorKind::UnexpectedEof, "GDB disconnected")),
+            Ok(len) => { self.input.extend_from_slice(&buffer[..len]); Ok(true) },
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// Names and bitfields of the memory mapped IO registers, as listed in GBATEK
+
+type Fields = &'static [(&'static str, u32, u32)];
+
+pub struct IORegisterInfo {
+    pub addr: u32,
+    pub name: &'static str,
+    // In bytes
+    pub size: u32,
+    // Write only registers read back as 0 to the CPU, but peek_io_register shows what was written
+    pub readable: bool,
+    // Name, first bit and width
+    pub fields: Fields,
+}
+
+impl IORegisterInfo {
+    const fn new(addr: u32, name: &'static str, size: u32, readable: bool, fields: Fields) -> IORegisterInfo {
+        IORegisterInfo { addr, name, size, readable, fields }
+    }
+
+    pub fn get_field(&self, value: u32, field_i: usize) -> u32 {
+        let (_, bit, width) = self.fields[field_i];
+        value >> bit & IORegisterInfo::mask(width)
+    }
+
+    pub fn set_field(&self, value: u32, field_i: usize, field_value: u32) -> u32 {
+        let (_, bit, width) = self.fields[field_i];
+        let mask = IORegisterInfo::mask(width) << bit;
+        value & !mask | field_value << bit & mask
+    }
+
+    pub fn get_group(&self) -> &'static str {
+        match self.addr {
+            0x0400_0000 ..= 0x0400_005F => "LCD",
+            0x0400_0060 ..= 0x0400_00AF => "Sound",
+            0x0400_00B0 ..= 0x0400_00FF => "DMA",
+            0x0400_0100 ..= 0x0400_011F => "Timers",
+            0x0400_0130 ..= 0x0400_0133 => "Keypad",
+            0x0400_0120 ..= 0x0400_01FF => "Serial",
+            _ => "System",
+        }
+    }
+
+    fn mask(width: u32) -> u32 { if width == 32 { 0xFFFF_FFFF } else { (1 << width) - 1 } }
+}
+
+const BGCNT: Fields = &[
+    ("Priority", 0, 2), ("Tile Block", 2, 2), ("Mosaic", 6, 1), ("256 Colors", 7, 1),
+    ("Map Block", 8, 5), ("Wrap", 13, 1), ("Screen Size", 14, 2),
+];
+const OFS: Fields = &[("Offset", 0, 9)];
+const AFFINE_PARAM: Fields = &[("Fraction", 0, 8), ("Integer", 8, 8)];
+const REFERENCE_POINT: Fields = &[("Fraction", 0, 8), ("Integer", 8, 20)];
+const WINH: Fields = &[("X2", 0, 8), ("X1", 8, 8)];
+const WINV: Fields = &[("Y2", 0, 8), ("Y1", 8, 8)];
+const ENVELOPE: Fields = &[
+    ("Length", 0, 6), ("Duty", 6, 2), ("Envelope Step", 8, 3), ("Envelope Increase", 11, 1), ("Initial Volume", 12, 4),
+];
+const FREQUENCY: Fields = &[("Frequency", 0, 11), ("Length Enable", 14, 1), ("Restart", 15, 1)];
+const ADDRESS: Fields = &[("Address", 0, 28)];
+const WORD_COUNT: Fields = &[("Word Count", 0, 16)];
+const DMACNT: Fields = &[
+    ("Dest Control", 5, 2), ("Source Control", 7, 2), ("Repeat", 9, 1), ("32 Bit", 10, 1),
+    ("Game Pak DRQ", 11, 1), ("Start Timing", 12, 2), ("IRQ", 14, 1), ("Enable", 15, 1),
+];
+const TMCNT_L: Fields = &[("Counter/Reload", 0, 16)];
+const TMCNT_H: Fields = &[("Prescaler", 0, 2), ("Count Up", 2, 1), ("IRQ", 6, 1), ("Enable", 7, 1)];
+const KEYS: Fields = &[
+    ("A", 0, 1), ("B", 1, 1), ("Select", 2, 1), ("Start", 3, 1), ("Right", 4, 1),
+    ("Left", 5, 1), ("Up", 6, 1), ("Down", 7, 1), ("R", 8, 1), ("L", 9, 1),
+];
+const KEYCNT: Fields = &[
+    ("A", 0, 1), ("B", 1, 1), ("Select", 2, 1), ("Start", 3, 1), ("Right", 4, 1),
+    ("Left", 5, 1), ("Up", 6, 1), ("Down", 7, 1), ("R", 8, 1), ("L", 9, 1),
+    ("IRQ Enable", 14, 1), ("IRQ All Pressed", 15, 1),
+];
+const INTERRUPTS: Fields = &[
+    ("VBlank", 0, 1), ("HBlank", 1, 1), ("VCounter", 2, 1), ("Timer 0", 3, 1), ("Timer 1", 4, 1),
+    ("Timer 2", 5, 1), ("Timer 3", 6, 1), ("Serial", 7, 1), ("DMA 0", 8, 1), ("DMA 1", 9, 1),
+    ("DMA 2", 10, 1), ("DMA 3", 11, 1), ("Keypad", 12, 1), ("Game Pak", 13, 1),
+];
+
+pub const IO_REGISTERS: &[IORegisterInfo] = &[
+    // LCD
+    IORegisterInfo::new(0x0400_0000, "DISPCNT", 2, true, &[
+        ("BG Mode", 0, 3), ("CGB Mode", 3, 1), ("Frame Select", 4, 1), ("HBlank Interval Free", 5, 1),
+        ("OBJ 1D Mapping", 6, 1), ("Forced Blank", 7, 1), ("BG0", 8, 1), ("BG1", 9, 1), ("BG2", 10, 1),
+        ("BG3", 11, 1), ("OBJ", 12, 1), ("Window 0", 13, 1), ("Window 1", 14, 1), ("OBJ Window", 15, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0002, "GREENSWAP", 2, true, &[("Green Swap", 0, 1)]),
+    IORegisterInfo::new(0x0400_0004, "DISPSTAT", 2, true, &[
+        ("VBlank", 0, 1), ("HBlank", 1, 1), ("VCounter", 2, 1), ("VBlank IRQ", 3, 1),
+        ("HBlank IRQ", 4, 1), ("VCounter IRQ", 5, 1), ("VCount Setting", 8, 8),
+    ]),
+    IORegisterInfo::new(0x0400_0006, "VCOUNT", 2, true, &[("Line", 0, 8)]),
+    IORegisterInfo::new(0x0400_0008, "BG0CNT", 2, true, BGCNT),
+    IORegisterInfo::new(0x0400_000A, "BG1CNT", 2, true, BGCNT),
+    IORegisterInfo::new(0x0400_000C, "BG2CNT", 2, true, BGCNT),
+    IORegisterInfo::new(0x0400_000E, "BG3CNT", 2, true, BGCNT),
+    IORegisterInfo::new(0x0400_0010, "BG0HOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_0012, "BG0VOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_0014, "BG1HOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_0016, "BG1VOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_0018, "BG2HOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_001A, "BG2VOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_001C, "BG3HOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_001E, "BG3VOFS", 2, false, OFS),
+    IORegisterInfo::new(0x0400_0020, "BG2PA", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0022, "BG2PB", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0024, "BG2PC", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0026, "BG2PD", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0028, "BG2X", 4, false, REFERENCE_POINT),
+    IORegisterInfo::new(0x0400_002C, "BG2Y", 4, false, REFERENCE_POINT),
+    IORegisterInfo::new(0x0400_0030, "BG3PA", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0032, "BG3PB", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0034, "BG3PC", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0036, "BG3PD", 2, false, AFFINE_PARAM),
+    IORegisterInfo::new(0x0400_0038, "BG3X", 4, false, REFERENCE_POINT),
+    IORegisterInfo::new(0x0400_003C, "BG3Y", 4, false, REFERENCE_POINT),
+    IORegisterInfo::new(0x0400_0040, "WIN0H", 2, false, WINH),
+    IORegisterInfo::new(0x0400_0042, "WIN1H", 2, false, WINH),
+    IORegisterInfo::new(0x0400_0044, "WIN0V", 2, false, WINV),
+    IORegisterInfo::new(0x0400_0046, "WIN1V", 2, false, WINV),
+    IORegisterInfo::new(0x0400_0048, "WININ", 2, true, &[
+        ("Window 0 BG0", 0, 1), ("Window 0 BG1", 1, 1), ("Window 0 BG2", 2, 1), ("Window 0 BG3", 3, 1),
+        ("Window 0 OBJ", 4, 1), ("Window 0 Effects", 5, 1), ("Window 1 BG0", 8, 1), ("Window 1 BG1", 9, 1),
+        ("Window 1 BG2", 10, 1), ("Window 1 BG3", 11, 1), ("Window 1 OBJ", 12, 1), ("Window 1 Effects", 13, 1),
+    ]),
+    IORegisterInfo::new(0x0400_004A, "WINOUT", 2, true, &[
+        ("Outside BG0", 0, 1), ("Outside BG1", 1, 1), ("Outside BG2", 2, 1), ("Outside BG3", 3, 1),
+        ("Outside OBJ", 4, 1), ("Outside Effects", 5, 1), ("OBJ Window BG0", 8, 1), ("OBJ Window BG1", 9, 1),
+        ("OBJ Window BG2", 10, 1), ("OBJ Window BG3", 11, 1), ("OBJ Window OBJ", 12, 1), ("OBJ Window Effects", 13, 1),
+    ]),
+    IORegisterInfo::new(0x0400_004C, "MOSAIC", 2, false, &[
+        ("BG H Size", 0, 4), ("BG V Size", 4, 4), ("OBJ H Size", 8, 4), ("OBJ V Size", 12, 4),
+    ]),
+    IORegisterInfo::new(0x0400_0050, "BLDCNT", 2, true, &[
+        ("1st BG0", 0, 1), ("1st BG1", 1, 1), ("1st BG2", 2, 1), ("1st BG3", 3, 1), ("1st OBJ", 4, 1),
+        ("1st Backdrop", 5, 1), ("Effect", 6, 2), ("2nd BG0", 8, 1), ("2nd BG1", 9, 1), ("2nd BG2", 10, 1),
+        ("2nd BG3", 11, 1), ("2nd OBJ", 12, 1), ("2nd Backdrop", 13, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0052, "BLDALPHA", 2, true, &[("EVA", 0, 5), ("EVB", 8, 5)]),
+    IORegisterInfo::new(0x0400_0054, "BLDY", 2, false, &[("EVY", 0, 5)]),
+    // Sound
+    IORegisterInfo::new(0x0400_0060, "SOUND1CNT_L", 2, true, &[
+        ("Sweep Shift", 0, 3), ("Sweep Decrease", 3, 1), ("Sweep Time", 4, 3),
+    ]),
+    IORegisterInfo::new(0x0400_0062, "SOUND1CNT_H", 2, true, ENVELOPE),
+    IORegisterInfo::new(0x0400_0064, "SOUND1CNT_X", 2, true, FREQUENCY),
+    IORegisterInfo::new(0x0400_0068, "SOUND2CNT_L", 2, true, ENVELOPE),
+    IORegisterInfo::new(0x0400_006C, "SOUND2CNT_H", 2, true, FREQUENCY),
+    IORegisterInfo::new(0x0400_0070, "SOUND3CNT_L", 2, true, &[
+        ("Two Banks", 5, 1), ("Bank", 6, 1), ("Enable", 7, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0072, "SOUND3CNT_H", 2, true, &[
+        ("Length", 0, 8), ("Volume", 13, 2), ("Force 75%", 15, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0074, "SOUND3CNT_X", 2, true, FREQUENCY),
+    IORegisterInfo::new(0x0400_0078, "SOUND4CNT_L", 2, true, &[
+        ("Length", 0, 6), ("Envelope Step", 8, 3), ("Envelope Increase", 11, 1), ("Initial Volume", 12, 4),
+    ]),
+    IORegisterInfo::new(0x0400_007C, "SOUND4CNT_H", 2, true, &[
+        ("Divide Ratio", 0, 3), ("7 Bit Counter", 3, 1), ("Shift Frequency", 4, 4),
+        ("Length Enable", 14, 1), ("Restart", 15, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0080, "SOUNDCNT_L", 2, true, &[
+        ("Right Volume", 0, 3), ("Left Volume", 4, 3), ("Right Channel 1", 8, 1), ("Right Channel 2", 9, 1),
+        ("Right Channel 3", 10, 1), ("Right Channel 4", 11, 1), ("Left Channel 1", 12, 1),
+        ("Left Channel 2", 13, 1), ("Left Channel 3", 14, 1), ("Left Channel 4", 15, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0082, "SOUNDCNT_H", 2, true, &[
+        ("PSG Volume", 0, 2), ("DMA A Volume", 2, 1), ("DMA B Volume", 3, 1), ("DMA A Right", 8, 1),
+        ("DMA A Left", 9, 1), ("DMA A Timer", 10, 1), ("DMA A Reset", 11, 1), ("DMA B Right", 12, 1),
+        ("DMA B Left", 13, 1), ("DMA B Timer", 14, 1), ("DMA B Reset", 15, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0084, "SOUNDCNT_X", 2, true, &[
+        ("Channel 1 On", 0, 1), ("Channel 2 On", 1, 1), ("Channel 3 On", 2, 1), ("Channel 4 On", 3, 1),
+        ("Master Enable", 7, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0088, "SOUNDBIAS", 2, true, &[("Bias", 1, 9), ("Resolution", 14, 2)]),
+    // DMA
+    IORegisterInfo::new(0x0400_00B0, "DMA0SAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00B4, "DMA0DAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00B8, "DMA0CNT_L", 2, false, WORD_COUNT),
+    IORegisterInfo::new(0x0400_00BA, "DMA0CNT_H", 2, true, DMACNT),
+    IORegisterInfo::new(0x0400_00BC, "DMA1SAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00C0, "DMA1DAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00C4, "DMA1CNT_L", 2, false, WORD_COUNT),
+    IORegisterInfo::new(0x0400_00C6, "DMA1CNT_H", 2, true, DMACNT),
+    IORegisterInfo::new(0x0400_00C8, "DMA2SAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00CC, "DMA2DAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00D0, "DMA2CNT_L", 2, false, WORD_COUNT),
+    IORegisterInfo::new(0x0400_00D2, "DMA2CNT_H", 2, true, DMACNT),
+    IORegisterInfo::new(0x0400_00D4, "DMA3SAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00D8, "DMA3DAD", 4, false, ADDRESS),
+    IORegisterInfo::new(0x0400_00DC, "DMA3CNT_L", 2, false, WORD_COUNT),
+    IORegisterInfo::new(0x0400_00DE, "DMA3CNT_H", 2, true, DMACNT),
+    // Timers, which read back the counter and write the reload value
+    IORegisterInfo::new(0x0400_0100, "TM0CNT_L", 2, true, TMCNT_L),
+    IORegisterInfo::new(0x0400_0102, "TM0CNT_H", 2, true, TMCNT_H),
+    IORegisterInfo::new(0x0400_0104, "TM1CNT_L", 2, true, TMCNT_L),
+    IORegisterInfo::new(0x0400_0106, "TM1CNT_H", 2, true, TMCNT_H),
+    IORegisterInfo::new(0x0400_0108, "TM2CNT_L", 2, true, TMCNT_L),
+    IORegisterInfo::new(0x0400_010A, "TM2CNT_H", 2, true, TMCNT_H),
+    IORegisterInfo::new(0x0400_010C, "TM3CNT_L", 2, true, TMCNT_L),
+    IORegisterInfo::new(0x0400_010E, "TM3CNT_H", 2, true, TMCNT_H),
+    // Serial
+    IORegisterInfo::new(0x0400_0120, "SIODATA32", 4, true, &[("Data", 0, 32)]),
+    IORegisterInfo::new(0x0400_0128, "SIOCNT", 2, true, &[
+        ("Baud Rate", 0, 2), ("SI Terminal", 2, 1), ("SD Terminal", 3, 1), ("Multiplayer ID", 4, 2),
+        ("Error", 6, 1), ("Start/Busy", 7, 1), ("32 Bit", 12, 1), ("Mode", 13, 1), ("IRQ", 14, 1),
+    ]),
+    IORegisterInfo::new(0x0400_012A, "SIODATA8", 2, true, &[("Data", 0, 16)]),
+    IORegisterInfo::new(0x0400_0130, "KEYINPUT", 2, true, KEYS),
+    IORegisterInfo::new(0x0400_0132, "KEYCNT", 2, true, KEYCNT),
+    IORegisterInfo::new(0x0400_0134, "RCNT", 2, true, &[
+        ("SC", 0, 1), ("SD", 1, 1), ("SI", 2, 1), ("SO", 3, 1), ("SC Output", 4, 1), ("SD Output", 5, 1),
+        ("SI Output", 6, 1), ("SO Output", 7, 1), ("SI IRQ", 8, 1), ("Mode", 14, 2),
+    ]),
+    // System
+    IORegisterInfo::new(0x0400_0200, "IE", 2, true, INTERRUPTS),
+    IORegisterInfo::new(0x0400_0202, "IF", 2, true, INTERRUPTS),
+    IORegisterInfo::new(0x0400_0204, "WAITCNT", 2, true, &[
+        ("SRAM Wait", 0, 2), ("WS0 First", 2, 2), ("WS0 Second", 4, 1), ("WS1 First", 5, 2),
+        ("WS1 Second", 7, 1), ("WS2 First", 8, 2), ("WS2 Second", 10, 1), ("PHI Output", 11, 2),
+        ("Prefetch", 14, 1), ("Game Pak Type", 15, 1),
+    ]),
+    IORegisterInfo::new(0x0400_0208, "IME", 2, true, &[("Enable", 0, 1)]),
+    IORegisterInfo::new(0x0400_0300, "POSTFLG", 1, true, &[("First Boot Done", 0, 1)]),
+];
@@ -1,53 +1,320 @@
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+mod builder;
+mod debug;
+mod gdb;
+mod io_registers;
+mod movie;
+mod ram_search;
+mod rewind;
+mod trace;
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use flume::{Receiver, Sender};
 
 use crate::cpu::CPU;
-use crate::io::IO;
+use crate::io::{HaltMode, IO};
+use crate::savestate::{SaveState, StateReader};
+pub use crate::savestate::{crc32, StateError};
 pub use crate::io::{
-    DebugSpecification, DebugWindows,
+    DebugSpecification, DebugWindows, OBJInfo, OBJMode,
+    AudioSink, NullSink, RingBufferSink, SampleBuffer, AudioChannel, AudioChannelState, AudioState,
+    LinkMessage, LinkTransport, LocalLink, TcpLink,
+    MGBALogLevel, Watchpoint, WatchKind, TimelineEntry, TimelineEvent,
+    Cheat, CheatCondition, CheatError, CheatFormat, CheatOp,
     keypad::KEYINPUT,
 };
+pub use crate::cpu::Mode as CPUMode;
+pub use crate::cpu::{disassemble_arm, disassemble_thumb};
+pub use builder::{Built, GBABuilder, GBAError};
+pub use debug::StopReason;
+pub use gdb::GDBStub;
+pub use io_registers::{IORegisterInfo, IO_REGISTERS};
+pub use movie::{Movie, MovieError, MovieStatus};
+pub use ram_search::{RAMSearch, SearchComparison, SearchFilter};
+use movie::ActiveMovie;
+use rewind::RewindBuffer;
+use trace::Trace;
 
 pub struct GBA {
     cpu: CPU,
     io: IO,
     next_frame_cycle: usize,
+    rom_checksum: u32,
+    rewind_buffer: Option<RewindBuffer>,
+    breakpoints: BTreeSet<u32>,
+    trace: Option<Trace>,
+    // Last finished frame's events while the timeline is enabled
+    timeline: Option<Vec<TimelineEntry>>,
+    movie: Option<ActiveMovie>,
 }
 
 impl GBA {
-    pub fn new(rom_file: PathBuf, render_tx: Sender<DebugWindows>, keypad_rx: Receiver<(KEYINPUT, bool)>) ->
-        (GBA, Arc<Mutex<Vec<u16>>>, Arc<Mutex<DebugSpecification>>) {
-        let bios = std::fs::read("gba_bios.bin").unwrap();
-        let (mut io, pixels, debug_windows_spec) =
-            IO::new(bios, rom_file, render_tx, keypad_rx);
-        (GBA {
-            cpu: CPU::new(false, &mut io),
-            io,
-            next_frame_cycle: 0,
-        }, pixels, debug_windows_spec)
+    const STATE_MAGIC: &'static [u8] = b"GBAS";
+    const STATE_VERSION: u32 = 5;
+    const ROM_ID_LEN: usize = 16;
+
+    pub fn new(rom_file: PathBuf, render_tx: Sender<DebugWindows>, keypad_rx: Receiver<(KEYINPUT, bool)>,
+        audio: Box<dyn AudioSink>) -> Result<Built, GBAError> {
+        if rom_file.extension() != Some(OsStr::new("gba")) {
+            return Err(GBAError::InvalidRomExtension(rom_file))
+        }
+        // Fall back to HLE when there's no BIOS dump
+        let bios_file = Path::new("gba_bios.bin");
+        let bios = if bios_file.exists() { Some(GBA::read_file(bios_file)?) } else { None };
+        let rom = GBA::read_file(&rom_file)?;
+        let save_file = rom_file.with_extension("sav");
+        let save_data = fs::read(&save_file).ok();
+
+        let mut builder = GBABuilder::new(&rom)
+            .save_file(save_file)
+            .render_tx(render_tx)
+            .keypad_rx(keypad_rx)
+            .audio_sink(audio)
+            // Cheats are listed in a text file next to the ROM
+            .cheat_file(rom_file.with_extension("cht"));
+        if let Some(bios) = &bios { builder = builder.bios(bios) }
+        if let Some(save_data) = &save_data { builder = builder.save_data(save_data) }
+        builder.build()
+    }
+
+    pub fn builder(rom: &[u8]) -> GBABuilder<'_> { GBABuilder::new(rom) }
+
+    fn read_file(path: &Path) -> Result<Vec<u8>, GBAError> {
+        fs::read(path).map_err(|err| GBAError::FileAccess(path.to_path_buf(), err))
     }
 
     pub fn emulate_frame(&mut self) {
+        // The frame may have been started by the debugger
+        if self.io.get_cycle() >= self.next_frame_cycle { self.start_frame() }
+        while self.io.get_cycle() < self.next_frame_cycle { self.emulate_step() }
+        self.end_frame();
+    }
+
+    fn start_frame(&mut self) {
         self.io.poll_keypad_updates();
+        if let Some(movie) = &mut self.movie {
+            if let Some(keyinput) = movie.start_frame(self.io.get_keyinput()) { self.io.set_keyinput(keyinput) }
+        }
+        self.io.apply_cheats();
         // TODO: This will overflow on 32-bit systems
         self.next_frame_cycle += CLOCKS_PER_FRAME;
-        while self.io.get_cycle() < self.next_frame_cycle {
-            self.io.run_dma();
-            self.cpu.handle_irq(&mut self.io);
-            self.cpu.emulate_instr(&mut self.io);
+    }
+
+    fn emulate_step(&mut self) {
+        self.io.run_dma();
+        match self.io.get_halt_mode() {
+            HaltMode::Running => {
+                self.cpu.handle_irq(&mut self.io);
+                match &mut self.trace {
+                    Some(trace) => {
+                        trace.start_instr(&self.cpu, self.io.get_cycle());
+                        self.cpu.emulate_instr(&mut self.io);
+                        if let Err(err) = trace.end_instr(&self.cpu) {
+                            warn!("Stopping instruction trace: {}", err);
+                            self.trace = None;
+                        }
+                    },
+                    None => self.cpu.emulate_instr(&mut self.io),
+                }
+            },
+            HaltMode::Halted => self.io.run_halted(self.next_frame_cycle),
+            HaltMode::Stopped => self.io.run_stopped(self.next_frame_cycle),
         }
     }
 
+    fn end_frame(&mut self) {
+        if let Some(timeline) = self.io.take_timeline(self.next_frame_cycle - CLOCKS_PER_FRAME) {
+            self.timeline = Some(timeline);
+        }
+        if let Some(movie) = &mut self.movie {
+            let io = &self.io;
+            movie.end_frame(|| io.checksum());
+        }
+        let take_snapshot = match &mut self.rewind_buffer {
+            Some(rewind_buffer) => rewind_buffer.on_frame(),
+            None => false,
+        };
+        if take_snapshot {
+            let mut state = Vec::new();
+            self.save_machine_state(&mut state);
+            self.rewind_buffer.as_mut().unwrap().push(state);
+        }
+    }
+
+    // Keeps up to capacity snapshots, taken every interval frames
+    pub fn enable_rewind(&mut self, capacity: usize, interval: usize) {
+        self.rewind_buffer = Some(RewindBuffer::new(capacity, interval));
+    }
+
+    pub fn disable_rewind(&mut self) { self.rewind_buffer = None }
+
+    // Logs every instruction the CPU executes to a file until stopped
+    pub fn start_trace(&mut self, path: &Path) -> io::Result<()> {
+        self.trace = Some(Trace::new(path)?);
+        Ok(())
+    }
+
+    pub fn stop_trace(&mut self) { self.trace = None }
+
+    // Records when blanking, timer overflows, DMAs, IRQs and halting happen in each frame
+    pub fn enable_timeline(&mut self, enable: bool) {
+        self.io.enable_timeline(enable);
+        self.timeline = None;
+    }
+
+    pub fn take_timeline(&mut self) -> Option<Vec<TimelineEntry>> { self.timeline.take() }
+
+    pub fn movie_status(&self) -> Option<MovieStatus> { self.movie.as_ref().map(ActiveMovie::status) }
+
+    // Stops recording or playing back, returning the movie so a recording can be saved
+    pub fn finish_movie(&mut self) -> Option<Movie> {
+        let io = &self.io;
+        self.movie.take().map(|movie| movie.finish(|| io.checksum()))
+    }
+
+    // Movies only have inputs going forwards, so rewinding one would desync it
+    fn movie_active(&self) -> bool { matches!(&self.movie, Some(movie) if movie.is_active()) }
+
+    // Returns how many frames were actually rewound
+    pub fn rewind(&mut self, frames: usize) -> usize {
+        if self.movie_active() { return 0 }
+        let rewound = self.rewind_buffer.as_mut().and_then(|rewind_buffer| rewind_buffer.rewind(frames));
+        if let Some((state, frames_rewound)) = rewound {
+            self.load_machine_state(&mut StateReader::new(&state)).unwrap();
+            frames_rewound
+        } else { 0 }
+    }
+
     pub fn peek_mem(&self, region: VisibleMemoryRegion, addr: usize) -> u8 {
         self.io.peek_mem(region, addr as u32)
     }
+
+    pub fn poke_mem(&mut self, region: VisibleMemoryRegion, addr: usize, value: u8) {
+        self.io.poke_mem(region, addr as u32, value)
+    }
+
+    // Includes what was written to write only registers
+    pub fn peek_io_register(&self, register: &IORegisterInfo) -> u32 {
+        (0..register.size).rev().fold(0, |value, i| value << 8 | self.io.inspect_io_register(register.addr + i) as u32)
+    }
+
+    // Only the bytes holding the field are written, without side effects, so e.g. setting an IF flag doesn't
+    // acknowledge the others
+    pub fn poke_io_register(&mut self, register: &IORegisterInfo, field_i: usize, field_value: u32) {
+        let value = register.set_field(self.peek_io_register(register), field_i, field_value);
+        let (_, bit, width) = register.fields[field_i];
+        let addr = register.addr - VisibleMemoryRegion::IO.get_start_addr();
+        for i in bit / 8 ..= (bit + width - 1) / 8 {
+            self.io.poke_mem(VisibleMemoryRegion::IO, addr + i, (value >> (8 * i)) as u8)
+        }
+    }
+
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) { self.io.set_cheats(cheats) }
+
+    pub fn cheats(&self) -> &[Cheat] { self.io.get_cheats() }
+
+    // False if there's no cheat at index
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        self.io.set_cheat_enabled(index, enabled)
+    }
+
+    pub fn save_data(&self) -> &[u8] { self.io.get_save_data() }
+
+    pub fn set_link(&mut self, link: Box<dyn LinkTransport>) { self.io.set_link(link) }
+
+    // Emulation speed as a multiple of full speed, so that audio comes out at the sink's rate regardless
+    pub fn set_audio_speed(&mut self, speed: f64) { self.io.set_audio_speed(speed) }
+
+    // Skipped frames aren't drawn or sent to the render channel, but the framebuffer is part of a movie's checksums
+    pub fn skip_rendering(&mut self, skip: bool) { self.io.set_skip_rendering(skip && !self.movie_active()) }
+
+    // Only changes what's heard, so games and movies play out the same
+    pub fn mute_audio_channel(&mut self, channel: AudioChannel, muted: bool) { self.io.set_audio_channel_muted(channel, muted) }
+
+    // While any channel is soloed, only soloed channels are heard
+    pub fn solo_audio_channel(&mut self, channel: AudioChannel, soloed: bool) {
+        self.io.set_audio_channel_soloed(channel, soloed)
+    }
+
+    // Keeps each channel's most recent samples for AudioState::scope
+    pub fn enable_audio_scope(&mut self, enable: bool) { self.io.enable_audio_scope(enable) }
+
+    pub fn audio_state(&self) -> AudioState { self.io.get_audio_state() }
+
+    // Writes 16-bit WAV files at AUDIO_SAMPLE_RATE from emulated time, so recordings don't depend on speed or the
+    // audio device. With channels, each channel is also written before mixing, see AudioChannel::recording_path
+    pub fn start_audio_recording(&mut self, path: &Path, channels: bool) -> io::Result<()> {
+        self.io.start_audio_recording(path, channels)
+    }
+
+    // Does nothing if not recording
+    pub fn stop_audio_recording(&mut self) -> io::Result<()> { self.io.stop_audio_recording() }
+
+    // Messages printed through mGBA's debug registers since the last call
+    pub fn take_debug_messages(&mut self) -> Vec<(MGBALogLevel, String)> { self.io.take_debug_messages() }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = GBA::STATE_MAGIC.to_vec();
+        GBA::STATE_VERSION.save_state(&mut state);
+        state.extend_from_slice(&self.rom_id());
+        self.rom_checksum.save_state(&mut state);
+
+        self.save_machine_state(&mut state);
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader::new(state);
+        if reader.read_bytes(GBA::STATE_MAGIC.len()).ok() != Some(GBA::STATE_MAGIC) {
+            return Err(StateError::InvalidHeader)
+        }
+        let version = reader.read::<u32>()?;
+        if version != GBA::STATE_VERSION { return Err(StateError::UnsupportedVersion(version)) }
+        if self.movie_active() { return Err(StateError::MovieActive) }
+        let rom_id = reader.read_bytes(GBA::ROM_ID_LEN)?;
+        if reader.read::<u32>()? != self.rom_checksum {
+            let title = String::from_utf8_lossy(&rom_id[..12]).trim_end_matches('\0').to_string();
+            return Err(StateError::RomMismatch { title })
+        }
+
+        // Restore the current state if the new one turns out to be corrupt
+        let mut prev_state = Vec::new();
+        self.save_machine_state(&mut prev_state);
+        let result = self.load_machine_state(&mut reader);
+        if result.is_err() { self.load_machine_state(&mut StateReader::new(&prev_state)).unwrap() }
+        result
+    }
+
+    fn save_machine_state(&self, state: &mut Vec<u8>) {
+        self.cpu.save_state(state);
+        self.io.save_state(state);
+        self.next_frame_cycle.save_state(state);
+    }
+
+    fn load_machine_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.cpu.load_state(state)?;
+        self.io.load_state(state)?;
+        self.io.schedule_serial_poll();
+        self.next_frame_cycle.load_state(state)?;
+        if state.is_empty() { Ok(()) } else { Err(StateError::InvalidValue) }
+    }
+
+    // Game title and code from the cartridge header
+    fn rom_id(&self) -> [u8; GBA::ROM_ID_LEN] {
+        let mut rom_id = [0; GBA::ROM_ID_LEN];
+        for (i, byte) in self.io.get_rom().iter().skip(0xA0).take(GBA::ROM_ID_LEN).enumerate() { rom_id[i] = *byte }
+        rom_id
+    }
 }
 
 pub const WIDTH: usize = 240;
 pub const HEIGHT: usize = 160;
 pub const SCALE: usize = 2;
 
+// The rate recordings are written at and that NullSink asks for, the mixer's rate before SOUNDBIAS raises it
 pub const AUDIO_SAMPLE_RATE: usize = 0x8000;
 pub const AUDIO_BUFFER_LEN: usize = 0x1000;
 pub const CLOCK_FREQ: usize = 1 << 24;
@@ -56,7 +323,7 @@ pub const FRAME_PERIOD: std::time::Duration = std::time::Duration::from_nanos(
     1e9 as u64 * CLOCKS_PER_FRAME as u64 / CLOCK_FREQ as u64
 );
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum VisibleMemoryRegion {
     BIOS = 0,
     EWRAM = 1,
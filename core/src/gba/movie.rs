@@ -0,0 +1,179 @@
+use std::fmt;
+
+use crate::savestate::{SaveState, StateReader, StateError};
+
+// Inputs for every frame since power on, along with everything else that decides how the game plays out
+pub struct Movie {
+    rom_checksum: u32,
+    // Whether SWIs were emulated at a high level instead of running a BIOS dump
+    hle_bios: bool,
+    save_data: Vec<u8>,
+    rtc_date_time: [u8; 7],
+    inputs: Vec<u16>,
+    // Frame and checksum of RAM and the framebuffer at the end of it
+    checksums: Vec<(u32, u32)>,
+}
+
+impl Movie {
+    const MAGIC: &'static [u8] = b"GBAM";
+    const VERSION: u32 = 2;
+    pub const CHECKSUM_INTERVAL: usize = 60;
+    // Highest value of each RTC date and time byte, all of which are BCD
+    const RTC_MAX: [u8; 7] = [0x99, 0x12, 0x31, 0x07, 0x23, 0x59, 0x59];
+
+    pub(super) fn new(rom_checksum: u32, hle_bios: bool, save_data: Vec<u8>, rtc_date_time: [u8; 7]) -> Movie {
+        Movie {
+            rom_checksum,
+            hle_bios,
+            save_data,
+            rtc_date_time,
+            inputs: Vec::new(),
+            checksums: Vec::new(),
+        }
+    }
+
+    pub fn load(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut reader = StateReader::new(data);
+        if reader.read_bytes(Movie::MAGIC.len()).ok() != Some(Movie::MAGIC) { return Err(MovieError::InvalidHeader) }
+        let version = reader.read::<u32>()?;
+        if version != Movie::VERSION { return Err(MovieError::UnsupportedVersion(version)) }
+        let rom_checksum = reader.read::<u32>()?;
+        let hle_bios = reader.read::<bool>()?;
+        let rtc_date_time = reader.read::<[u8; 7]>()?;
+        let valid_bcd = |(&value, &max): (&u8, &u8)| value & 0xF < 0xA && value >> 4 < 0xA && value <= max;
+        if !rtc_date_time.iter().zip(Movie::RTC_MAX.iter()).all(valid_bcd) { return Err(MovieError::Corrupt) }
+        let save_data = reader.read::<Vec<u8>>()?;
+        let inputs = (0..reader.read::<u32>()?).map(|_| reader.read::<u16>()).collect::<Result<_, _>>()?;
+        let checksums = (0..reader.read::<u32>()?).map(|_| Ok((reader.read::<u32>()?, reader.read::<u32>()?)))
+            .collect::<Result<_, StateError>>()?;
+        if !reader.is_empty() { return Err(MovieError::Corrupt) }
+        Ok(Movie {
+            rom_checksum,
+            hle_bios,
+            save_data,
+            rtc_date_time,
+            inputs,
+            checksums,
+        })
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        let mut data = Movie::MAGIC.to_vec();
+        Movie::VERSION.save_state(&mut data);
+        self.rom_checksum.save_state(&mut data);
+        self.hle_bios.save_state(&mut data);
+        self.rtc_date_time.save_state(&mut data);
+        self.save_data.save_state(&mut data);
+        (self.inputs.len() as u32).save_state(&mut data);
+        for input in self.inputs.iter() { input.save_state(&mut data) }
+        (self.checksums.len() as u32).save_state(&mut data);
+        for (frame, checksum) in self.checksums.iter() {
+            frame.save_state(&mut data);
+            checksum.save_state(&mut data);
+        }
+        data
+    }
+
+    pub fn len(&self) -> usize { self.inputs.len() }
+
+    pub fn is_empty(&self) -> bool { self.inputs.is_empty() }
+
+    pub fn rom_checksum(&self) -> u32 { self.rom_checksum }
+
+    pub fn hle_bios(&self) -> bool { self.hle_bios }
+
+    // Empty when the game started without any save data
+    pub fn save_data(&self) -> &[u8] { &self.save_data }
+
+    pub fn rtc_date_time(&self) -> [u8; 7] { self.rtc_date_time }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovieStatus {
+    pub recording: bool,
+    // Frames recorded or played back so far
+    pub frame: usize,
+    pub len: usize,
+    // First frame whose checksum didn't match the movie's
+    pub desync: Option<usize>,
+}
+
+impl MovieStatus {
+    pub fn finished(&self) -> bool { !self.recording && self.frame >= self.len }
+}
+
+// A movie that's being recorded or played back by a GBA
+pub(super) struct ActiveMovie {
+    movie: Movie,
+    recording: bool,
+    frame: usize,
+    desync: Option<usize>,
+}
+
+impl ActiveMovie {
+    pub fn record(movie: Movie) -> ActiveMovie { ActiveMovie { movie, recording: true, frame: 0, desync: None } }
+
+    pub fn play(movie: Movie) -> ActiveMovie { ActiveMovie { movie, recording: false, frame: 0, desync: None } }
+
+    pub fn status(&self) -> MovieStatus {
+        MovieStatus { recording: self.recording, frame: self.frame, len: self.movie.len(), desync: self.desync }
+    }
+
+    // Records the keys that were just polled, or returns the ones to use instead while playing back
+    pub fn start_frame(&mut self, keyinput: u16) -> Option<u16> {
+        if self.recording {
+            self.movie.inputs.push(keyinput);
+            None
+        } else { self.movie.inputs.get(self.frame).copied() }
+    }
+
+    // Only frames with a checksum need it calculated
+    pub fn end_frame(&mut self, checksum: impl FnOnce() -> u32) {
+        let frame = self.frame;
+        if frame >= self.movie.len() { return }
+        self.frame += 1;
+        if self.recording {
+            if self.frame.is_multiple_of(Movie::CHECKSUM_INTERVAL) { self.movie.checksums.push((frame as u32, checksum())) }
+        } else if let Ok(i) = self.movie.checksums.binary_search_by_key(&(frame as u32), |&(frame, _)| frame) {
+            if self.desync.is_none() && self.movie.checksums[i].1 != checksum() {
+                warn!("Movie desynced at frame {}", frame);
+                self.desync = Some(frame);
+            }
+        }
+    }
+
+    // The last frame of a recording always gets a checksum
+    pub fn finish(mut self, checksum: impl FnOnce() -> u32) -> Movie {
+        let last_frame = self.frame as u32;
+        if self.recording && self.frame > 0 &&
+            self.movie.checksums.last().map(|&(frame, _)| frame) != Some(last_frame - 1) {
+            self.movie.checksums.push((last_frame - 1, checksum()));
+        }
+        self.movie
+    }
+
+    pub fn is_active(&self) -> bool { !self.status().finished() }
+}
+
+#[derive(Debug)]
+pub enum MovieError {
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    Corrupt,
+}
+
+impl From<StateError> for MovieError {
+    fn from(_: StateError) -> MovieError { MovieError::Corrupt }
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::InvalidHeader => write!(f, "Not a movie"),
+            MovieError::UnsupportedVersion(version) => write!(f, "Unsupported movie version {}", version),
+            MovieError::Corrupt => write!(f, "Movie is truncated or corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
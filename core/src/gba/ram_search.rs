@@ -0,0 +1,110 @@
+use super::GBA;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchComparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+}
+
+impl SearchComparison {
+    fn test(&self, a: i64, b: i64) -> bool {
+        match self {
+            SearchComparison::Equal => a == b,
+            SearchComparison::NotEqual => a != b,
+            SearchComparison::Less => a < b,
+            SearchComparison::Greater => a > b,
+            SearchComparison::LessEqual => a <= b,
+            SearchComparison::GreaterEqual => a >= b,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchFilter {
+    // Compares the current value with the given one
+    Value(SearchComparison, u32),
+    // Compares the current value with the one at the last snapshot
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+// Narrows EWRAM and IWRAM down to the addresses that pass every filter, e.g. to find where a game keeps its HP
+pub struct RAMSearch {
+    size: u32,
+    signed: bool,
+    // Matching addresses and their values at the last snapshot
+    candidates: Vec<(u32, u32)>,
+}
+
+impl RAMSearch {
+    // Every aligned value of the given size starts out as a candidate
+    pub fn new(gba: &GBA, size: u32, signed: bool) -> RAMSearch {
+        assert!(size == 1 || size == 2 || size == 4);
+        let (ewram, iwram) = (gba.io.get_ewram(), gba.io.get_iwram());
+        let candidates = [(0x0200_0000, ewram), (0x0300_0000, iwram)].iter().flat_map(|&(start, mem)| {
+            (0..mem.len() as u32).step_by(size as usize).map(move |offset| (start + offset, RAMSearch::read(mem, offset, size)))
+        }).collect();
+        RAMSearch {
+            size,
+            signed,
+            candidates,
+        }
+    }
+
+    // Drops the candidates that don't match and snapshots the rest
+    pub fn filter(&mut self, gba: &GBA, filter: SearchFilter) {
+        let (size, signed) = (self.size, self.signed);
+        let extend = |value: u32| RAMSearch::extend(value, size, signed);
+        self.candidates.retain_mut(|(addr, snapshot)| {
+            let value = RAMSearch::read_addr(gba, *addr, size);
+            let (current, previous) = (extend(value), extend(*snapshot));
+            let matches = match filter {
+                SearchFilter::Value(comparison, value) => comparison.test(current, extend(value)),
+                SearchFilter::Changed => current != previous,
+                SearchFilter::Unchanged => current == previous,
+                SearchFilter::Increased => current > previous,
+                SearchFilter::Decreased => current < previous,
+            };
+            *snapshot = value;
+            matches
+        });
+    }
+
+    pub fn snapshot(&mut self, gba: &GBA) {
+        let size = self.size;
+        for (addr, snapshot) in self.candidates.iter_mut() { *snapshot = RAMSearch::read_addr(gba, *addr, size) }
+    }
+
+    pub fn candidates(&self) -> &[(u32, u32)] { &self.candidates }
+
+    pub fn size(&self) -> u32 { self.size }
+
+    pub fn signed(&self) -> bool { self.signed }
+
+    // The value's bits as a signed or unsigned number of the search's size
+    pub fn value(&self, value: u32) -> i64 { RAMSearch::extend(value, self.size, self.signed) }
+
+    pub fn current(&self, gba: &GBA, addr: u32) -> u32 { RAMSearch::read_addr(gba, addr, self.size) }
+
+    fn read_addr(gba: &GBA, addr: u32, size: u32) -> u32 {
+        match addr >> 24 {
+            0x02 => RAMSearch::read(gba.io.get_ewram(), addr & 0x3FFFF, size),
+            _ => RAMSearch::read(gba.io.get_iwram(), addr & 0x7FFF, size),
+        }
+    }
+
+    fn read(mem: &[u8], offset: u32, size: u32) -> u32 {
+        (0..size).rev().fold(0, |value, i| value << 8 | mem[(offset + i) as usize] as u32)
+    }
+
+    fn extend(value: u32, size: u32, signed: bool) -> i64 {
+        let shift = 32 - size * 8;
+        if signed { ((value << shift) as i32 >> shift) as i64 } else { (value << shift >> shift) as i64 }
+    }
+}
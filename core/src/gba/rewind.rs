@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+// Keeps the newest snapshot in full and every older snapshot as a delta against the one after it
+pub struct RewindBuffer {
+    capacity: usize,
+    interval: usize,
+    frame: usize,
+    latest: Option<(usize, Vec<u8>)>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize, interval: usize) -> RewindBuffer {
+        assert!(capacity > 0 && interval > 0);
+        RewindBuffer {
+            capacity,
+            interval,
+            frame: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    // Returns whether a snapshot should be pushed for the frame that just finished
+    pub fn on_frame(&mut self) -> bool {
+        self.frame += 1;
+        match self.latest {
+            Some((snapshot_frame, _)) => self.frame - snapshot_frame >= self.interval,
+            None => true,
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some((_, prev_state)) = self.latest.take() {
+            self.deltas.push_back(RewindBuffer::encode_delta(&state, &prev_state));
+            if self.deltas.len() >= self.capacity { self.deltas.pop_front(); }
+        }
+        self.latest = Some((self.frame, state));
+    }
+
+    // Returns the newest snapshot at least the given number of frames old and how far back it is
+    pub fn rewind(&mut self, frames: usize) -> Option<(Vec<u8>, usize)> {
+        let (mut snapshot_frame, mut state) = self.latest.take()?;
+        let target_frame = self.frame.saturating_sub(frames);
+        while snapshot_frame > target_frame {
+            match self.deltas.pop_back() {
+                Some(delta) => {
+                    state = RewindBuffer::apply_delta(&state, &delta);
+                    snapshot_frame -= self.interval;
+                },
+                None => break,
+            }
+        }
+        let rewound = self.frame - snapshot_frame;
+        self.frame = snapshot_frame;
+        self.latest = Some((snapshot_frame, state.clone()));
+        Some((state, rewound))
+    }
+
+    // Runs of unchanged bytes are stored as counts, followed by the XOR of the bytes that changed
+    fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+        let mut delta = Vec::new();
+        RewindBuffer::write_len(&mut delta, to.len());
+        let xor = |i: usize| to[i] ^ from.get(i).copied().unwrap_or(0);
+        let mut i = 0;
+        while i < to.len() {
+            let zeros_start = i;
+            while i < to.len() && xor(i) == 0 { i += 1 }
+            let literals_start = i;
+            while i < to.len() && xor(i) != 0 { i += 1 }
+            RewindBuffer::write_len(&mut delta, literals_start - zeros_start);
+            RewindBuffer::write_len(&mut delta, i - literals_start);
+            delta.extend((literals_start..i).map(xor));
+        }
+        delta
+    }
+
+    fn apply_delta(from: &[u8], delta: &[u8]) -> Vec<u8> {
+        let mut pos = 0;
+        let len = RewindBuffer::read_len(delta, &mut pos);
+        let mut state = from.to_vec();
+        state.resize(len, 0);
+        let mut i = 0;
+        while pos < delta.len() {
+            i += RewindBuffer::read_len(delta, &mut pos);
+            let literals_len = RewindBuffer::read_len(delta, &mut pos);
+            for byte in delta[pos..pos + literals_len].iter() {
+                state[i] ^= *byte;
+                i += 1;
+            }
+            pos += literals_len;
+        }
+        state
+    }
+
+    fn write_len(delta: &mut Vec<u8>, mut len: usize) {
+        while len >= 0x80 {
+            delta.push(len as u8 | 0x80);
+            len >>= 7;
+        }
+        delta.push(len as u8);
+    }
+
+    fn read_len(delta: &[u8], pos: &mut usize) -> usize {
+        let mut len = 0;
+        let mut shift = 0;
+        loop {
+            let byte = delta[*pos];
+            *pos += 1;
+            len |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 { return len }
+            shift += 7;
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::cpu::CPU;
+
+// One line per executed instruction in fixed columns, so traces can be diffed line by line:
+// cycle, address, opcode, disassembly, then every register the instruction changed
+pub struct Trace {
+    writer: BufWriter<File>,
+    regs: [u32; 16],
+    cpsr: u32,
+    line: String,
+}
+
+impl Trace {
+    pub fn new(path: &Path) -> io::Result<Trace> {
+        Ok(Trace {
+            writer: BufWriter::new(File::create(path)?),
+            regs: [0; 16],
+            cpsr: 0,
+            line: String::new(),
+        })
+    }
+
+    pub fn start_instr(&mut self, cpu: &CPU, cycle: usize) {
+        for (reg, value) in self.regs.iter_mut().enumerate() { *value = cpu.get_reg(reg) }
+        self.cpsr = cpu.get_cpsr();
+        let (instr, text) = cpu.next_instr();
+        let opcode = if cpu.in_thumb() { format!("    {:04X}", instr) } else { format!("{:08X}", instr) };
+        self.line = format!("{:>12} {:08X} {} {:<32}", cycle, self.regs[15], opcode, text);
+    }
+
+    pub fn end_instr(&mut self, cpu: &CPU) -> io::Result<()> {
+        // PC is left out since the next line starts with it
+        for reg in 0..15 {
+            let value = cpu.get_reg(reg);
+            if value != self.regs[reg] { self.line += &format!(" r{}={:08X}", reg, value) }
+        }
+        if cpu.get_cpsr() != self.cpsr { self.line += &format!(" cpsr={:08X}", cpu.get_cpsr()) }
+        writeln!(self.writer, "{}", self.line.trim_end())
+    }
+}
@@ -1,78 +1,56 @@
-extern crate sdl2;
-
-use sdl2::audio::{AudioCallback, AudioSpecDesired};
-pub use sdl2::audio::AudioDevice;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 
 use crate::gba;
 
-pub struct Audio {
-    buffer: [i16; gba::AUDIO_BUFFER_LEN],
-    read_i: usize,
-    write_i: usize,
-    count: usize,
-}
-
-impl Audio {
-    const DESIRED_SPEC: AudioSpecDesired = AudioSpecDesired {
-        freq: Some(gba::AUDIO_SAMPLE_RATE as i32),
-        channels: Some(2),
-        samples: None,
-    };
-
-    const VOLUME_FACTOR: i16 = 8;
+pub type SampleBuffer = Arc<Mutex<VecDeque<(i16, i16)>>>;
 
-    pub fn new() -> AudioDevice<Audio> {
-        let sdl_ctx = sdl2::init().unwrap();
-        let audio_subsystem = sdl_ctx.audio().unwrap();
+// The APU resamples its output to whatever rate the sink asks for, which it checks before every sample
+pub trait AudioSink: Send {
+    fn push_sample(&mut self, left_sample: i16, right_sample: i16);
+    fn sample_rate(&self) -> usize;
+}
 
-        let device = audio_subsystem
-        .open_playback(None, &Audio::DESIRED_SPEC, |_spec| {
-            Audio {
-                buffer: [0; gba::AUDIO_BUFFER_LEN],
-                read_i: 0,
-                write_i: 0,
-                count: 0,
-            }
-        }).unwrap();
-        device.resume();
-        device
-    }
+pub struct NullSink;
 
-    pub fn queue(&mut self, left_sample: i16, right_sample: i16) {
-        self.push(Audio::VOLUME_FACTOR * left_sample);
-        self.push(Audio::VOLUME_FACTOR * right_sample);
-    }
-
-    fn push(&mut self, sample: i16) {
-        self.buffer[self.write_i] = sample;
-        self.write_i = (self.write_i + 1) % gba::AUDIO_BUFFER_LEN;
-        self.count = std::cmp::min(self.count + 1, self.buffer.len());
-    }
+impl AudioSink for NullSink {
+    fn push_sample(&mut self, _left_sample: i16, _right_sample: i16) {}
+    fn sample_rate(&self) -> usize { gba::AUDIO_SAMPLE_RATE }
+}
 
-    fn pop(&mut self) -> i16 {
-        let value = self.buffer[self.read_i];
-        self.count -= 1;
-        self.read_i = (self.read_i + 1) % gba::AUDIO_BUFFER_LEN;
-        value
-    }
+// Buffers samples for a consumer on another thread, nudging the rate it asks for so that the buffer stays about a
+// quarter full, because the consumer's clock never quite matches the emulator's
+pub struct RingBufferSink {
+    buffer: SampleBuffer,
+    capacity: usize,
+    sample_rate: usize,
+}
 
-    fn peek(&self, i: usize) -> i16 {
-        self.buffer[(self.read_i + i) % gba::AUDIO_BUFFER_LEN]
+impl RingBufferSink {
+    // Small enough to not be heard as a change in pitch
+    const MAX_RATE_ADJUST: f64 = 0.005;
+
+    pub fn new(capacity: usize, sample_rate: usize) -> (RingBufferSink, SampleBuffer) {
+        let buffer = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+        let consumer_buffer = Arc::clone(&buffer);
+        (RingBufferSink {
+            buffer,
+            capacity,
+            sample_rate,
+        }, consumer_buffer)
     }
 }
 
-impl AudioCallback for Audio {
-    type Channel = i16;
+impl AudioSink for RingBufferSink {
+    fn push_sample(&mut self, left_sample: i16, right_sample: i16) {
+        let mut buffer = self.buffer.lock().unwrap();
+        // Dropping new samples rather than old ones keeps what's already queued continuous
+        if buffer.len() < self.capacity { buffer.push_back((left_sample, right_sample)) }
+    }
 
-    fn callback(&mut self, out: &mut [i16]) {
-        if self.count < out.len() {
-            for (i, x) in out.iter_mut().enumerate() {
-                *x = self.peek(i % gba::AUDIO_BUFFER_LEN);
-            }
-        } else {
-            for x in out.iter_mut() {
-                *x = self.pop();
-            }
-        }
+    fn sample_rate(&self) -> usize {
+        let target = self.capacity / 4;
+        let error = (target as f64 - self.buffer.lock().unwrap().len() as f64) / target as f64;
+        (self.sample_rate as f64 * (1.0 + RingBufferSink::MAX_RATE_ADJUST * error.clamp(-1.0, 1.0))).round() as usize
     }
 }
@@ -1,6 +1,8 @@
 use num_traits as num;
 use num::{NumAssign, Unsigned};
 
+use crate::savestate::{SaveState, StateReader, StateError};
+
 pub struct Sweep {
     // Registers
     shift: u8,
@@ -196,3 +198,26 @@ impl<T: NumAssign + Unsigned + Copy> Timer<T> {
         self.counter = reload;
     }
 }
+
+impl Timer<u16> {
+    // The same as clocking it the given number of times, returning how many times it reloaded
+    pub fn clock_many(&mut self, cycles: usize, reload: u16) -> usize {
+        let counter = self.counter as usize;
+        if cycles < counter {
+            self.counter -= cycles as u16;
+            return 0
+        }
+        let cycles_after_reload = cycles - counter;
+        self.counter = reload - (cycles_after_reload % reload as usize) as u16;
+        1 + cycles_after_reload / reload as usize
+    }
+}
+
+impl_save_state!(Sweep { shift, negate, period, enabled, timer, freq, freq_shadow, freq_overflowed });
+impl_save_state!(LengthCounter { length });
+impl_save_state!(Envelope { step_period, inc, initial_volume, cur_volume, timer, active });
+
+impl<T: NumAssign + Unsigned + Copy + SaveState> SaveState for Timer<T> {
+    fn save_state(&self, state: &mut Vec<u8>) { self.counter.save_state(state) }
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> { self.counter.load_state(state) }
+}
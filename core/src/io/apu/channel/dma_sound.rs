@@ -38,6 +38,10 @@ impl DMASound {
         } else { false }
     }
 
+    pub fn fifo_len(&self) -> usize { self.fifo.len() }
+
+    pub fn timer(&self) -> usize { self.timer_select as usize }
+
     pub fn read_cnt(&self) -> u8 {
         self.timer_select << 2 | self.enable_left << 1 | self.enable_right
     }
@@ -67,3 +71,6 @@ impl Channel for DMASound {
     }
 }
 
+
+impl_save_state!(DMASound { enable_right, enable_left, timer_select, fifo, sample }
+    where |sound| sound.timer_select < 2 && sound.fifo.len() <= 32);
@@ -1,6 +1,7 @@
 use super::components::*;
 
 use super::{Channel, Scheduler, IORegister};
+use crate::gba;
 
 pub struct Noise {
     // Registers
@@ -38,6 +39,24 @@ impl Noise {
         if self.ratio == 0 { interval / 2 } else { interval * self.ratio as u16 }
     }
 
+    // Of shifts of the LFSR
+    pub fn frequency(&self) -> f64 {
+        match self.calc_reload() {
+            0 => 0.0,
+            reload => gba::CLOCK_FREQ as f64 / reload as f64,
+        }
+    }
+
+    pub fn lfsr_width(&self) -> u8 { if self.counter_width { 7 } else { 15 } }
+
+    // Like read, but with the length that can't be read back
+    pub fn inspect(&self, byte: u8) -> u8 {
+        match byte {
+            0 => self.length_reload,
+            _ => self.read(byte),
+        }
+    }
+
     pub fn clock(&mut self) {
         if !self.is_on() { return }
         let reload = self.calc_reload();
@@ -47,6 +66,17 @@ impl Noise {
             if carry { self.lfsr ^= [0x6000, 0x60][self.counter_width as usize] }
         }
     }
+
+    pub fn clock_many(&mut self, cycles: usize) {
+        if !self.is_on() { return }
+        let reload = self.calc_reload();
+        if reload == 0 { return }
+        for _ in 0..self.timer.clock_many(cycles, reload) {
+            let carry = self.lfsr & 0x1 != 0;
+            self.lfsr >>= 1;
+            if carry { self.lfsr ^= [0x6000, 0x60][self.counter_width as usize] }
+        }
+    }
 }
 
 impl Channel for Noise {
@@ -102,3 +132,7 @@ impl IORegister for Noise {
         }
     }
 }
+
+impl_save_state!(Noise {
+    length_reload, envelope, ratio, counter_width, shift, use_length, length_counter, timer, lfsr,
+} where |noise| noise.shift <= 0xF && noise.ratio <= 7);
@@ -1,6 +1,7 @@
 use super::components::*;
 
 use super::{Channel, Scheduler, IORegister};
+use crate::gba;
 
 pub struct Tone {
     // Registers
@@ -45,11 +46,32 @@ impl Tone {
         16 * (2048 - self.sweep.freq)
     }
 
+    // Of the whole waveform, which takes 8 duty steps
+    pub fn frequency(&self) -> f64 { gba::CLOCK_FREQ as f64 / (self.calc_reload() as f64 * 8.0) }
+
+    // Fraction of the waveform that's high
+    pub fn duty(&self) -> f32 { [0.125, 0.25, 0.5, 0.75][self.duty as usize] }
+
     pub fn clock(&mut self) {
         if self.timer.clock_with_reload(self.calc_reload()) {
             self.duty_pos = (self.duty_pos + 1) % 8;
         }
     }
+
+    pub fn clock_many(&mut self, cycles: usize) {
+        let steps = self.timer.clock_many(cycles, self.calc_reload());
+        self.duty_pos = (self.duty_pos + steps) % 8;
+    }
+
+    // Like read, but with the length and frequency that can't be read back
+    pub fn inspect(&self, byte: u8) -> u8 {
+        match byte {
+            2 => self.duty << 6 | self.length_reload,
+            4 => self.sweep.freq as u8,
+            5 => (self.use_length as u8) << 6 | (self.sweep.freq >> 8) as u8,
+            _ => self.read(byte),
+        }
+    }
 }
 
 impl Channel for Tone {
@@ -104,3 +126,6 @@ impl IORegister for Tone {
         }
     }
 }
+
+impl_save_state!(Tone { sweep, length_reload, duty, envelope, use_length, length_counter, timer, duty_pos }
+    where |tone| tone.duty < 4 && tone.duty_pos < 8);
@@ -1,6 +1,7 @@
 use super::components::*;
 
 use super::{Channel, Scheduler, IORegister};
+use crate::gba;
 
 pub struct Wave {
     // Registers
@@ -55,6 +56,25 @@ impl Wave {
         }
     }
 
+    pub fn clock_many(&mut self, cycles: usize) {
+        let steps = self.wave_ram_i + self.timer.clock_many(cycles, self.calc_reload());
+        self.wave_ram_i = steps % 32;
+        if self.use_two_banks && steps / 32 % 2 == 1 { self.wave_ram_bank ^= 1 }
+    }
+
+    // Of the whole waveform, which is 32 samples or 64 when both banks are used
+    pub fn frequency(&self) -> f64 {
+        let samples = if self.use_two_banks { 64.0 } else { 32.0 };
+        gba::CLOCK_FREQ as f64 / (self.calc_reload() as f64 * samples)
+    }
+
+    // As a percentage
+    pub fn volume(&self) -> u8 { if self.force_volume { 75 } else { [0, 100, 50, 25][self.volume as usize] } }
+
+    pub fn playing_bank(&self) -> usize { self.wave_ram_bank as usize }
+
+    pub fn uses_two_banks(&self) -> bool { self.use_two_banks }
+
     pub fn read_wave_ram(&self, offset: u32) -> u8 {
         self.wave_ram[(self.wave_ram_bank as usize) ^ 1][offset as usize]
     }
@@ -62,6 +82,16 @@ impl Wave {
     pub fn write_wave_ram(&mut self, offset: u32, value: u8) {
         self.wave_ram[(self.wave_ram_bank as usize) ^ 1][offset as usize] = value;
     }
+
+    // Like read, but with the length and sample rate that can't be read back
+    pub fn inspect(&self, byte: u8) -> u8 {
+        match byte {
+            2 => self.length_reload,
+            4 => self.sample_rate as u8,
+            5 => (self.use_length as u8) << 6 | (self.sample_rate >> 8) as u8,
+            _ => self.read(byte),
+        }
+    }
 }
 
 impl Channel for Wave {
@@ -83,7 +113,7 @@ impl IORegister for Wave {
             0 => (self.enabled as u8) << 7 | self.wave_ram_bank << 6 | (self.use_two_banks as u8) << 5,
             1 => 0,
             2 => 0,
-            3 => self.volume << 5,
+            3 => (self.force_volume as u8) << 7 | self.volume << 5,
             4 => 0,
             5 => (self.use_length as u8) << 6,
             6 | 7 => 0,
@@ -119,3 +149,8 @@ impl IORegister for Wave {
         }
     }
 }
+
+impl_save_state!(Wave {
+    use_two_banks, wave_ram_bank, enabled, length_reload, volume, force_volume, sample_rate, use_length,
+    length_counter, wave_ram, wave_ram_i, timer,
+} where |wave| wave.wave_ram_bank < 2 && wave.volume < 4 && wave.wave_ram_i < 32);
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use super::APU;
+use super::channel::{Channel, DMASound};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioChannel {
+    Tone1 = 0,
+    Tone2 = 1,
+    Wave = 2,
+    Noise = 3,
+    SoundA = 4,
+    SoundB = 5,
+}
+
+impl AudioChannel {
+    pub const ALL: [AudioChannel; 6] = [
+        AudioChannel::Tone1, AudioChannel::Tone2, AudioChannel::Wave, AudioChannel::Noise,
+        AudioChannel::SoundA, AudioChannel::SoundB,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioChannel::Tone1 => "Tone 1",
+            AudioChannel::Tone2 => "Tone 2",
+            AudioChannel::Wave => "Wave",
+            AudioChannel::Noise => "Noise",
+            AudioChannel::SoundA => "DMA Sound A",
+            AudioChannel::SoundB => "DMA Sound B",
+        }
+    }
+
+    // Where the channel goes when recording it separately, e.g. music.wav has music.tone1.wav through music.sound_b.wav
+    pub fn recording_path(&self, path: &Path) -> PathBuf {
+        let name = match self {
+            AudioChannel::Tone1 => "tone1",
+            AudioChannel::Tone2 => "tone2",
+            AudioChannel::Wave => "wave",
+            AudioChannel::Noise => "noise",
+            AudioChannel::SoundA => "sound_a",
+            AudioChannel::SoundB => "sound_b",
+        };
+        let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{}.wav", name));
+        path.with_file_name(file_name)
+    }
+}
+
+// Frequencies are in Hz and volumes are out of 15 for channels with an envelope, or a percentage otherwise
+#[derive(Clone, Debug)]
+pub enum AudioChannelState {
+    Tone { playing: bool, frequency: f64, duty: f32, volume: u8 },
+    Wave { playing: bool, frequency: f64, volume: u8, wave_ram: [[u8; 16]; 2], playing_bank: usize, two_banks: bool },
+    Noise { playing: bool, frequency: f64, volume: u8, lfsr_width: u8 },
+    DMASound { fifo_len: usize, volume: u8, timer: usize },
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioState {
+    pub master_enable: bool,
+    pub channels: Vec<AudioChannelState>,
+    pub muted: [bool; 6],
+    pub soloed: [bool; 6],
+    pub recording: bool,
+    // Each channel's most recent samples before mixing, while the scope is enabled
+    pub scope: Vec<[i16; 6]>,
+}
+
+impl APU {
+    // About 30ms at full speed and the default mixer rate
+    const SCOPE_LEN: usize = 1024;
+
+    pub fn set_channel_muted(&mut self, channel: AudioChannel, muted: bool) { self.muted[channel as usize] = muted }
+
+    pub fn set_channel_soloed(&mut self, channel: AudioChannel, soloed: bool) { self.soloed[channel as usize] = soloed }
+
+    pub fn enable_scope(&mut self, enable: bool) {
+        self.scope = if enable { Some(VecDeque::with_capacity(APU::SCOPE_LEN)) } else { None };
+    }
+
+    pub(super) fn record_scope(&mut self, samples: [i16; 6]) {
+        if let Some(scope) = &mut self.scope {
+            if scope.len() == APU::SCOPE_LEN { scope.pop_front(); }
+            scope.push_back(samples);
+        }
+    }
+
+    pub(super) fn mute(&self, mut samples: [i16; 6]) -> [i16; 6] {
+        let soloing = self.soloed.contains(&true);
+        for (i, sample) in samples.iter_mut().enumerate() {
+            if self.muted[i] || soloing && !self.soloed[i] { *sample = 0 }
+        }
+        samples
+    }
+
+    pub fn get_state(&self) -> AudioState {
+        let dma_sound = |sound: &DMASound, volume: u8| AudioChannelState::DMASound {
+            fifo_len: sound.fifo_len(),
+            volume: [50, 100][volume as usize],
+            timer: sound.timer(),
+        };
+        AudioState {
+            master_enable: self.master_enable,
+            channels: vec![
+                AudioChannelState::Tone {
+                    playing: self.tone1.is_on(),
+                    frequency: self.tone1.frequency(),
+                    duty: self.tone1.duty(),
+                    volume: self.tone1.envelope.get_volume() as u8,
+                },
+                AudioChannelState::Tone {
+                    playing: self.tone2.is_on(),
+                    frequency: self.tone2.frequency(),
+                    duty: self.tone2.duty(),
+                    volume: self.tone2.envelope.get_volume() as u8,
+                },
+                AudioChannelState::Wave {
+                    playing: self.wave.is_on(),
+                    frequency: self.wave.frequency(),
+                    volume: self.wave.volume(),
+                    wave_ram: self.wave.wave_ram,
+                    playing_bank: self.wave.playing_bank(),
+                    two_banks: self.wave.uses_two_banks(),
+                },
+                AudioChannelState::Noise {
+                    playing: self.noise.is_on(),
+                    frequency: self.noise.frequency(),
+                    volume: self.noise.envelope.get_volume() as u8,
+                    lfsr_width: self.noise.lfsr_width(),
+                },
+                dma_sound(&self.sound_a, self.cnt.dma_sound_a_vol),
+                dma_sound(&self.sound_b, self.cnt.dma_sound_b_vol),
+            ],
+            muted: self.muted,
+            soloed: self.soloed,
+            recording: self.is_recording(),
+            scope: self.scope.as_ref().map(|scope| scope.iter().copied().collect()).unwrap_or_default(),
+        }
+    }
+}
@@ -1,13 +1,21 @@
+use std::collections::VecDeque;
+
 mod audio;
+mod debug;
 mod registers;
 mod channel;
+mod recorder;
+mod resampler;
 
 use super::{Scheduler, IORegister};
 use crate::gba;
 
-use audio::{Audio, AudioDevice};
+pub use audio::{AudioSink, NullSink, RingBufferSink, SampleBuffer};
+pub use debug::{AudioChannel, AudioChannelState, AudioState};
 use registers::*;
 use channel::*;
+use recorder::AudioRecorder;
+use resampler::Resampler;
 
 pub struct APU {
     // Channels
@@ -23,16 +31,32 @@ pub struct APU {
     master_enable: bool,
     
     // Sound Generation
-    audio: AudioDevice<Audio>,
+    audio: Box<dyn AudioSink>,
+    // Mixed samples at the rate SOUNDBIAS selects go through this unless the sink asks for that rate
+    resampler: Resampler,
+    // Counts down by the mixer's sample rate every clock, so samples come every sample_period / that rate clocks
+    sample_period: usize,
     sample_clock: usize,
+    // Faster than full speed, samples are still mixed at the full speed rate and averaged until the next one
+    // goes to the sink, so that the audio is low-passed instead of aliasing when it's decimated
+    decimate_clock: usize,
+    decimate_sum: [i32; 2],
+    decimate_len: i32,
     fifo_a_req: bool,
     fifo_b_req: bool,
+
+    // Debugging
+    muted: [bool; 6],
+    soloed: [bool; 6],
+    scope: Option<VecDeque<[i16; 6]>>,
+    recorder: Option<AudioRecorder>,
+    record_clock: usize,
 }
 
 impl APU {
-    const CLOCKS_PER_SAMPLE: usize = gba::CLOCK_FREQ / gba::AUDIO_SAMPLE_RATE;
+    const CLOCKS_PER_RECORDED_SAMPLE: usize = gba::CLOCK_FREQ / gba::AUDIO_SAMPLE_RATE;
 
-    pub fn new() -> APU {
+    pub fn new(audio: Box<dyn AudioSink>) -> APU {
         APU {
             // Channels
             tone1: Tone::new(),
@@ -47,14 +71,27 @@ impl APU {
             master_enable: false,
 
             // Sound Generation
-            audio: Audio::new(),
-            sample_clock: APU::CLOCKS_PER_SAMPLE,
+            audio,
+            resampler: Resampler::new(1.0),
+            sample_period: gba::CLOCK_FREQ,
+            sample_clock: gba::CLOCK_FREQ,
+            decimate_clock: gba::CLOCK_FREQ,
+            decimate_sum: [0; 2],
+            decimate_len: 0,
             fifo_a_req: false,
             fifo_b_req: false,
+
+            // Debugging
+            muted: [false; 6],
+            soloed: [false; 6],
+            scope: None,
+            recorder: None,
+            record_clock: 0,
         }
     }
 
     pub fn clock(&mut self) {
+        if self.recorder.is_some() { self.clock_recorder() }
         if !self.master_enable { return }
 
         self.tone1.clock();
@@ -65,6 +102,44 @@ impl APU {
         self.generate_sample();
     }
 
+    // The same as clocking it the given number of times, with the channels clocked in bulk between samples
+    pub fn clock_many(&mut self, mut cycles: usize) {
+        while cycles > 0 {
+            // Cycles before the next one that generates or records a sample
+            let mut quiet_cycles = cycles - 1;
+            let sample_rate = self.bias.sample_rate();
+            if self.master_enable {
+                let next_sample =
+                    if self.decimating() { self.sample_clock.min(self.decimate_clock) } else { self.sample_clock };
+                let sample_cycles = next_sample.div_ceil(sample_rate);
+                quiet_cycles = quiet_cycles.min(sample_cycles.saturating_sub(1));
+            }
+            if self.recorder.is_some() { quiet_cycles = quiet_cycles.min(self.record_clock - 1) }
+
+            if quiet_cycles > 0 {
+                if self.recorder.is_some() { self.record_clock -= quiet_cycles }
+                if self.master_enable {
+                    self.tone1.clock_many(quiet_cycles);
+                    self.tone2.clock_many(quiet_cycles);
+                    self.wave.clock_many(quiet_cycles);
+                    self.noise.clock_many(quiet_cycles);
+                    self.sample_clock -= quiet_cycles * sample_rate;
+                    if self.decimating() { self.decimate_clock -= quiet_cycles * sample_rate }
+                }
+            }
+            self.clock();
+            cycles -= quiet_cycles + 1;
+        }
+    }
+
+    // Running faster than full speed spaces samples further apart so that the sink still gets them at its rate
+    pub fn set_speed(&mut self, speed: f64) {
+        self.sample_period = ((gba::CLOCK_FREQ as f64 * speed) as usize).max(SOUNDBIAS::MAX_SAMPLE_RATE);
+        self.sample_clock = self.sample_clock.min(self.sample_period);
+    }
+
+    fn decimating(&self) -> bool { self.sample_period > gba::CLOCK_FREQ }
+
     pub fn on_timer_overflowed(&mut self, timer: usize) {
         self.fifo_a_req = self.sound_a.on_timer_overflowed(timer) || self.fifo_a_req;
         self.fifo_b_req = self.sound_b.on_timer_overflowed(timer) || self.fifo_b_req;
@@ -81,6 +156,9 @@ impl APU {
         }
     }
 
+    // Like fifo_a_req and fifo_b_req, but leaves the requests for the DMA to take
+    pub fn fifo_dma_requested(&self) -> bool { self.fifo_a_req || self.fifo_b_req }
+
     pub fn fifo_a_req(&mut self) -> bool {
         let fifo_a_req = self.fifo_a_req;
         self.fifo_a_req = false;
@@ -107,45 +185,81 @@ impl APU {
     }
 
     fn generate_sample(&mut self) {
-        self.sample_clock -= 1;
-        if self.sample_clock == 0 {
-            let channel1_sample = self.tone1.generate_sample();
-            let channel2_sample = self.tone2.generate_sample();
-            let channel3_sample = self.wave.generate_sample();
-            let channel4_sample = self.noise.generate_sample();
-            let (mut psg_l, mut psg_r) = (0, 0);
-            
-            psg_l += self.cnt.psg_enable_l.channel1 as i16 * channel1_sample;
-            psg_l += self.cnt.psg_enable_l.channel2 as i16 * channel2_sample;
-            psg_l += self.cnt.psg_enable_l.channel3 as i16 * channel3_sample;
-            psg_l += self.cnt.psg_enable_l.channel4 as i16 * channel4_sample;
-            psg_r += self.cnt.psg_enable_r.channel1 as i16 * channel1_sample;
-            psg_r += self.cnt.psg_enable_r.channel2 as i16 * channel2_sample;
-            psg_r += self.cnt.psg_enable_r.channel3 as i16 * channel3_sample;
-            psg_r += self.cnt.psg_enable_r.channel4 as i16 * channel4_sample;
-            
-            psg_l *= 1 + self.cnt.psg_master_volume_l as i16;
-            psg_r *= 1 + self.cnt.psg_master_volume_r as i16;
-            
-            let sound_a_sample = DMASound::VOLUME_FACTORS[self.cnt.dma_sound_a_vol as usize] * self.sound_a.generate_sample();
-            let sound_b_sample = DMASound::VOLUME_FACTORS[self.cnt.dma_sound_b_vol as usize] * self.sound_b.generate_sample();
-            let (mut dma_l, mut dma_r) = (0, 0);
-
-            dma_l += self.sound_a.enable_left as i16 * sound_a_sample;
-            dma_l += self.sound_b.enable_left as i16 * sound_b_sample;
-            dma_r += self.sound_a.enable_right as i16 * sound_a_sample;
-            dma_r += self.sound_b.enable_right as i16 * sound_b_sample;
-
-            let mut samples = [psg_l + dma_l, psg_r + dma_r];
-            for sample in samples.iter_mut() {
-                *sample = *sample + self.bias.bias_level as i16;
-                *sample = num::clamp(*sample, 0, 0x3FF);
-                *sample -= 0x200;
+        let sample_rate = self.bias.sample_rate();
+        if self.decimating() {
+            if self.decimate_clock <= sample_rate {
+                let (_, mixed) = self.mix();
+                self.decimate_sum[0] += mixed[0] as i32;
+                self.decimate_sum[1] += mixed[1] as i32;
+                self.decimate_len += 1;
+                self.decimate_clock += gba::CLOCK_FREQ;
             }
+            self.decimate_clock -= sample_rate;
+        }
+        if self.sample_clock <= sample_rate {
+            let (samples, mut mixed) = self.mix();
+            if self.decimate_len > 0 {
+                let len = self.decimate_len;
+                mixed = [(self.decimate_sum[0] / len) as i16, (self.decimate_sum[1] / len) as i16];
+                self.decimate_sum = [0; 2];
+                self.decimate_len = 0;
+            }
+            self.record_scope(samples);
+            self.output_sample(mixed, sample_rate);
+            self.sample_clock += self.sample_period;
+        }
+        self.sample_clock -= sample_rate;
+    }
+
+    fn output_sample(&mut self, mixed: [i16; 2], mixer_rate: usize) {
+        let sample_rate = self.audio.sample_rate();
+        if sample_rate == mixer_rate { return self.audio.push_sample(mixed[0], mixed[1]) }
+        self.resampler.set_ratio(sample_rate as f64 / mixer_rate as f64);
+        let audio = &mut self.audio;
+        self.resampler.push(mixed[0], mixed[1], |left_sample, right_sample| {
+            audio.push_sample(left_sample, right_sample)
+        });
+    }
+
+    // Each channel's sample before mixing, and the stereo output with any muted channels left out
+    fn mix(&self) -> ([i16; 6], [i16; 2]) {
+        let samples = [
+            self.tone1.generate_sample(), self.tone2.generate_sample(),
+            self.wave.generate_sample(), self.noise.generate_sample(),
+            self.sound_a.generate_sample(), self.sound_b.generate_sample(),
+        ];
+        let [channel1_sample, channel2_sample, channel3_sample, channel4_sample, sound_a_sample, sound_b_sample] =
+            self.mute(samples);
+        let (mut psg_l, mut psg_r) = (0, 0);
+        
+        psg_l += self.cnt.psg_enable_l.channel1 as i16 * channel1_sample;
+        psg_l += self.cnt.psg_enable_l.channel2 as i16 * channel2_sample;
+        psg_l += self.cnt.psg_enable_l.channel3 as i16 * channel3_sample;
+        psg_l += self.cnt.psg_enable_l.channel4 as i16 * channel4_sample;
+        psg_r += self.cnt.psg_enable_r.channel1 as i16 * channel1_sample;
+        psg_r += self.cnt.psg_enable_r.channel2 as i16 * channel2_sample;
+        psg_r += self.cnt.psg_enable_r.channel3 as i16 * channel3_sample;
+        psg_r += self.cnt.psg_enable_r.channel4 as i16 * channel4_sample;
+        
+        psg_l *= 1 + self.cnt.psg_master_volume_l as i16;
+        psg_r *= 1 + self.cnt.psg_master_volume_r as i16;
+        
+        let sound_a_sample = DMASound::VOLUME_FACTORS[self.cnt.dma_sound_a_vol as usize] * sound_a_sample;
+        let sound_b_sample = DMASound::VOLUME_FACTORS[self.cnt.dma_sound_b_vol as usize] * sound_b_sample;
+        let (mut dma_l, mut dma_r) = (0, 0);
 
-            self.audio.lock().queue(samples[0], samples[1]);
-            self.sample_clock = APU::CLOCKS_PER_SAMPLE;
+        dma_l += self.sound_a.enable_left as i16 * sound_a_sample;
+        dma_l += self.sound_b.enable_left as i16 * sound_b_sample;
+        dma_r += self.sound_a.enable_right as i16 * sound_a_sample;
+        dma_r += self.sound_b.enable_right as i16 * sound_b_sample;
+
+        let mut mixed = [psg_l + dma_l, psg_r + dma_r];
+        for sample in mixed.iter_mut() {
+            *sample = *sample + self.bias.bias_level as i16;
+            *sample = num::clamp(*sample, 0, 0x3FF) & self.bias.resolution_mask();
+            *sample -= 0x200;
         }
+        (samples, mixed)
     }
 }
 
@@ -202,6 +316,19 @@ impl APU {
         }
     }
 
+    // Like read_register, but with the lengths and frequencies that can't be read back
+    pub fn inspect_register(&self, addr: u32) -> u8 {
+        assert_eq!(addr >> 12, 0x04000);
+        match addr & 0xFFF {
+            0x060 ..= 0x067 => self.tone1.inspect(addr as u8 - 0x60),
+            0x068 | 0x069 => self.tone2.inspect(addr as u8 - 0x68 + 2),
+            0x06C ..= 0x06F => self.tone2.inspect(addr as u8 - 0x68),
+            0x070 ..= 0x077 => self.wave.inspect(addr as u8 - 0x70),
+            0x078 ..= 0x07F => self.noise.inspect(addr as u8 - 0x78),
+            _ => self.read_register(addr),
+        }
+    }
+
     pub fn write_register(&mut self, scheduler: &mut Scheduler, addr: u32, value: u8) {
         assert_eq!(addr >> 12, 0x04000);
         match addr & 0xFFF {
@@ -264,3 +391,9 @@ impl APU {
         }
     }
 }
+
+impl_save_state!(APU {
+    tone1, tone2, wave, noise, sound_a, sound_b,
+    cnt, bias, master_enable,
+    sample_clock, fifo_a_req, fifo_b_req,
+});
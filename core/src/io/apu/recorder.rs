@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::{APU, AudioChannel};
+use crate::gba;
+
+// 16-bit PCM, with the sizes in the header filled in once it's finished or dropped
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    channels: u16,
+    data_len: u32,
+    finished: bool,
+}
+
+impl WavWriter {
+    const HEADER_LEN: u32 = 44;
+    // The RIFF size that follows the first 8 bytes has to fit in 32 bits
+    const MAX_DATA_LEN: u32 = u32::MAX - (WavWriter::HEADER_LEN - 8);
+
+    pub fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<WavWriter> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let block_align = channels * 2;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(WavWriter::HEADER_LEN - 8).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter {
+            writer,
+            channels,
+            data_len: 0,
+            finished: false,
+        })
+    }
+
+    pub fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        assert_eq!(samples.len(), self.channels as usize);
+        let data_len = match self.data_len.checked_add(2 * self.channels as u32) {
+            Some(data_len) if data_len <= WavWriter::MAX_DATA_LEN => data_len,
+            _ => return Err(io::Error::other("WAV files are limited to 4 GiB")),
+        };
+        for sample in samples.iter() { self.writer.write_all(&sample.to_le_bytes())? }
+        self.data_len = data_len;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> { self.write_sizes() }
+
+    fn write_sizes(&mut self) -> io::Result<()> {
+        self.finished = true;
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(WavWriter::HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(WavWriter::HEADER_LEN as u64 - 4))?;
+        self.writer.write_all(&self.data_len.to_le_bytes())?;
+        self.writer.flush()
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        if self.finished { return }
+        if let Err(err) = self.write_sizes() { warn!("Unable to finish WAV file: {}", err) }
+    }
+}
+
+// Writes the mixed output in stereo, and optionally each channel before mixing in mono to files next to it
+pub struct AudioRecorder {
+    mixed: WavWriter,
+    channels: Vec<WavWriter>,
+    // Recording stops at the first error, which is returned when it's finished. What was recorded before it is kept
+    error: Option<io::Error>,
+}
+
+impl AudioRecorder {
+    // Samples are scaled up to use the full 16 bits
+    const MIXED_SCALE: i16 = 64;
+    const CHANNEL_SCALE: i16 = 256;
+
+    pub fn new(path: &Path, channels: bool) -> io::Result<AudioRecorder> {
+        let sample_rate = gba::AUDIO_SAMPLE_RATE as u32;
+        let channels = if channels {
+            AudioChannel::ALL.iter().map(|channel| WavWriter::create(&channel.recording_path(path), 1, sample_rate))
+                .collect::<io::Result<_>>()?
+        } else { Vec::new() };
+        Ok(AudioRecorder {
+            mixed: WavWriter::create(path, 2, sample_rate)?,
+            channels,
+            error: None,
+        })
+    }
+
+    pub fn record(&mut self, channels: [i16; 6], mixed: [i16; 2]) {
+        if self.error.is_some() { return }
+        let mut result = self.mixed.write(&[mixed[0] * AudioRecorder::MIXED_SCALE, mixed[1] * AudioRecorder::MIXED_SCALE]);
+        for (writer, &sample) in self.channels.iter_mut().zip(channels.iter()) {
+            result = result.and_then(|_| writer.write(&[sample.saturating_mul(AudioRecorder::CHANNEL_SCALE)]));
+        }
+        if let Err(err) = result {
+            warn!("Stopping audio recording: {}", err);
+            self.error = Some(err);
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        if let Some(err) = self.error { return Err(err) }
+        self.mixed.finish()?;
+        for writer in self.channels { writer.finish()? }
+        Ok(())
+    }
+}
+
+impl APU {
+    // Any recording that was already going is finished first
+    pub fn start_recording(&mut self, path: &Path, channels: bool) -> io::Result<()> {
+        if let Err(err) = self.stop_recording() { warn!("Unable to finish audio recording: {}", err) }
+        self.recorder = Some(AudioRecorder::new(path, channels)?);
+        self.record_clock = APU::CLOCKS_PER_RECORDED_SAMPLE;
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool { self.recorder.is_some() }
+
+    // Recordings are always at full speed and go on while sound is off, so they line up with emulated time
+    pub(super) fn clock_recorder(&mut self) {
+        self.record_clock -= 1;
+        if self.record_clock > 0 { return }
+        self.record_clock = APU::CLOCKS_PER_RECORDED_SAMPLE;
+        let (channels, mixed) = if self.master_enable { self.mix() } else { ([0; 6], [0; 2]) };
+        if let Some(recorder) = &mut self.recorder { recorder.record(channels, mixed) }
+    }
+}
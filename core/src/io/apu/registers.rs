@@ -89,12 +89,19 @@ pub struct SOUNDBIAS {
 }
 
 impl SOUNDBIAS {
+    pub const MAX_SAMPLE_RATE: usize = 0x40000;
+
     pub fn new() -> SOUNDBIAS {
         SOUNDBIAS {
             bias_level: 0x200,
             amplitude_res: 0,
         }
     }
+
+    // The output is 9 bits at 32768 Hz, trading a bit of resolution for each doubling of the rate
+    pub fn sample_rate(&self) -> usize { 0x8000 << self.amplitude_res }
+
+    pub fn resolution_mask(&self) -> i16 { !0 << self.amplitude_res }
 }
 
 impl IORegister for SOUNDBIAS {
@@ -117,3 +124,9 @@ impl IORegister for SOUNDBIAS {
         }
     }
 }
+
+impl_save_state!(SoundEnableFlags { channel1, channel2, channel3, channel4 });
+impl_save_state!(SOUNDCNT {
+    psg_master_volume_r, psg_master_volume_l, psg_enable_r, psg_enable_l, psg_volume, dma_sound_a_vol, dma_sound_b_vol,
+} where |cnt| cnt.dma_sound_a_vol < 2 && cnt.dma_sound_b_vol < 2);
+impl_save_state!(SOUNDBIAS { bias_level, amplitude_res } where |bias| bias.amplitude_res < 4);
@@ -0,0 +1,80 @@
+use std::f64::consts::PI;
+
+// Band-limited resampling in the style of blip_buf. The GBA outputs a series of steps, so every change in the input
+// is added to the output as a step with its sharp edge filtered out, and the output is the running sum of those
+pub struct Resampler {
+    kernel: Vec<[f32; Resampler::TAPS]>,
+    // Output samples per input sample
+    ratio: f64,
+    // Where the next input sample lands, as a fraction of an output sample
+    time: f64,
+    // Changes to the next few output samples, with the first one due next
+    deltas: [[f32; 2]; Resampler::TAPS],
+    start: usize,
+    last_input: [i16; 2],
+    output: [f32; 2],
+}
+
+impl Resampler {
+    const PHASES: usize = 32;
+    const TAPS: usize = 16;
+    // Fraction of the output's Nyquist frequency that's kept
+    const CUTOFF: f64 = 0.9;
+
+    pub fn new(ratio: f64) -> Resampler {
+        let kernel = (0..Resampler::PHASES).map(|phase| {
+            let half_width = (Resampler::TAPS / 2) as f64;
+            // Centered half the taps in, so that a step never changes output samples that were already taken
+            let center = half_width - 1.0 + phase as f64 / Resampler::PHASES as f64;
+            let mut taps = [0.0; Resampler::TAPS];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                let x = i as f64 - center;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * Resampler::CUTOFF * x).sin() / (PI * Resampler::CUTOFF * x) };
+                let blackman = 0.42 + 0.5 * (PI * x / half_width).cos() + 0.08 * (2.0 * PI * x / half_width).cos();
+                *tap = sinc * blackman;
+            }
+            // Every step has to end up at its full height
+            let sum: f64 = taps.iter().sum();
+            let mut normalized = [0.0; Resampler::TAPS];
+            for (normalized, tap) in normalized.iter_mut().zip(taps.iter()) { *normalized = (tap / sum) as f32 }
+            normalized
+        }).collect();
+        Resampler {
+            kernel,
+            ratio,
+            time: 0.0,
+            deltas: [[0.0; 2]; Resampler::TAPS],
+            start: 0,
+            last_input: [0; 2],
+            output: [0.0; 2],
+        }
+    }
+
+    pub fn set_ratio(&mut self, ratio: f64) { self.ratio = ratio }
+
+    // Output samples are passed to the closure as soon as no later input can change them
+    pub fn push(&mut self, left_sample: i16, right_sample: i16, mut output: impl FnMut(i16, i16)) {
+        let input = [left_sample, right_sample];
+        if input != self.last_input {
+            let delta = [left_sample as f32 - self.last_input[0] as f32, right_sample as f32 - self.last_input[1] as f32];
+            let phase = (self.time * Resampler::PHASES as f64) as usize;
+            for (i, tap) in self.kernel[phase].iter().enumerate() {
+                let deltas = &mut self.deltas[(self.start + i) % Resampler::TAPS];
+                deltas[0] += delta[0] * tap;
+                deltas[1] += delta[1] * tap;
+            }
+            self.last_input = input;
+        }
+
+        self.time += self.ratio;
+        while self.time >= 1.0 {
+            let deltas = &mut self.deltas[self.start];
+            self.output[0] += deltas[0];
+            self.output[1] += deltas[1];
+            *deltas = [0.0; 2];
+            self.start = (self.start + 1) % Resampler::TAPS;
+            output(self.output[0].round() as i16, self.output[1].round() as i16);
+            self.time -= 1.0;
+        }
+    }
+}
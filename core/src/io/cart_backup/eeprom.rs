@@ -2,11 +2,13 @@ use std::path::PathBuf;
 use std::cell::Cell;
 
 use super::CartBackup;
+use crate::savestate::{SaveState, StateReader, StateError};
 
 pub struct EEPROM {
     mem: Vec<u8>,
     mem_size: usize,
-    save_file: PathBuf,
+    save_data: Option<Vec<u8>>,
+    save_file: Option<PathBuf>,
     is_dirty: bool,
     
     
@@ -15,10 +17,11 @@ pub struct EEPROM {
 }
 
 impl EEPROM {
-    pub fn new(save_file: PathBuf) -> EEPROM {
+    pub fn new(save_data: Option<Vec<u8>>, save_file: Option<PathBuf>) -> EEPROM {
         EEPROM {
             mem: Vec::new(),
             mem_size: 0,
+            save_data,
             save_file,
             is_dirty: false,
             
@@ -34,7 +37,7 @@ impl CartBackup for EEPROM {
             assert!(dma_count == 9 || dma_count == 17);
             self.addr_size = dma_count as usize - 3; // Number of bits
             self.mem_size = 1 << (self.addr_size + 3); // Number of bytes
-            self.mem = CartBackup::get_initial_mem(&self.save_file, 0, self.mem_size);
+            self.mem = CartBackup::get_initial_mem(self.save_data.take(), 0, self.mem_size);
         }
     }
 
@@ -104,8 +107,17 @@ impl CartBackup for EEPROM {
 
     fn read(&self, _addr: u32) -> u8 { unreachable!() }
     fn write(&mut self, _addr: u32, _value: u8) { unreachable!() }
+    // The size isn't known until the first DMA, so the memory may still be empty
+    fn peek(&self, addr: u32) -> u8 { self.mem.get(addr as usize).copied().unwrap_or(0xFF) }
+
+    fn poke(&mut self, addr: u32, value: u8) {
+        if let Some(byte) = self.mem.get_mut(addr as usize) {
+            self.is_dirty = true;
+            *byte = value;
+        }
+    }
     fn is_dirty(&mut self) -> bool { let is_dirty = self.is_dirty; self.is_dirty = false; is_dirty }
-    fn get_save_file(&self) -> &PathBuf { &self.save_file }
+    fn get_save_file(&self) -> Option<&PathBuf> { self.save_file.as_ref() }
     fn get_mem(&self) -> &Vec<u8> { &self.mem }
     fn is_eeprom(&self) -> bool { true }
 }
@@ -122,3 +134,54 @@ enum IOMode {
     Address(usize, usize),
     Data(usize, usize),
 }
+
+// Before the first DMA there's no memory yet, otherwise it's 512 bytes or 8KB
+impl_save_state!(EEPROM { mem, mem_size, addr_size, mode } where |eeprom| eeprom.mem.len() == eeprom.mem_size &&
+    if eeprom.mem_size == 0 { eeprom.addr_size == 0 } else { [6, 14].contains(&eeprom.addr_size) && eeprom.mem_size == 1 << (eeprom.addr_size + 3) });
+
+impl SaveState for Mode {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        match *self {
+            Mode::Request { done } => { 0u8.save_state(state); done.save_state(state) },
+            Mode::Read(io_mode) => { 1u8.save_state(state); io_mode.save_state(state) },
+            Mode::Write(io_mode) => { 2u8.save_state(state); io_mode.save_state(state) },
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let tag = state.read::<u8>()?;
+        *self = match tag {
+            0 => Mode::Request { done: state.read()? },
+            1 | 2 => {
+                let mut io_mode = IOMode::Address(0, 0);
+                io_mode.load_state(state)?;
+                if tag == 1 { Mode::Read(io_mode) } else { Mode::Write(io_mode) }
+            },
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl SaveState for IOMode {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        let (tag, counter, addr) = match *self {
+            IOMode::Address(counter, addr) => (0u8, counter, addr),
+            IOMode::Data(counter, addr) => (1u8, counter, addr),
+        };
+        tag.save_state(state);
+        counter.save_state(state);
+        addr.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let tag = state.read::<u8>()?;
+        let (counter, addr) = (state.read()?, state.read()?);
+        *self = match tag {
+            0 => IOMode::Address(counter, addr),
+            1 => IOMode::Data(counter, addr),
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
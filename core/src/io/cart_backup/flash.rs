@@ -5,7 +5,7 @@ use super::CartBackup;
 pub struct Flash {
     mem: Vec<u8>,
     mem_size: usize,
-    save_file: PathBuf,
+    save_file: Option<PathBuf>,
     is_dirty: bool,
 
     command: Command,
@@ -22,9 +22,9 @@ impl Flash {
     const COMMAND_ADDR: u32 = 0x5555;
     const COMMAND1_ADDR: u32 = 0x2AAA;
 
-    pub fn new(save_file: PathBuf, size: usize) -> Flash {
+    pub fn new(save_data: Option<Vec<u8>>, save_file: Option<PathBuf>, size: usize) -> Flash {
         Flash {
-            mem: CartBackup::get_initial_mem(&save_file, 0xFF, size),
+            mem: CartBackup::get_initial_mem(save_data, 0xFF, size),
             mem_size: size,
             save_file,
             is_dirty: false,
@@ -46,6 +46,13 @@ impl CartBackup for Flash {
         } else { self.mem[self.bank * 0x10000 + addr as usize] }
     }
 
+    fn peek(&self, addr: u32) -> u8 { self.mem[self.bank * 0x10000 + addr as usize] }
+
+    fn poke(&mut self, addr: u32, value: u8) {
+        self.is_dirty = true;
+        self.mem[self.bank * 0x10000 + addr as usize] = value;
+    }
+
     fn write(&mut self, addr: u32, value: u8) {
         if self.mode == Mode::Write {
             self.is_dirty = true;
@@ -110,22 +117,27 @@ impl CartBackup for Flash {
     fn read_eeprom(&self, _addr: u32) -> u16 { unreachable!() }
     fn write_eeprom(&mut self, _addr: u32, _value: u16) { unreachable!() }
     fn is_dirty(&mut self) -> bool { let is_dirty = self.is_dirty; self.is_dirty = false; is_dirty }
-    fn get_save_file(&self) -> &PathBuf { &self.save_file }
+    fn get_save_file(&self) -> Option<&PathBuf> { self.save_file.as_ref() }
     fn get_mem(&self) -> &Vec<u8> { &self.mem }
     fn is_eeprom(&self) -> bool { false }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Command {
     Command0,
     Command1,
     Command2,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Mode {
     Ready,
     Erase,
     Write,
     SetBank,
 }
+
+impl_save_state!(Flash { mem, command, mode, bank, in_chip_ident }
+    where |flash| flash.mem.len() == flash.mem_size && flash.bank * 0x10000 < flash.mem_size);
+impl_save_state_enum!(Command { Command0, Command1, Command2 });
+impl_save_state_enum!(Mode { Ready, Erase, Write, SetBank });
@@ -8,16 +8,20 @@ mod flash;
 use eeprom::EEPROM;
 use sram::SRAM;
 use flash::Flash;
+use crate::savestate::{SaveState, StateReader, StateError};
 
-pub trait CartBackup {
+pub trait CartBackup: SaveState {
     fn read(&self, addr: u32) -> u8;
     fn write(&mut self, addr: u32, value: u8);
     fn read_eeprom(&self, addr: u32) -> u16;
     fn write_eeprom(&mut self, addr: u32, value: u16);
     fn init_eeprom(&mut self, dma_count: u32);
+    // Access the memory the way a debugger sees it, bypassing commands and chip IDs
+    fn peek(&self, addr: u32) -> u8;
+    fn poke(&mut self, addr: u32, value: u8);
 
     fn is_dirty(&mut self) -> bool;
-    fn get_save_file(&self) -> &PathBuf;
+    fn get_save_file(&self) -> Option<&PathBuf>;
     fn get_mem(&self) -> &Vec<u8>;
     fn is_eeprom(&self) -> bool;
 }
@@ -45,18 +49,18 @@ impl dyn CartBackup {
         cart_backup_type
     }
 
-    pub fn get(rom: &Vec<u8>, save_file: PathBuf) -> Box<dyn CartBackup> {
+    pub fn get(rom: &Vec<u8>, save_data: Option<Vec<u8>>, save_file: Option<PathBuf>) -> Box<dyn CartBackup> {
         if let Some(cart_backup_type) = CartBackup::get_type(rom) {
             match cart_backup_type {
-                CartBackupType::EEPROM => Box::new(EEPROM::new(save_file)),
-                CartBackupType::SRAM => Box::new(SRAM::new(save_file)),
-                CartBackupType::Flash => Box::new(Flash::new(save_file, 0x10000)),
-                CartBackupType::Flash512 => Box::new(Flash::new(save_file, 0x10000)),
-                CartBackupType::Flash1M => Box::new(Flash::new(save_file, 0x20000)),
+                CartBackupType::EEPROM => Box::new(EEPROM::new(save_data, save_file)),
+                CartBackupType::SRAM => Box::new(SRAM::new(save_data, save_file)),
+                CartBackupType::Flash => Box::new(Flash::new(save_data, save_file, 0x10000)),
+                CartBackupType::Flash512 => Box::new(Flash::new(save_data, save_file, 0x10000)),
+                CartBackupType::Flash1M => Box::new(Flash::new(save_data, save_file, 0x20000)),
             }
         } else {
             warn!("Unable to detect Cartr Backup Type - Defaulting to SRAM");
-            Box::new(SRAM::new(save_file))
+            Box::new(SRAM::new(save_data, save_file))
         }
     }
 
@@ -64,20 +68,28 @@ impl dyn CartBackup {
         self.is_eeprom() && (rom_size <= 0x1000000 || addr >= 0x0DFFFF00)
     }
 
-    fn get_initial_mem(save_file: &PathBuf, default_val: u8, size: usize) -> Vec<u8> {
-        if let Ok(mem) = fs::read(&save_file) {
-            if mem.len() == size { mem } else { vec![default_val; size] }
-        } else { vec![default_val; size] }
+    fn get_initial_mem(save_data: Option<Vec<u8>>, default_val: u8, size: usize) -> Vec<u8> {
+        match save_data {
+            Some(mem) if mem.len() == size => mem,
+            _ => vec![default_val; size],
+        }
     }
 
     pub fn save_to_file(&mut self) {
         if self.is_dirty() {
-            fs::write(self.get_save_file(), self.get_mem())
-            .unwrap_or_else(|err| warn!("Unable to Save to Fil: {}!", err))
+            if let Some(save_file) = self.get_save_file() {
+                fs::write(save_file, self.get_mem())
+                .unwrap_or_else(|err| warn!("Unable to Save to Fil: {}!", err))
+            }
         }
     }
 }
 
+impl SaveState for Box<dyn CartBackup> {
+    fn save_state(&self, state: &mut Vec<u8>) { (**self).save_state(state) }
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> { (**self).load_state(state) }
+}
+
 enum CartBackupType {
     EEPROM = 0,
     SRAM = 1,
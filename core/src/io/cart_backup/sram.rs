@@ -4,16 +4,16 @@ use super::CartBackup;
 
 pub struct SRAM {
     mem: Vec<u8>,
-    save_file: PathBuf,
+    save_file: Option<PathBuf>,
     is_dirty: bool,
 }
 
 impl SRAM {
     const SIZE: usize = 0x8000;
 
-    pub fn new(save_file: PathBuf) -> SRAM {
+    pub fn new(save_data: Option<Vec<u8>>, save_file: Option<PathBuf>) -> SRAM {
         SRAM {
-            mem: CartBackup::get_initial_mem(&save_file, 0, SRAM::SIZE),
+            mem: CartBackup::get_initial_mem(save_data, 0, SRAM::SIZE),
             save_file,
             is_dirty: false,
         }
@@ -32,10 +32,14 @@ impl CartBackup for SRAM {
     }
 
     fn init_eeprom(&mut self, _dma_count: u32) {}
+    fn peek(&self, addr: u32) -> u8 { self.read(addr) }
+    fn poke(&mut self, addr: u32, value: u8) { self.write(addr, value) }
     fn read_eeprom(&self, _addr: u32) -> u16 { unreachable!() }
     fn write_eeprom(&mut self, _addr: u32, _value: u16) { unreachable!() }
     fn is_dirty(&mut self) -> bool { let is_dirty = self.is_dirty; self.is_dirty = false; is_dirty }
-    fn get_save_file(&self) -> &PathBuf { &self.save_file }
+    fn get_save_file(&self) -> Option<&PathBuf> { self.save_file.as_ref() }
     fn get_mem(&self) -> &Vec<u8> { &self.mem }
     fn is_eeprom(&self) -> bool { false }
-}
\ No newline at end of file
+}
+
+impl_save_state!(SRAM { mem } where |sram| sram.mem.len() == SRAM::SIZE);
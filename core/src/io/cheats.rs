@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheatFormat {
+    // GameShark and Action Replay v1/v2, encrypted
+    GameSharkV1,
+    // GameShark and Action Replay v3, encrypted
+    GameSharkV3,
+    CodeBreaker,
+}
+
+impl CheatFormat {
+    const GAMESHARK_V1_SEEDS: [u32; 4] = [0x09F4FBBD, 0x9681884A, 0x352027E9, 0xF3DEE5A7];
+    const GAMESHARK_V3_SEEDS: [u32; 4] = [0x7AA9648F, 0x7FAE6994, 0xC0EFAAD5, 0x42712C57];
+
+    pub fn from_name(name: &str) -> Option<CheatFormat> {
+        match name.to_lowercase().as_str() {
+            "gameshark" | "gamesharkv1" | "actionreplay" | "actionreplayv1" => Some(CheatFormat::GameSharkV1),
+            "gamesharkv3" | "actionreplayv3" => Some(CheatFormat::GameSharkV3),
+            "codebreaker" => Some(CheatFormat::CodeBreaker),
+            _ => None,
+        }
+    }
+
+    fn code_digits(&self) -> usize {
+        match self {
+            CheatFormat::GameSharkV1 | CheatFormat::GameSharkV3 => 16,
+            CheatFormat::CodeBreaker => 12,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheatCondition {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessUnsigned,
+    GreaterUnsigned,
+    // Any of the bits in the value are set
+    And,
+}
+
+impl CheatCondition {
+    pub fn test(&self, mem_value: u32, value: u32, size: u32) -> bool {
+        let shift = 32 - size * 8;
+        let (signed_mem, signed_value) = ((mem_value << shift) as i32 >> shift, (value << shift) as i32 >> shift);
+        match self {
+            CheatCondition::Equal => mem_value == value,
+            CheatCondition::NotEqual => mem_value != value,
+            CheatCondition::Less => signed_mem < signed_value,
+            CheatCondition::Greater => signed_mem > signed_value,
+            CheatCondition::LessUnsigned => mem_value < value,
+            CheatCondition::GreaterUnsigned => mem_value > value,
+            CheatCondition::And => mem_value & value != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheatOp {
+    Write { addr: u32, size: u32, value: u32 },
+    Or { addr: u32, size: u32, value: u32 },
+    And { addr: u32, size: u32, value: u32 },
+    Add { addr: u32, size: u32, value: u32 },
+    // Replaces a halfword of ROM whenever it's read
+    Patch { addr: u32, value: u16 },
+    // Skips the next skip ops unless the condition holds
+    If { addr: u32, size: u32, condition: CheatCondition, value: u32, skip: usize },
+    // Master (enable) codes tell the device where to hook into the game. Cheats are applied by the
+    // emulator every frame instead, so these don't do anything.
+    Master,
+}
+
+#[derive(Debug)]
+pub enum CheatError {
+    InvalidCode(String),
+    UnsupportedCode(String),
+    // A multi-line code or block that's missing its end
+    IncompleteCode(String),
+    UnknownFormat(String),
+    CodeOutsideCheat(String),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::InvalidCode(code) => write!(f, "Invalid code {}", code),
+            CheatError::UnsupportedCode(code) => write!(f, "Unsupported code {}", code),
+            CheatError::IncompleteCode(code) => write!(f, "Code {} is incomplete", code),
+            CheatError::UnknownFormat(format) => write!(f, "Unknown cheat format {}", format),
+            CheatError::CodeOutsideCheat(code) => write!(f, "Code {} comes before any [Cheat Name]", code),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+#[derive(Clone, Debug)]
+pub struct Cheat {
+    pub name: String,
+    pub format: CheatFormat,
+    pub enabled: bool,
+    codes: Vec<String>,
+    ops: Vec<CheatOp>,
+}
+
+impl Cheat {
+    pub fn new(name: &str, format: CheatFormat, codes: &[&str]) -> Result<Cheat, CheatError> {
+        let codes = codes.iter().map(|code| code.split_whitespace().collect::<Vec<_>>().join(" ")).collect::<Vec<_>>();
+        let mut words = Vec::new();
+        for code in codes.iter() {
+            let digits = code.replace(' ', "");
+            if digits.len() != format.code_digits() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(CheatError::InvalidCode(code.clone()))
+            }
+            let op1 = u32::from_str_radix(&digits[..8], 16).unwrap();
+            let op2 = u32::from_str_radix(&digits[8..], 16).unwrap();
+            words.push(match format {
+                CheatFormat::GameSharkV1 => Cheat::decrypt(op1, op2, &CheatFormat::GAMESHARK_V1_SEEDS),
+                CheatFormat::GameSharkV3 => Cheat::decrypt(op1, op2, &CheatFormat::GAMESHARK_V3_SEEDS),
+                CheatFormat::CodeBreaker => (op1, op2),
+            });
+        }
+        let ops = match format {
+            CheatFormat::GameSharkV1 => Cheat::parse_gameshark_v1(&codes, &words)?,
+            CheatFormat::GameSharkV3 => Cheat::parse_gameshark_v3(&codes, &words)?,
+            CheatFormat::CodeBreaker => Cheat::parse_codebreaker(&codes, &words)?,
+        };
+        Ok(Cheat {
+            name: name.to_string(),
+            format,
+            enabled: true,
+            codes,
+            ops,
+        })
+    }
+
+    // Cheat lists look like this, where the format is optional and otherwise guessed from the code length:
+    // # Comment
+    // [Infinite Health] GameSharkV3
+    // XXXXXXXX YYYYYYYY
+    pub fn parse_list(text: &str) -> Result<Vec<Cheat>, CheatError> {
+        let mut headers: Vec<(&str, Option<CheatFormat>, Vec<&str>)> = Vec::new();
+        for line in text.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') { continue }
+            if line.starts_with('[') {
+                let end = line.find(']').ok_or_else(|| CheatError::InvalidCode(line.to_string()))?;
+                let format_name = line[end + 1..].trim();
+                let format = if format_name.is_empty() { None } else {
+                    Some(CheatFormat::from_name(format_name).ok_or_else(|| CheatError::UnknownFormat(format_name.to_string()))?)
+                };
+                headers.push((line[1..end].trim(), format, Vec::new()));
+            } else {
+                match headers.last_mut() {
+                    Some((_, _, codes)) => codes.push(line),
+                    None => return Err(CheatError::CodeOutsideCheat(line.to_string())),
+                }
+            }
+        }
+        headers.into_iter().map(|(name, format, codes)| {
+            let format = format.unwrap_or_else(|| {
+                let digits = codes.first().map(|code| code.split_whitespace().map(str::len).sum()).unwrap_or(0);
+                if digits == CheatFormat::CodeBreaker.code_digits() { CheatFormat::CodeBreaker } else { CheatFormat::GameSharkV1 }
+            });
+            Cheat::new(name, format, &codes)
+        }).collect()
+    }
+
+    pub fn codes(&self) -> &[String] { &self.codes }
+
+    pub fn ops(&self) -> &[CheatOp] { &self.ops }
+
+    pub fn is_master(&self) -> bool { self.ops.contains(&CheatOp::Master) }
+
+    // TEA with the seeds used by the device
+    fn decrypt(mut op1: u32, mut op2: u32, seeds: &[u32; 4]) -> (u32, u32) {
+        let mut sum = 0xC6EF3720u32;
+        for _ in 0..32 {
+            op2 = op2.wrapping_sub((op1 << 4).wrapping_add(seeds[2]) ^ op1.wrapping_add(sum) ^
+                (op1 >> 5).wrapping_add(seeds[3]));
+            op1 = op1.wrapping_sub((op2 << 4).wrapping_add(seeds[0]) ^ op2.wrapping_add(sum) ^
+                (op2 >> 5).wrapping_add(seeds[1]));
+            sum = sum.wrapping_sub(0x9E3779B9);
+        }
+        (op1, op2)
+    }
+
+    fn parse_gameshark_v1(codes: &[String], words: &[(u32, u32)]) -> Result<Vec<CheatOp>, CheatError> {
+        let mut ops = Vec::new();
+        for (code, &(op1, op2)) in codes.iter().zip(words.iter()) {
+            // Reseeding needs the device's key tables
+            if op1 == 0xDEADFACE { return Err(CheatError::UnsupportedCode(code.clone())) }
+            let addr = op1 & 0x0FFF_FFFF;
+            ops.push(match op1 >> 28 {
+                0x0 => CheatOp::Write { addr, size: 1, value: op2 & 0xFF },
+                0x1 => CheatOp::Write { addr, size: 2, value: op2 & 0xFFFF },
+                0x2 => CheatOp::Write { addr, size: 4, value: op2 },
+                0x6 => CheatOp::Patch { addr: 0x0800_0000 | (op1 & 0x00FF_FFFF) << 1, value: op2 as u16 },
+                0xD => CheatOp::If { addr, size: 2, condition: CheatCondition::Equal, value: op2 & 0xFFFF, skip: 1 },
+                0xE => CheatOp::If {
+                    addr: op2 & 0x0FFF_FFFF, size: 2, condition: CheatCondition::Equal,
+                    value: op1 & 0xFFFF, skip: (op1 >> 16 & 0xFF) as usize,
+                },
+                0xF => CheatOp::Master,
+                _ => return Err(CheatError::UnsupportedCode(code.clone())),
+            });
+        }
+        Ok(ops)
+    }
+
+    fn parse_gameshark_v3(codes: &[String], words: &[(u32, u32)]) -> Result<Vec<CheatOp>, CheatError> {
+        let mut ops = Vec::new();
+        // Conditional blocks that are waiting for their end
+        let mut blocks = Vec::new();
+        let mut words_iter = codes.iter().zip(words.iter());
+        while let Some((code, &(op1, op2))) = words_iter.next() {
+            if op1 == 0xDEADFACE { return Err(CheatError::UnsupportedCode(code.clone())) }
+            // Addresses are packed into the low 24 bits
+            let addr = (op1 & 0x00F0_0000) << 4 | op1 & 0x0003_FFFF;
+            let size = 1 << (op1 >> 25 & 0x3);
+            if op1 == 0 {
+                match op2 & 0xFE00_0000 {
+                    0x1800_0000 => match words_iter.next() {
+                        Some((_, &(value, _))) =>
+                            ops.push(CheatOp::Patch { addr: 0x0800_0000 | (op2 & 0x00FF_FFFF) << 1, value: value as u16 }),
+                        None => return Err(CheatError::IncompleteCode(code.clone())),
+                    },
+                    0x4000_0000 => match blocks.pop() {
+                        Some((start, _)) => {
+                            let end = ops.len();
+                            if let CheatOp::If { skip, .. } = &mut ops[start] { *skip = end - start - 1 }
+                        },
+                        None => return Err(CheatError::InvalidCode(code.clone())),
+                    },
+                    _ => return Err(CheatError::UnsupportedCode(code.clone())),
+                }
+                continue
+            }
+            let condition = match op1 & 0x3800_0000 {
+                0x0000_0000 => None,
+                0x0800_0000 => Some(CheatCondition::Equal),
+                0x1000_0000 => Some(CheatCondition::NotEqual),
+                0x1800_0000 => Some(CheatCondition::Less),
+                0x2000_0000 => Some(CheatCondition::Greater),
+                0x2800_0000 => Some(CheatCondition::LessUnsigned),
+                0x3000_0000 => Some(CheatCondition::GreaterUnsigned),
+                _ => Some(CheatCondition::And),
+            };
+            // Far addresses only exist for conditionals
+            if size == 8 { return Err(CheatError::UnsupportedCode(code.clone())) }
+            let value = if size == 4 { op2 } else { op2 & ((1 << (size * 8)) - 1) };
+            ops.push(match (condition, op1 >> 30) {
+                (Some(condition), action) => {
+                    let skip = match action {
+                        0 => 1,
+                        1 => 2,
+                        2 => { blocks.push((ops.len(), code)); 0 },
+                        _ => return Err(CheatError::UnsupportedCode(code.clone())),
+                    };
+                    CheatOp::If { addr, size, condition, value, skip }
+                },
+                (None, 0) => CheatOp::Write { addr, size, value },
+                (None, 2) => CheatOp::Add { addr, size, value },
+                (None, 3) => CheatOp::Master,
+                // Indirect writes through a pointer
+                _ => return Err(CheatError::UnsupportedCode(code.clone())),
+            });
+        }
+        match blocks.first() {
+            Some((_, code)) => Err(CheatError::IncompleteCode(code.to_string())),
+            None => Ok(ops),
+        }
+    }
+
+    fn parse_codebreaker(codes: &[String], words: &[(u32, u32)]) -> Result<Vec<CheatOp>, CheatError> {
+        let mut ops = Vec::new();
+        // Set by a 9 code, after which every code is encrypted
+        let mut key = None;
+        for (code, &(op1, value)) in codes.iter().zip(words.iter()) {
+            if op1 >> 28 == 0x9 {
+                key = Some(CodeBreakerKey::new(op1, value as u16));
+                continue
+            }
+            let (op1, value) = match &key {
+                Some(key) => key.decrypt(op1, value as u16),
+                None => (op1, value),
+            };
+            let addr = op1 & 0x0FFF_FFFF;
+            let condition = |condition| CheatOp::If { addr, size: 2, condition, value, skip: 1 };
+            ops.push(match op1 >> 28 {
+                0x0 | 0x1 => CheatOp::Master,
+                0x2 => CheatOp::Or { addr, size: 2, value },
+                0x3 => CheatOp::Write { addr, size: 1, value: value & 0xFF },
+                0x6 => CheatOp::And { addr, size: 2, value },
+                0x7 => condition(CheatCondition::Equal),
+                0x8 => CheatOp::Write { addr, size: 2, value },
+                0xA => condition(CheatCondition::NotEqual),
+                0xB => condition(CheatCondition::GreaterUnsigned),
+                0xC => condition(CheatCondition::LessUnsigned),
+                0xE => CheatOp::Add { addr, size: 2, value },
+                0xF => condition(CheatCondition::And),
+                // Slides, multi-line writes and button conditions
+                _ => return Err(CheatError::UnsupportedCode(code.clone())),
+            });
+        }
+        Ok(ops)
+    }
+}
+
+// CodeBreaker codes are encrypted by shuffling their 48 bits and XORing them with seeds, all of which come
+// from a game's 9 code
+struct CodeBreakerKey {
+    bit_order: [u8; 48],
+    seeds: [u32; 4],
+}
+
+impl CodeBreakerKey {
+    fn new(op1: u32, op2: u16) -> CodeBreakerKey {
+        let mut bit_order = [0; 48];
+        for (i, bit) in bit_order.iter_mut().enumerate() { *bit = i as u8 }
+        let mut state = (op2 & 0xFF) as u32 ^ 0x1111;
+        for _ in 0..0x50 {
+            let x = (CodeBreakerKey::rand(&mut state) % 48) as usize;
+            let y = (CodeBreakerKey::rand(&mut state) % 48) as usize;
+            bit_order.swap(x, y);
+        }
+
+        let mut seeds = [0; 4];
+        state = 0x4EFAD1C3;
+        for _ in 0..(op1 >> 24 & 0xF) { state = CodeBreakerKey::rand(&mut state) }
+        seeds[2] = CodeBreakerKey::rand(&mut state);
+        seeds[3] = CodeBreakerKey::rand(&mut state);
+        state = (op2 >> 8) as u32 ^ 0xF254;
+        for _ in 0..(op2 >> 8) { state = CodeBreakerKey::rand(&mut state) }
+        seeds[0] = CodeBreakerKey::rand(&mut state);
+        seeds[1] = CodeBreakerKey::rand(&mut state);
+        CodeBreakerKey { bit_order, seeds }
+    }
+
+    // Takes bits from three steps of an LCG
+    fn rand(state: &mut u32) -> u32 {
+        let step = |state: u32| state.wrapping_mul(0x41C64E6D).wrapping_add(0x3039);
+        let roll1 = step(*state);
+        let roll2 = step(roll1);
+        let roll3 = step(roll2);
+        *state = roll3;
+        roll1 << 14 & 0xC000_0000 | roll2 >> 1 & 0x3FFF_8000 | roll3 >> 16 & 0x7FFF
+    }
+
+    fn decrypt(&self, op1: u32, op2: u16) -> (u32, u32) {
+        let mut bytes = [0; 6];
+        bytes[..4].copy_from_slice(&op1.to_be_bytes());
+        bytes[4..].copy_from_slice(&op2.to_be_bytes());
+        for i in (0..48).rev() {
+            let j = self.bit_order[i] as usize;
+            let (bit_i, bit_j) = (bytes[i / 8] >> (i % 8) & 0x1, bytes[j / 8] >> (j % 8) & 0x1);
+            bytes[i / 8] = bytes[i / 8] & !(1 << (i % 8)) | bit_j << (i % 8);
+            bytes[j / 8] = bytes[j / 8] & !(1 << (j % 8)) | bit_i << (j % 8);
+        }
+
+        let op1 = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) ^ self.seeds[0];
+        let op2 = u16::from_be_bytes([bytes[4], bytes[5]]) ^ self.seeds[1] as u16;
+        bytes[..4].copy_from_slice(&op1.to_be_bytes());
+        bytes[4..].copy_from_slice(&op2.to_be_bytes());
+        // Each byte is also chained to the one after it
+        for i in 0..5 { bytes[i] ^= bytes[i + 1] }
+        let op1 = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) ^ self.seeds[2];
+        let op2 = u16::from_be_bytes([bytes[4], bytes[5]]) ^ self.seeds[3] as u16;
+        (op1, op2 as u32)
+    }
+}
+
+pub struct Cheats {
+    list: Vec<Cheat>,
+    // Bytes of RAM set by enabled cheats without conditions, which stay set even when the game writes
+    frozen: HashMap<u32, u8>,
+    // Bytes of ROM replaced whenever they're read
+    patches: HashMap<u32, u8>,
+}
+
+impl Cheats {
+    pub fn new() -> Cheats {
+        Cheats {
+            list: Vec::new(),
+            frozen: HashMap::new(),
+            patches: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, cheats: Vec<Cheat>) {
+        self.list = cheats;
+        self.update();
+    }
+
+    pub fn get(&self) -> &[Cheat] { &self.list }
+
+    // False if there's no cheat at index, as when the list was reloaded since it was shown
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.list.get_mut(index) {
+            Some(cheat) => cheat.enabled = enabled,
+            None => return false,
+        }
+        self.update();
+        true
+    }
+
+    // Ops of each enabled cheat in order
+    pub fn enabled_ops(&self) -> Vec<Vec<CheatOp>> {
+        self.list.iter().filter(|cheat| cheat.enabled).map(|cheat| cheat.ops.clone()).collect()
+    }
+
+    pub fn any_enabled(&self) -> bool { self.list.iter().any(|cheat| cheat.enabled) }
+
+    pub fn get_frozen(&self, addr: u32) -> Option<u8> { self.frozen.get(&addr).copied() }
+
+    pub fn has_frozen(&self) -> bool { !self.frozen.is_empty() }
+
+    pub fn patch<T>(&self, addr: u32, value: T) -> T where T: super::MemoryValue {
+        if self.patches.is_empty() { return value }
+        let mut value = num::cast::<T, u32>(value).unwrap();
+        for i in 0..std::mem::size_of::<T>() as u32 {
+            if let Some(byte) = self.patches.get(&(addr + i)) {
+                value = value & !(0xFF << (i * 8)) | (*byte as u32) << (i * 8);
+            }
+        }
+        num::cast(value).unwrap()
+    }
+
+    fn update(&mut self) {
+        self.frozen.clear();
+        self.patches.clear();
+        for cheat in self.list.iter().filter(|cheat| cheat.enabled) {
+            // Ops up to this one are only run when a condition holds
+            let mut guarded_until = 0;
+            for (i, op) in cheat.ops.iter().enumerate() {
+                match *op {
+                    CheatOp::Write { addr, size, value } if i >= guarded_until && Cheats::is_ram(addr) => {
+                        let addr = addr & !(size - 1);
+                        for byte in 0..size { self.frozen.insert(Cheats::canonical(addr + byte), (value >> (byte * 8)) as u8); }
+                    },
+                    CheatOp::Patch { addr, value } => {
+                        self.patches.insert(addr, value as u8);
+                        self.patches.insert(addr + 1, (value >> 8) as u8);
+                    },
+                    CheatOp::If { skip, .. } => guarded_until = guarded_until.max(i + 1 + skip),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    pub fn is_ram(addr: u32) -> bool { matches!(addr >> 24, 0x02 | 0x03) }
+
+    // Undoes RAM mirroring
+    pub fn canonical(addr: u32) -> u32 {
+        match addr >> 24 {
+            0x02 => 0x0200_0000 | addr & 0x3FFFF,
+            0x03 => 0x0300_0000 | addr & 0x7FFF,
+            _ => addr,
+        }
+    }
+}
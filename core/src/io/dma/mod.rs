@@ -22,12 +22,19 @@ impl DMA {
         }
     }
 
-    pub fn get_channel_running(&mut self, hblank_called: bool, vblank_called: bool, fifo_req: [bool; 2]) -> usize {
+    pub fn get_channel_running(&mut self, hblank_called: bool, vblank_called: bool, video_capture_called: bool,
+        fifo_req: [bool; 2]) -> usize {
         for (i, channel) in self.channels.iter().enumerate() {
-            if (*channel).needs_to_transfer(hblank_called, vblank_called, fifo_req) { return i }
+            if (*channel).needs_to_transfer(hblank_called, vblank_called, video_capture_called, fifo_req) { return i }
         }
         return 4;
     }
+
+    // Video capture stops by itself after the last captured line
+    pub fn end_video_capture(&mut self) {
+        let channel = &mut self.channels[3];
+        if channel.cnt.start_timing == 3 { channel.cnt.enable = false }
+    }
 }
 
 pub struct DMAChannel {
@@ -60,7 +67,8 @@ impl DMAChannel {
         }
     }
 
-    pub fn needs_to_transfer(&self, hblank_called: bool, vblank_called: bool, fifo_req: [bool; 2]) -> bool {
+    pub fn needs_to_transfer(&self, hblank_called: bool, vblank_called: bool, video_capture_called: bool,
+        fifo_req: [bool; 2]) -> bool {
         if !self.cnt.enable { return false }
         match self.cnt.start_timing {
             0 => true,
@@ -70,7 +78,7 @@ impl DMAChannel {
                 0 => { warn!("Special DMA for DMA 0 Called!"); false }
                 1 | 2 => fifo_req[0] && self.dad.addr == DMAChannel::FIFO_A_ADDR ||
                          fifo_req[1] && self.dad.addr == DMAChannel::FIFO_B_ADDR,
-                3 => { warn!("Video Capture DMA Called!"); false }, // TODO: Implement Video Capture DMA
+                3 => video_capture_called,
                 _ => unreachable!(),
             },
             _ => unreachable!(),
@@ -82,6 +90,16 @@ impl DMAChannel {
         self.dad_latch = self.dad.addr;
         self.count_latch = if self.count.count == 0 { self.count.get_max() + 1 } else { self.count.count as u32 };
     }
+
+    // Like read, but with the addresses and count that can't be read back
+    pub fn inspect(&self, byte: u8) -> u8 {
+        match byte {
+            0x0 ..= 0x3 => (self.sad.addr >> (8 * byte)) as u8,
+            0x4 ..= 0x7 => (self.dad.addr >> (8 * (byte - 4))) as u8,
+            0x8 | 0x9 => (self.count.count >> (8 * (byte - 8))) as u8,
+            _ => self.read(byte),
+        }
+    }
 }
 
 impl IORegister for DMAChannel {
@@ -125,3 +143,6 @@ impl IORegister for DMAChannel {
         }
     }
 }
+
+impl_save_state!(DMA { channels, in_dma });
+impl_save_state!(DMAChannel { sad_latch, dad_latch, count_latch, sad, dad, count, cnt });
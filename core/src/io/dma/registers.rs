@@ -114,3 +114,9 @@ impl IORegister for DMACNT {
         }
     }
 }
+
+impl_save_state!(Address { addr });
+impl_save_state!(WordCount { count });
+impl_save_state!(DMACNT {
+    dest_addr_ctrl, src_addr_ctrl, repeat, transfer_32, game_pak_drq, start_timing, irq, enable,
+} where |cnt| cnt.start_timing <= 3 && cnt.src_addr_ctrl <= 3 && cnt.dest_addr_ctrl <= 3);
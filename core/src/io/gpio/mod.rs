@@ -5,6 +5,8 @@ pub use rtc::RTC;
 
 pub trait GPIO {
     fn clock(&mut self);
+    // Devices can skip ahead instead of being clocked every cycle
+    fn clock_many(&mut self, cycles: usize) { for _ in 0..cycles { self.clock() } }
     fn process_write(&mut self);
     fn read(&self, byte: u8) -> u8;
     fn write(&mut self, byte: u8, value: u8);
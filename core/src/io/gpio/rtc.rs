@@ -1,5 +1,6 @@
 use super::GPIO;
 use crate::gba;
+use crate::savestate::{SaveState, StateReader, StateError};
 
 pub struct RTC {
     // Pins
@@ -24,8 +25,8 @@ impl RTC {
     const BIT_REVERSAL: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
 
     pub fn new(rom: &Vec<u8>) -> RTC {
-        let is_used = (0..(rom.len() - RTC::IDENTIFIER_STRING.len())).any(|i|
-            rom[i..(i + RTC::IDENTIFIER_STRING.len())] == *RTC::IDENTIFIER_STRING
+        let is_used = rom.windows(RTC::IDENTIFIER_STRING.len()).any(|window|
+            window == RTC::IDENTIFIER_STRING
         );
         RTC {
             // Pins
@@ -45,6 +46,17 @@ impl RTC {
         }
     }
 
+    // Year, month, day, day of week, hour, minute and second in BCD
+    pub fn get_date_time(&self) -> [u8; 7] {
+        let date_time = &self.date_time;
+        [date_time.year.value(), date_time.month.value(), date_time.day.value(), date_time.day_of_week.value(),
+            date_time.hour.value(), date_time.minute.value(), date_time.second.value()]
+    }
+
+    pub fn set_date_time(&mut self, values: [u8; 7]) {
+        for (byte, value) in values.iter().enumerate() { self.date_time.write(byte as u8, *value) }
+    }
+
     fn read_parameter(&mut self, parameter: Parameter) -> (u8, Parameter) {
         let value = match parameter {
             Parameter::Control(byte) => {
@@ -125,6 +137,20 @@ impl GPIO for RTC {
         } else { self.counter -= 1}
     }
 
+    fn clock_many(&mut self, mut cycles: usize) {
+        if !self.is_used { return }
+        while cycles > 0 {
+            if self.counter == 0 {
+                self.clock();
+                cycles -= 1;
+            } else {
+                let skipped = cycles.min(self.counter);
+                self.counter -= skipped;
+                cycles -= skipped;
+            }
+        }
+    }
+
     fn process_write(&mut self) {
         self.mode = match self.mode {
             Mode::StartCommand { done: false } => {
@@ -416,3 +442,91 @@ impl BCD {
     pub fn value(&self) -> u8 { self.value }
     pub fn set_value(&mut self, value: u8) { self.value = value; assert!(self.value & 0xF < 0xA && self.value >> 4 < 0xA) }
 }
+
+impl_save_state!(RTC { prev_sck, sck, sio, cs, write_only, write_mask, mode, last_byte, counter, date_time });
+impl_save_state!(Control { is_24h, per_min_irq });
+impl_save_state!(DateTime { control, year, month, day, day_of_week, is_pm, hour, minute, second });
+impl_save_state!(BCD { value } where |bcd| bcd.value & 0xF < 0xA && bcd.value >> 4 < 0xA);
+
+impl SaveState for Mode {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        match *self {
+            Mode::StartCommand { done } => { 0u8.save_state(state); done.save_state(state) },
+            Mode::SetCommand(command, bit) => { 1u8.save_state(state); command.save_state(state); bit.save_state(state) },
+            Mode::ExecCommand(parameter, access_type) => {
+                2u8.save_state(state);
+                parameter.save_state(state);
+                access_type.save_state(state);
+            },
+            Mode::EndCommand => 3u8.save_state(state),
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        *self = match state.read::<u8>()? {
+            0 => Mode::StartCommand { done: state.read()? },
+            1 => Mode::SetCommand(state.read()?, state.read()?),
+            2 => {
+                let mut parameter = Parameter::Reset;
+                let mut access_type = AccessType::Read(0, 0);
+                parameter.load_state(state)?;
+                access_type.load_state(state)?;
+                Mode::ExecCommand(parameter, access_type)
+            },
+            3 => Mode::EndCommand,
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl SaveState for AccessType {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        let (tag, value, bit) = match *self {
+            AccessType::Read(value, bit) => (0u8, value, bit),
+            AccessType::Write(value, bit) => (1u8, value, bit),
+        };
+        tag.save_state(state);
+        value.save_state(state);
+        bit.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let tag = state.read::<u8>()?;
+        let (value, bit) = (state.read()?, state.read()?);
+        *self = match tag {
+            0 => AccessType::Read(value, bit),
+            1 => AccessType::Write(value, bit),
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl SaveState for Parameter {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        let (tag, byte) = match *self {
+            Parameter::Control(byte) => (0u8, byte),
+            Parameter::DateTime(byte) => (1u8, byte),
+            Parameter::Time(byte) => (2u8, byte),
+            Parameter::Reset => (3u8, 0),
+            Parameter::IRQ => (4u8, 0),
+        };
+        tag.save_state(state);
+        byte.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let tag = state.read::<u8>()?;
+        let byte = state.read::<u8>()?;
+        *self = match tag {
+            0 => Parameter::Control(byte),
+            1 => Parameter::DateTime(byte),
+            2 => Parameter::Time(byte),
+            3 => Parameter::Reset,
+            4 => Parameter::IRQ,
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
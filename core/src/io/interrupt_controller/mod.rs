@@ -18,3 +18,5 @@ impl InterruptController {
         }
     }
 }
+
+impl_save_state!(InterruptController { enable, master_enable, request });
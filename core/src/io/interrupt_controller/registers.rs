@@ -97,3 +97,5 @@ impl IORegister for InterruptRequest {
         }
     }
 }
+
+impl_save_state_bitflags!(InterruptEnable, InterruptMasterEnable, InterruptRequest);
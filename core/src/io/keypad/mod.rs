@@ -8,11 +8,11 @@ use registers::*;
 pub struct Keypad {
     pub keyinput: KEYINPUT,
     pub keycnt: KEYCNT,
-    rx: Receiver<(KEYINPUT, bool)>
+    rx: Option<Receiver<(KEYINPUT, bool)>>,
 }
 
 impl Keypad {
-    pub fn new(rx: Receiver<(KEYINPUT, bool)>) -> Keypad {
+    pub fn new(rx: Option<Receiver<(KEYINPUT, bool)>>) -> Keypad {
         Keypad {
             keyinput: KEYINPUT::all(),
             keycnt: KEYCNT::empty(),
@@ -21,11 +21,13 @@ impl Keypad {
     }
 
     pub fn poll(&mut self) {
-        for (key, pressed) in self.rx.try_iter() {
-            if pressed {
-                self.keyinput.remove(key);
-            } else {
-                self.keyinput.insert(key);
+        if let Some(rx) = &self.rx {
+            for (key, pressed) in rx.try_iter() {
+                if pressed {
+                    self.keyinput.remove(key);
+                } else {
+                    self.keyinput.insert(key);
+                }
             }
         }
     }
@@ -38,3 +40,5 @@ impl Keypad {
         } else { false }
     }
 }
+
+impl_save_state!(Keypad { keyinput, keycnt });
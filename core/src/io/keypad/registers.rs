@@ -61,3 +61,5 @@ impl IORegister for KEYCNT {
         }
     }
 }
+
+impl_save_state_bitflags!(KEYINPUT, KEYCNT);
@@ -1,6 +1,6 @@
 use std::mem::size_of;
 use num::{cast::FromPrimitive, NumCast, PrimInt, Unsigned};
-use super::{PPU, GPIO, IO, IORegister};
+use super::{PPU, GPIO, HaltMode, IO, IORegister, InterruptRequest};
 
 impl MemoryHandler for IO {
     fn read<T>(&self, addr: u32) -> T where T: MemoryValue {
@@ -29,8 +29,14 @@ impl MemoryHandler for IO {
     fn write<T>(&mut self, addr: u32, value: T) where T: MemoryValue {
         match MemoryRegion::get_region(addr) {
             MemoryRegion::BIOS => (),
-            MemoryRegion::EWRAM => IO::write_mem(&mut self.ewram, addr & IO::EWRAM_MASK, value),
-            MemoryRegion::IWRAM => IO::write_mem(&mut self.iwram, addr & IO::IWRAM_MASK, value),
+            MemoryRegion::EWRAM => {
+                IO::write_mem(&mut self.ewram, addr & IO::EWRAM_MASK, value);
+                if self.cheats.has_frozen() { self.refreeze_cheats(addr, size_of::<T>() as u32) }
+            },
+            MemoryRegion::IWRAM => {
+                IO::write_mem(&mut self.iwram, addr & IO::IWRAM_MASK, value);
+                if self.cheats.has_frozen() { self.refreeze_cheats(addr, size_of::<T>() as u32) }
+            },
             MemoryRegion::IO => IO::write_from_bytes(self, &IO::write_register, addr, value),
             MemoryRegion::Palette => self.write_palette_ram(addr, value),
             MemoryRegion::VRAM => self.write_vram(PPU::parse_vram_addr(addr), value),
@@ -97,6 +103,12 @@ impl IO {
         self.instr_buffer = instr_buffer.clone();
     }
 
+    // Side effects of the CPU or DMA reading, which peeking at memory leaves out
+    pub fn finish_read(&mut self, addr: u32, size: u32) {
+        const SIODATA8: u32 = 0x0400012A;
+        if addr <= SIODATA8 && SIODATA8 - addr < size { self.serial.pop_uart_data() }
+    }
+
     fn read_openbus<T>(&self, addr: u32) -> T where T: MemoryValue {
         let value = if self.in_thumb {
             match MemoryRegion::get_region(self.pc) {
@@ -148,7 +160,15 @@ impl IO {
     }
 
     fn read_io_register(&self, addr: u32) -> u8 {
-        match addr {
+        self.peek_io_register(addr).unwrap_or_else(|| {
+            warn!("Reading Unimplemented IO Register at {:08X}", addr);
+            0
+        })
+    }
+
+    // None for registers that aren't implemented
+    pub fn peek_io_register(&self, addr: u32) -> Option<u8> {
+        Some(match addr {
             0x04000000 ..= 0x0400005F => self.ppu.read_register(addr),
             0x04000060 ..= 0x040000AF => self.apu.read_register(addr),
             0x040000B0 ..= 0x040000BB => self.dma.channels[0].read(addr as u8 - 0xB0),
@@ -159,10 +179,12 @@ impl IO {
             0x04000104 ..= 0x04000107 => self.timers.timers[1].read(&self.scheduler, addr as u8 % 4),
             0x04000108 ..= 0x0400010B => self.timers.timers[2].read(&self.scheduler, addr as u8 % 4),
             0x0400010C ..= 0x0400010F => self.timers.timers[3].read(&self.scheduler, addr as u8 % 4),
+            0x04000120 ..= 0x0400012F => self.serial.read_register(addr),
             0x04000130 => self.keypad.keyinput.read(0),
             0x04000131 => self.keypad.keyinput.read(1),
             0x04000132 => self.keypad.keycnt.read(0),
             0x04000133 => self.keypad.keycnt.read(1),
+            0x04000134 ..= 0x04000135 => self.serial.read_register(addr),
             0x04000200 => self.interrupt_controller.enable.read(0),
             0x04000201 => self.interrupt_controller.enable.read(1),
             0x04000202 => self.interrupt_controller.request.read(0),
@@ -176,10 +198,53 @@ impl IO {
             0x04000300 => self.haltcnt as u8,
             0x04000301 => (self.haltcnt >> 8) as u8,
             0x04FFF780 ..= 0x04FFF781 => self.mgba_test_suite.read_register(addr),
-            _ => { warn!("Reading Unimplemented IO Register at {:08X}", addr); 0 }
+            _ => return None,
+        })
+    }
+
+    // What a register holds, including what was written to write only registers
+    pub fn inspect_io_register(&self, addr: u32) -> u8 {
+        match addr {
+            0x04000000 ..= 0x0400005F => self.ppu.inspect_register(addr),
+            0x04000060 ..= 0x040000AF => self.apu.inspect_register(addr),
+            0x040000B0 ..= 0x040000BB => self.dma.channels[0].inspect(addr as u8 - 0xB0),
+            0x040000BC ..= 0x040000C7 => self.dma.channels[1].inspect(addr as u8 - 0xBC),
+            0x040000C8 ..= 0x040000D3 => self.dma.channels[2].inspect(addr as u8 - 0xC8),
+            0x040000D4 ..= 0x040000DF => self.dma.channels[3].inspect(addr as u8 - 0xD4),
+            _ => self.peek_io_register(addr).unwrap_or(0),
         }
     }
 
+    // Changes what a register holds without anything a CPU write would set off: IF is set rather than
+    // acknowledged, HALTCNT doesn't halt and bits that start DMAs, timers, transfers or sounds keep their value
+    pub fn poke_io_register(&mut self, addr: u32, value: u8) {
+        let current = self.inspect_io_register(addr);
+        let value = match addr {
+            0x04000065 | 0x0400006D | 0x04000075 | 0x0400007D => value & !0x80,
+            0x04000083 => value & !0x88,
+            0x04000084 => value & !0x80 | current & 0x80,
+            0x040000A0 ..= 0x040000A7 => return,
+            0x040000BB | 0x040000C7 | 0x040000D3 | 0x040000DF => value & !0x80 | current & 0x80,
+            0x04000102 | 0x04000106 | 0x0400010A | 0x0400010E => value & !0x80 | current & 0x80,
+            0x04000120 ..= 0x0400012F | 0x04000134 ..= 0x04000135 => {
+                self.serial.poke_register(&mut self.scheduler, addr, value);
+                return
+            },
+            0x04000202 | 0x04000203 => {
+                let shift = 8 * (addr & 0x1);
+                let bits = self.interrupt_controller.request.bits() & !(0xFF << shift) | (value as u16) << shift;
+                self.interrupt_controller.request = InterruptRequest::from_bits_truncate(bits);
+                return
+            },
+            0x04000301 => {
+                self.haltcnt = (self.haltcnt & !0xFF00) | (value as u16) << 8;
+                return
+            },
+            _ => value,
+        };
+        self.write_register(addr, value)
+    }
+
     fn write_register(&mut self, addr: u32, value: u8) {
         let _event = match addr {
             0x04000000 ..= 0x0400005F => self.ppu.write_register(&mut self.scheduler, addr, value),
@@ -192,10 +257,12 @@ impl IO {
             0x04000104 ..= 0x04000107 => self.timers.timers[1].write(&mut self.scheduler, addr as u8 % 4, value),
             0x04000108 ..= 0x0400010B => self.timers.timers[2].write(&mut self.scheduler, addr as u8 % 4, value),
             0x0400010C ..= 0x0400010F => self.timers.timers[3].write(&mut self.scheduler, addr as u8 % 4, value),
+            0x04000120 ..= 0x0400012F => self.serial.write_register(&mut self.scheduler, addr, value),
             0x04000130 => self.keypad.keyinput.write(&mut self.scheduler, 0, value),
             0x04000131 => self.keypad.keyinput.write(&mut self.scheduler, 1, value),
             0x04000132 => self.keypad.keycnt.write(&mut self.scheduler, 0, value),
             0x04000133 => self.keypad.keycnt.write(&mut self.scheduler, 1, value),
+            0x04000134 ..= 0x04000135 => self.serial.write_register(&mut self.scheduler, addr, value),
             0x04000200 => self.interrupt_controller.enable.write(&mut self.scheduler, 0, value),
             0x04000201 => self.interrupt_controller.enable.write(&mut self.scheduler, 1, value),
             0x04000202 => self.interrupt_controller.request.write(&mut self.scheduler, 0, value),
@@ -207,7 +274,10 @@ impl IO {
             0x04000209 => self.interrupt_controller.master_enable.write(&mut self.scheduler, 1, value),
             0x0400020A ..= 0x040002FF => (), // Unused IO Register
             0x04000300 => self.haltcnt = (self.haltcnt & !0x00FF) | value as u16,
-            0x04000301 => self.haltcnt = (self.haltcnt & !0xFF00) | (value as u16) << 8,
+            0x04000301 => {
+                self.haltcnt = (self.haltcnt & !0xFF00) | (value as u16) << 8;
+                self.halt_mode = if value & 0x80 != 0 { HaltMode::Stopped } else { HaltMode::Halted };
+            },
             0x04FFF600 ..= 0x04FFF701 => self.mgba_test_suite.write_register(addr, value),
             0x04FFF780 ..= 0x04FFF781 => self.mgba_test_suite.write_enable(addr, value),
             _ => warn!("Writng Unimplemented IO Register at {:08X} = {:08X}", addr, 0),
@@ -231,7 +301,7 @@ impl IO {
 
     fn read_rom<T>(&self, addr: u32) -> T where T: MemoryValue {
         let addr = addr - 0x08000000;
-        if (addr as usize) < self.rom.len() { IO::read_mem(&self.rom, addr) }
+        if (addr as usize) < self.rom.len() { self.cheats.patch(0x08000000 | addr & 0x01FFFFFF, IO::read_mem(&self.rom, addr)) }
         else { warn!("Returning Invalid ROM Read at 0x{:08X}", addr + 0x08000000); num::zero() }
     }
 
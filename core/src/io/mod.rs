@@ -6,13 +6,15 @@ mod dma;
 mod timers;
 pub mod keypad;
 mod interrupt_controller;
+mod serial;
 mod gpio;
 mod cart_backup;
+mod timeline;
+mod cheats;
 
 use std::cell::Cell;
 use std::collections::VecDeque;
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use flume::{Receiver, Sender};
 
@@ -24,11 +26,19 @@ use ppu::PPU;
 use apu::APU;
 use keypad::{Keypad, KEYINPUT};
 use interrupt_controller::{InterruptController, InterruptRequest};
+use serial::Serial;
 use gpio::{GPIO, RTC};
 use cart_backup::CartBackup;
+use timeline::Timeline;
+use cheats::Cheats;
 
 use crate::gba::VisibleMemoryRegion;
-pub use ppu::{DebugSpecification, DebugWindows};
+pub use ppu::{DebugSpecification, DebugWindows, OBJInfo, OBJMode};
+pub use apu::{AudioSink, NullSink, RingBufferSink, SampleBuffer, AudioChannel, AudioChannelState, AudioState};
+pub use serial::{LinkMessage, LinkTransport, LocalLink, TcpLink};
+pub use mgba_test_suite::MGBALogLevel;
+pub use timeline::{TimelineEntry, TimelineEvent};
+pub use cheats::{Cheat, CheatCondition, CheatError, CheatFormat, CheatOp};
 
 pub struct IO {
     bios: Vec<u8>,
@@ -45,11 +55,13 @@ pub struct IO {
     timers: Timers,
     keypad: Keypad,
     interrupt_controller: InterruptController,
+    serial: Serial,
     rtc: RTC,
     cart_backup: Box<dyn CartBackup>,
 
     // Registers
     haltcnt: u16,
+    halt_mode: HaltMode,
     waitcnt: WaitStateControl,
 
     // Open Bus
@@ -59,20 +71,24 @@ pub struct IO {
     bios_latch: Cell<u32>,
 
     mgba_test_suite: mgba_test_suite::MGBATestSuite,
+
+    // Debugging
+    watchpoints: Vec<Watchpoint>,
+    watchpoint_hit: Option<(Watchpoint, u32)>,
+    timeline: Option<Timeline>,
+
+    cheats: Cheats,
 }
 
 impl IO {
     const EWRAM_MASK: u32 = 0x3FFFF;
     const IWRAM_MASK: u32 = 0x7FFF;
 
-    pub fn new(bios: Vec<u8>, rom_file: PathBuf, render_tx: Sender<DebugWindows>, keypad_rx: Receiver<(KEYINPUT, bool)>) ->
+    pub fn new(bios: Vec<u8>, rom: Vec<u8>, save_data: Option<Vec<u8>>, save_file: Option<PathBuf>,
+        render_tx: Option<Sender<DebugWindows>>, keypad_rx: Option<Receiver<(KEYINPUT, bool)>>, audio: Box<dyn AudioSink>) ->
         (IO, Arc<Mutex<Vec<u16>>>, Arc<Mutex<DebugSpecification>>) {
         let (ppu, pixels, debug_windows_spec) = PPU::new(render_tx);
-        assert_eq!(rom_file.extension().unwrap(), "gba");
-        let mut save_file = rom_file.clone();
-        save_file.set_extension("sav");
-        let rom = fs::read(rom_file).unwrap();
-        let cart_backup = CartBackup::get(&rom, save_file);
+        let cart_backup = CartBackup::get(&rom, save_data, save_file);
         let rtc = RTC::new(&rom);
         (IO {
             bios,
@@ -84,25 +100,35 @@ impl IO {
 
             // IO
             ppu,
-            apu: APU::new(),
+            apu: APU::new(audio),
             dma: DMA::new(),
             timers: Timers::new(),
             keypad: Keypad::new(keypad_rx),
             interrupt_controller: InterruptController::new(),
+            serial: Serial::new(),
             rtc,
             cart_backup,
 
             // Registers
             haltcnt: 0,
+            halt_mode: HaltMode::Running,
             waitcnt: WaitStateControl::new(),
 
             // Open Bus
             pc: 0,
             in_thumb: false,
             instr_buffer: [0; 2],
-            bios_latch: Cell::new(0),
+            // Last opcode fetched by the BIOS before jumping to the ROM
+            bios_latch: Cell::new(0xE129F000),
 
             mgba_test_suite: mgba_test_suite::MGBATestSuite::new(),
+
+            // Debugging
+            watchpoints: Vec::new(),
+            watchpoint_hit: None,
+            timeline: None,
+
+            cheats: Cheats::new(),
         }, pixels, debug_windows_spec)
     }
 
@@ -135,35 +161,312 @@ impl IO {
         self.clocks_ahead += clocks_inc;
         while self.clocks_ahead >= 4 {
             self.clocks_ahead -= 4;
-            self.interrupt_controller.request |= self.ppu.emulate_dot();
+            let interrupts = self.emulate_dot();
+            self.interrupt_controller.request |= interrupts;
         }
     }
 
+    fn emulate_dot(&mut self) -> InterruptRequest {
+        let interrupts = self.ppu.emulate_dot();
+        if self.timeline.is_some() {
+            let cycle = self.scheduler.cycle;
+            match self.ppu.blank_started() {
+                Some(TimelineEvent::HBlank) => self.record_timeline(TimelineEvent::HBlank, cycle, cycle + PPU::HBLANK_CYCLES),
+                Some(TimelineEvent::VBlank) => self.record_timeline(TimelineEvent::VBlank, cycle, cycle + PPU::VBLANK_CYCLES),
+                _ => (),
+            }
+        }
+        interrupts
+    }
+
     pub fn interrupts_requested(&mut self) -> bool {
+        self.interrupt_controller.master_enable.bits() != 0 && self.pending_interrupts() != 0
+    }
+
+    // IE & IF, which wakes the CPU from halt regardless of IME
+    fn pending_interrupts(&mut self) -> u16 {
         if self.keypad.interrupt_requested() { self.interrupt_controller.request |= InterruptRequest::KEYPAD }
 
-        self.interrupt_controller.master_enable.bits() != 0 &&
-        (self.interrupt_controller.request.bits() & self.interrupt_controller.enable.bits()) != 0
+        self.interrupt_controller.request.bits() & self.interrupt_controller.enable.bits()
+    }
+
+    pub fn get_halt_mode(&self) -> HaltMode { self.halt_mode }
+
+    // Runs everything but the CPU until an interrupt wakes it, a DMA might start or until_cycle is reached
+    pub fn run_halted(&mut self, until_cycle: usize) {
+        let start_cycle = self.scheduler.cycle;
+        self.run_halted_until(until_cycle);
+        if self.scheduler.cycle > start_cycle { self.record_timeline(TimelineEvent::Halt, start_cycle, self.scheduler.cycle) }
+    }
+
+    fn run_halted_until(&mut self, until_cycle: usize) {
+        while self.scheduler.cycle < until_cycle && !self.dma_requested() {
+            if self.pending_interrupts() != 0 {
+                self.halt_mode = HaltMode::Running;
+                return
+            }
+
+            // Jump to the next event, stopping early only if a PPU dot raises an interrupt or DMA
+            let target_cycle = self.scheduler.next_event_cycle().min(until_cycle).max(self.scheduler.cycle + 1);
+            while self.scheduler.cycle < target_cycle && !self.dma_requested() {
+                // Everything up to the next dot that does something happens in one go
+                let dots_cycles = 4 - self.clocks_ahead as usize + 4 * self.ppu.idle_dots();
+                let cycles = dots_cycles.min(target_cycle - self.scheduler.cycle);
+                self.rtc.clock_many(cycles - 1);
+                self.apu.clock_many(cycles - 1);
+                self.scheduler.cycle += cycles;
+                // Events come before the last cycle is clocked, as in inc_clock, since timers change the FIFO samples
+                while let Some(event) = self.scheduler.get_next_event() {
+                    self.handle_event(event);
+                }
+                self.rtc.clock();
+                self.apu.clock();
+                let dots = (self.clocks_ahead as usize + cycles) / 4;
+                self.clocks_ahead = (self.clocks_ahead + cycles as u32) % 4;
+                if dots > 0 {
+                    self.ppu.skip_dots(dots - 1);
+                    let interrupts = self.emulate_dot();
+                    self.interrupt_controller.request |= interrupts;
+                    if !interrupts.is_empty() || self.dma_requested() { break }
+                }
+            }
+        }
     }
 
+    // Timers run the sound FIFO DMAs, which have to keep up while halted
+    fn dma_requested(&self) -> bool { self.ppu.dma_requested() || self.apu.fifo_dma_requested() }
+
+    // The system clock is stopped, so only the keypad, serial and cartridge can wake the CPU
+    pub fn run_stopped(&mut self, until_cycle: usize) {
+        let wake_interrupts = (InterruptRequest::KEYPAD | InterruptRequest::SERIAL | InterruptRequest::GAME_PAK).bits();
+        if self.pending_interrupts() & wake_interrupts != 0 {
+            self.halt_mode = HaltMode::Running;
+            return
+        }
+        let start_cycle = self.scheduler.cycle;
+        self.skip_cycles(until_cycle.saturating_sub(self.scheduler.cycle));
+        if self.scheduler.cycle > start_cycle { self.record_timeline(TimelineEvent::Halt, start_cycle, self.scheduler.cycle) }
+        // Keep the frontend fed with frames while the LCD is off
+        self.ppu.signal_frame();
+    }
+
+    // Reads the backing memory directly, so there's no BIOS latch, open bus or mirroring
     pub fn peek_mem(&self, region: VisibleMemoryRegion, addr: u32) -> u8 {
-        self.read::<u8>(region.get_start_addr() + addr)
+        let index = addr as usize;
+        match region {
+            VisibleMemoryRegion::BIOS => self.bios[index],
+            VisibleMemoryRegion::EWRAM => self.ewram[index],
+            VisibleMemoryRegion::IWRAM => self.iwram[index],
+            VisibleMemoryRegion::IO => self.peek_io_register(region.get_start_addr() + addr).unwrap_or(0),
+            VisibleMemoryRegion::Palette => self.ppu.read_palette_ram(addr),
+            VisibleMemoryRegion::VRAM => self.ppu.vram[index],
+            VisibleMemoryRegion::OAM => self.ppu.oam[index],
+            VisibleMemoryRegion::PakROM => self.rom.get(index).copied().unwrap_or(0),
+            VisibleMemoryRegion::CartRAM => self.cart_backup.peek(addr),
+        }
+    }
+
+    // Writes a single byte without the 8 bit write quirks of VRAM, OAM and palette RAM, or the side effects of
+    // writing IO registers
+    pub fn poke_mem(&mut self, region: VisibleMemoryRegion, addr: u32, value: u8) {
+        let index = addr as usize;
+        match region {
+            VisibleMemoryRegion::BIOS => self.bios[index] = value,
+            VisibleMemoryRegion::EWRAM => self.ewram[index] = value,
+            VisibleMemoryRegion::IWRAM => self.iwram[index] = value,
+            VisibleMemoryRegion::IO => self.poke_io_register(region.get_start_addr() + addr, value),
+            VisibleMemoryRegion::Palette => self.ppu.write_palette_ram(addr, value),
+            VisibleMemoryRegion::VRAM => self.ppu.vram[index] = value,
+            VisibleMemoryRegion::OAM => self.ppu.oam[index] = value,
+            VisibleMemoryRegion::PakROM => if let Some(byte) = self.rom.get_mut(index) { *byte = value },
+            VisibleMemoryRegion::CartRAM => self.cart_backup.poke(addr, value),
+        }
     }
 
     pub fn get_cycle(&self) -> usize { self.scheduler.cycle }
 
+    pub fn set_bios_latch(&self, value: u32) { self.bios_latch.set(value) }
+
+    pub fn set_link(&mut self, link: Box<dyn LinkTransport>) {
+        self.serial.set_link(link);
+        self.schedule_serial_poll();
+    }
+
+    pub fn set_audio_speed(&mut self, speed: f64) { self.apu.set_speed(speed) }
+
+    pub fn set_skip_rendering(&mut self, skip: bool) { self.ppu.skip_rendering = skip }
+
+    pub fn set_audio_channel_muted(&mut self, channel: AudioChannel, muted: bool) { self.apu.set_channel_muted(channel, muted) }
+
+    pub fn set_audio_channel_soloed(&mut self, channel: AudioChannel, soloed: bool) {
+        self.apu.set_channel_soloed(channel, soloed)
+    }
+
+    pub fn enable_audio_scope(&mut self, enable: bool) { self.apu.enable_scope(enable) }
+
+    pub fn get_audio_state(&self) -> AudioState { self.apu.get_state() }
+
+    pub fn start_audio_recording(&mut self, path: &Path, channels: bool) -> std::io::Result<()> {
+        self.apu.start_recording(path, channels)
+    }
+
+    pub fn stop_audio_recording(&mut self) -> std::io::Result<()> { self.apu.stop_recording() }
+
+    pub fn capture_debug_messages(&mut self) { self.mgba_test_suite.capture_messages() }
+
+    pub fn take_debug_messages(&mut self) -> Vec<(MGBALogLevel, String)> { self.mgba_test_suite.take_messages() }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) { self.watchpoints.push(watchpoint) }
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|other| *other != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    pub fn get_watchpoints(&self) -> &[Watchpoint] { &self.watchpoints }
+
+    // Called on every data access by the CPU
+    pub fn check_watchpoints(&mut self, addr: u32, size: u32, write: bool) {
+        if self.watchpoints.is_empty() { return }
+        let hit = self.watchpoints.iter().find(|watchpoint| {
+            let kind_matches = match watchpoint.kind {
+                WatchKind::Read => !write,
+                WatchKind::Write => write,
+                WatchKind::Access => true,
+            };
+            let overlaps = watchpoint.addr.wrapping_sub(addr) < size || addr.wrapping_sub(watchpoint.addr) < watchpoint.len;
+            kind_matches && overlaps
+        });
+        if let Some(watchpoint) = hit { self.watchpoint_hit = Some((*watchpoint, addr)) }
+    }
+
+    // Watchpoint and accessed address, if any were hit since the last call
+    pub fn take_watchpoint_hit(&mut self) -> Option<(Watchpoint, u32)> { self.watchpoint_hit.take() }
+
+    pub fn enable_timeline(&mut self, enable: bool) {
+        self.timeline = if enable { Some(Timeline::new()) } else { None };
+    }
+
+    pub fn take_timeline(&mut self, frame_start: usize) -> Option<Vec<TimelineEntry>> {
+        self.timeline.as_mut().map(|timeline| timeline.take_frame(frame_start))
+    }
+
+    pub fn record_timeline(&mut self, event: TimelineEvent, start: usize, end: usize) {
+        if let Some(timeline) = &mut self.timeline { timeline.record(event, start, end) }
+    }
+
+    // Called by the CPU when it takes an IRQ
+    pub fn record_irq(&mut self) {
+        let cycle = self.scheduler.cycle;
+        let interrupts = self.pending_interrupts();
+        self.record_timeline(TimelineEvent::IRQ(interrupts), cycle, cycle);
+    }
+
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) { self.cheats.set(cheats) }
+
+    pub fn get_cheats(&self) -> &[Cheat] { self.cheats.get() }
+
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        self.cheats.set_enabled(index, enabled)
+    }
+
+    // Runs every enabled cheat once, which is done at the start of each frame
+    pub fn apply_cheats(&mut self) {
+        if !self.cheats.any_enabled() { return }
+        for ops in self.cheats.enabled_ops() { self.apply_cheat(&ops) }
+    }
+
+    // Conditions only skip ops within their own cheat
+    fn apply_cheat(&mut self, ops: &[CheatOp]) {
+        let mut skip = 0;
+        for &op in ops.iter() {
+            if skip > 0 { skip -= 1; continue }
+            match op {
+                CheatOp::Write { addr, size, value } => self.write_cheat_value(addr, size, value),
+                CheatOp::Or { addr, size, value } => self.write_cheat_value(addr, size, self.read_cheat_value(addr, size) | value),
+                CheatOp::And { addr, size, value } => self.write_cheat_value(addr, size, self.read_cheat_value(addr, size) & value),
+                CheatOp::Add { addr, size, value } =>
+                    self.write_cheat_value(addr, size, self.read_cheat_value(addr, size).wrapping_add(value)),
+                CheatOp::If { addr, size, condition, value, skip: count } =>
+                    if !condition.test(self.read_cheat_value(addr, size), value, size) { skip = count },
+                CheatOp::Patch { .. } | CheatOp::Master => (),
+            }
+        }
+    }
+
+    fn read_cheat_value(&self, addr: u32, size: u32) -> u32 {
+        let addr = addr & !(size - 1);
+        match size {
+            1 => self.read::<u8>(addr) as u32,
+            2 => self.read::<u16>(addr) as u32,
+            _ => self.read::<u32>(addr),
+        }
+    }
+
+    fn write_cheat_value(&mut self, addr: u32, size: u32, value: u32) {
+        let addr = addr & !(size - 1);
+        match size {
+            1 => self.write::<u8>(addr, value as u8),
+            2 => self.write::<u16>(addr, value as u16),
+            _ => self.write::<u32>(addr, value),
+        }
+    }
+
+    // Puts back RAM set by constant write cheats after anything else writes to it
+    fn refreeze_cheats(&mut self, addr: u32, size: u32) {
+        for i in 0..size {
+            let addr = Cheats::canonical(addr + i);
+            if let Some(value) = self.cheats.get_frozen(addr) {
+                match addr >> 24 {
+                    0x02 => self.ewram[(addr & IO::EWRAM_MASK) as usize] = value,
+                    _ => self.iwram[(addr & IO::IWRAM_MASK) as usize] = value,
+                }
+            }
+        }
+    }
+
+    pub fn get_ewram(&self) -> &[u8] { &self.ewram }
+
+    // CRC-32 of EWRAM, IWRAM and the framebuffer
+    pub fn checksum(&self) -> u32 {
+        let mut data = [&self.ewram[..], &self.iwram[..]].concat();
+        data.extend(self.ppu.get_pixels().iter().flat_map(|pixel| pixel.to_le_bytes().to_vec()));
+        crate::savestate::crc32(&data)
+    }
+
+    pub fn get_keyinput(&self) -> u16 { self.keypad.keyinput.bits() }
+
+    // Replaces whatever was polled from the keypad channel
+    pub fn set_keyinput(&mut self, keyinput: u16) { self.keypad.keyinput = KEYINPUT::from_bits_truncate(keyinput) }
+
+    pub fn get_rtc_date_time(&self) -> [u8; 7] { self.rtc.get_date_time() }
+
+    pub fn set_rtc_date_time(&mut self, date_time: [u8; 7]) { self.rtc.set_date_time(date_time) }
+
+    pub fn get_iwram(&self) -> &[u8] { &self.iwram }
+
+    pub fn get_save_data(&self) -> &Vec<u8> { self.cart_backup.get_mem() }
+
+    pub fn get_rom(&self) -> &Vec<u8> { &self.rom }
+
     pub fn poll_keypad_updates(&mut self) {
-        if self.ppu.rendered_frame() {
+        if self.ppu.rendered_frame() || self.halt_mode == HaltMode::Stopped {
             self.cart_backup.save_to_file();
             self.keypad.poll();
         }
     }
 
     pub fn run_dma(&mut self) {
+        if self.ppu.video_capture_ended() { self.dma.end_video_capture() }
         let dma_channel = self.dma.get_channel_running(
-            self.ppu.hblank_called(), self.ppu.vblank_called(), [self.apu.fifo_a_req(), self.apu.fifo_b_req()]
+            self.ppu.hblank_called(), self.ppu.vblank_called(), self.ppu.video_capture_called(),
+            [self.apu.fifo_a_req(), self.apu.fifo_b_req()]
         );
         if dma_channel < 4 {
+            let start_cycle = self.scheduler.cycle;
             self.dma.in_dma = true;
             let channel = &mut self.dma.channels[dma_channel];
             let is_fifo = (channel.num == 1 || channel.num == 2) && channel.cnt.start_timing == 3;
@@ -193,6 +496,7 @@ impl IO {
                 self.inc_clock(cycle_type, dest_addr, access_width);
                 if transfer_32 { self.write::<u32>(dest_addr, self.read::<u32>(src_addr)) }
                 else { self.write::<u16>(dest_addr, self.read::<u16>(src_addr)) }
+                self.finish_read(src_addr, 1 << access_width);
 
                 src_addr = match src_addr_ctrl {
                     0 => src_addr.wrapping_add(addr_change),
@@ -224,6 +528,7 @@ impl IO {
                 _ => unreachable!(),
             } }
             self.dma.in_dma = false;
+            self.record_timeline(TimelineEvent::DMA(dma_channel), start_cycle, self.scheduler.cycle);
         }
     }
 }
@@ -253,6 +558,27 @@ impl std::convert::Into<Cycle> for AccessType {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub addr: u32,
+    pub len: u32,
+    pub kind: WatchKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HaltMode {
+    Running,
+    Halted,
+    Stopped,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Cycle {
     N,
@@ -389,10 +715,23 @@ impl IORegister for WaitStateControl {
     }
 }
 
-
+impl_save_state!(IO {
+    ewram, iwram, scheduler, clocks_ahead, ppu, apu, dma, timers, keypad, interrupt_controller, serial, rtc,
+    cart_backup,
+    haltcnt, halt_mode, waitcnt, pc, in_thumb, instr_buffer, bios_latch, mgba_test_suite,
+} where |io| io.ewram.len() == 0x40000 && io.iwram.len() == 0x8000 && io.timers.valid_at(io.scheduler.cycle));
+impl_save_state_enum!(AccessType { N, S });
+impl_save_state_enum!(HaltMode { Running, Halted, Stopped });
+impl_save_state!(WaitStateControl {
+    sram_setting, n_wait_state_settings, s_wait_state_settings, phi_terminal_out, use_prefetch, type_flag,
+    can_prefetch, prefetch, prefetch_waitstate, prefetch_addr, prefetch_cycles_spent,
+} where |waitcnt| waitcnt.sram_setting < 4 && waitcnt.n_wait_state_settings.iter().all(|&setting| setting < 4) &&
+    waitcnt.s_wait_state_settings.iter().all(|&setting| setting < 2) && waitcnt.phi_terminal_out < 4 &&
+    waitcnt.prefetch.len() <= 8 && waitcnt.prefetch_waitstate < 3);
 
 mod mgba_test_suite {
-    enum MGBALogLevel {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum MGBALogLevel {
         Fatal,
         Error,
         Warn,
@@ -419,6 +758,8 @@ mod mgba_test_suite {
         // Registers
         enable: u16,
         flags: u16,
+        // Only kept when something is going to take them, e.g. tests
+        messages: Option<Vec<(MGBALogLevel, String)>>,
     }
     
     impl MGBATestSuite {
@@ -427,8 +768,17 @@ mod mgba_test_suite {
                 buffer: ['\0'; 0x100],
                 enable: 0,
                 flags: 0,
+                messages: None,
             }
         }
+
+        pub fn capture_messages(&mut self) {
+            if self.messages.is_none() { self.messages = Some(Vec::new()) }
+        }
+
+        pub fn take_messages(&mut self) -> Vec<(MGBALogLevel, String)> {
+            self.messages.as_mut().map(std::mem::take).unwrap_or_default()
+        }
     
         pub fn enabled(&self) -> bool {
             self.enable == 0xC0DE
@@ -462,12 +812,14 @@ mod mgba_test_suite {
                         let null_byte_pos = self.buffer.iter().position(|&c| c == '\0')
                             .unwrap_or(self.buffer.len());
                         let message: String = self.buffer.iter().take(null_byte_pos).collect();
-                        
+                        let level = MGBALogLevel::new(self.flags & 0x7);
+                        if let Some(messages) = &mut self.messages { messages.push((level, message.clone())) }
+
                         if message.contains("PASS") { return }
                         let show_info = message.contains("FAIL") &&
                             !message.split(' ').skip(1).take(1).collect::<String>().contains("P");
                         let show_debug = !message.split(' ').rev().take(1).collect::<String>().contains("P");
-                        match MGBALogLevel::new(self.flags & 0x7) {
+                        match level {
                             Fatal => error!("{}", message),
                             Error => error!("{}", message),
                             Warn => warn!("{}", message),
@@ -480,4 +832,6 @@ mod mgba_test_suite {
             }
         }
     }
+
+    impl_save_state!(MGBATestSuite { buffer, enable, flags });
 }
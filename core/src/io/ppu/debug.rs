@@ -1,4 +1,4 @@
-use super::{BGMode, PPU};
+use super::{gba, BGMode, DISPCNTFlags, Layer, PPU, RotationScalingParameter};
 use std::collections::VecDeque;
 
 #[derive(Clone, Copy, Debug)]
@@ -6,12 +6,52 @@ pub struct DebugSpecification {
     pub map_enable: bool,
     pub tiles_enable: bool,
     pub palettes_enable: bool,
+    pub objs_enable: bool,
+    pub layers_enable: bool,
 
     pub map_spec: MapSpecification,
     pub tiles_spec: TilesSpecification,
+    pub layers_spec: LayersSpecification,
+    // Applies whether or not any debug window is open
+    pub layer_toggles: LayerToggles,
 }
 
-pub type DebugWindows = VecDeque<(Vec<u16>, usize, usize)>;
+#[derive(Clone, Debug, Default)]
+pub struct DebugWindows {
+    // Images for the enabled windows, in the order they're listed in the spec
+    pub images: VecDeque<(Vec<u16>, usize, usize)>,
+    // All 128 OAM entries, only filled in when OBJs are enabled
+    pub objs: Vec<OBJInfo>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OBJMode {
+    Normal,
+    SemiTransparent,
+    Window,
+    Prohibited,
+}
+
+#[derive(Clone, Debug)]
+pub struct OBJInfo {
+    pub x: i16,
+    // Negative when the OBJ wraps around from the bottom of the screen
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+    pub priority: u8,
+    // None in 256 color mode
+    pub palette: Option<usize>,
+    pub tile: usize,
+    pub mode: OBJMode,
+    pub mosaic: bool,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // Parameter group and pa, pb, pc, pd
+    pub affine: Option<(usize, [f64; 4])>,
+    pub double_size: bool,
+    pub disabled: bool,
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct MapSpecification {
@@ -25,19 +65,37 @@ pub struct TilesSpecification {
     pub bpp8: bool
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct LayersSpecification {
+    // BG0-3, OBJ, windows, then the layer each pixel comes from
+    pub layer: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LayerToggles {
+    pub bgs: [bool; 4],
+    pub obj: bool,
+    pub windows: bool,
+    pub color_effects: bool,
+}
+
 impl PPU {
     pub fn create_debug_windows(&self) -> DebugWindows {
-        let spec_lock = self.debug_spec.lock().unwrap();
-        let spec = spec_lock.clone();
-        drop(spec_lock);
+        let spec = self.debug_latch;
 
-        let mut debug_windows = VecDeque::with_capacity(3);
+        let mut images = VecDeque::with_capacity(5);
         // TODO: Order shouldn't be arbritrary
-        if spec.map_enable { debug_windows.push_back(self.render_map(&spec.map_spec)) }
-        if spec.tiles_enable { debug_windows.push_back(self.render_tiles(&spec.tiles_spec)) }
-        if spec.palettes_enable { debug_windows.push_back(self.render_palettes()) }
+        if spec.map_enable { images.push_back(self.render_map(&spec.map_spec)) }
+        if spec.tiles_enable { images.push_back(self.render_tiles(&spec.tiles_spec)) }
+        if spec.palettes_enable { images.push_back(self.render_palettes()) }
+        let objs = if spec.objs_enable {
+            let objs = self.parse_objs();
+            images.push_back(self.render_objs(&objs));
+            objs
+        } else { Vec::new() };
+        if spec.layers_enable { images.push_back((self.layer_frame.clone(), gba::WIDTH, gba::HEIGHT)) }
 
-        debug_windows
+        DebugWindows { images, objs }
     }
 
     fn render_map(&self, spec: &MapSpecification) -> (Vec<u16>, usize, usize) {
@@ -149,6 +207,115 @@ impl PPU {
         }
         (pixels, size, size)
     }
+
+    fn parse_objs(&self) -> Vec<OBJInfo> {
+        let affine_params: Vec<[f64; 4]> = self.oam.chunks(0x20).map(|chunk| {
+            let mut params = [0.0; 4];
+            for (i, param) in params.iter_mut().enumerate() {
+                let addr = i * 8 + 6;
+                *param = RotationScalingParameter::get_float_from_u16(u16::from_le_bytes([chunk[addr], chunk[addr + 1]]));
+            }
+            params
+        }).collect();
+        self.oam.chunks(8).map(|chunk| {
+            let attr0 = u16::from_le_bytes([chunk[0], chunk[1]]);
+            let attr1 = u16::from_le_bytes([chunk[2], chunk[3]]);
+            let attr2 = u16::from_le_bytes([chunk[4], chunk[5]]);
+            let obj_shape = (attr0 >> 14 & 0x3) as usize;
+            let obj_size = (attr1 >> 14 & 0x3) as usize;
+            let (width, height) = PPU::OBJ_SIZES[obj_size][obj_shape];
+            let affine = attr0 >> 8 & 0x1 != 0;
+            let double_size = affine && attr0 >> 9 & 0x1 != 0;
+            let obj_x = attr1 & 0x1FF;
+            let obj_y = attr0 & 0xFF;
+            let y_bounds = if double_size { height * 2 } else { height };
+            let bpp8 = attr0 >> 13 & 0x1 != 0;
+            OBJInfo {
+                x: if obj_x & 0x100 != 0 { (0xFE00 | obj_x) as i16 } else { obj_x as i16 },
+                y: if obj_y + y_bounds > 256 { obj_y as i16 - 256 } else { obj_y as i16 },
+                width,
+                height: height as i16,
+                priority: (attr2 >> 10 & 0x3) as u8,
+                palette: if bpp8 { None } else { Some((attr2 >> 12 & 0xF) as usize) },
+                tile: (attr2 & 0x3FF) as usize,
+                mode: match attr0 >> 10 & 0x3 {
+                    0 => OBJMode::Normal,
+                    1 => OBJMode::SemiTransparent,
+                    2 => OBJMode::Window,
+                    _ => OBJMode::Prohibited,
+                },
+                mosaic: attr0 >> 12 & 0x1 != 0,
+                flip_x: !affine && attr1 >> 12 & 0x1 != 0,
+                flip_y: !affine && attr1 >> 13 & 0x1 != 0,
+                affine: if affine {
+                    let param_i = (attr1 >> 9 & 0x1F) as usize;
+                    Some((param_i, affine_params[param_i]))
+                } else { None },
+                double_size,
+                disabled: !affine && attr0 >> 9 & 0x1 != 0,
+            }
+        }).collect()
+    }
+
+    // Sheet of 16x8 cells, each holding one OBJ untransformed in its top left corner
+    fn render_objs(&self, objs: &[OBJInfo]) -> (Vec<u16>, usize, usize) {
+        let (cell_size, cells_x, cells_y) = (OBJInfo::THUMBNAIL_SIZE, 16, 8);
+        let width = cell_size * cells_x;
+        let mut pixels = vec![0; width * cell_size * cells_y];
+        let tiles_1d = self.dispcnt.contains(DISPCNTFlags::OBJ_TILES1D);
+        for (i, obj) in objs.iter().enumerate() {
+            let start_i = (i / cells_x * width + i % cells_x) * cell_size;
+            let bit_depth = if obj.palette.is_some() { 4 } else { 8 };
+            let base_tile_num = if bit_depth == 8 { obj.tile / 2 } else { obj.tile };
+            for y in 0..obj.height as usize {
+                for x in 0..obj.width as usize {
+                    let tile_num = base_tile_num + if tiles_1d {
+                        (y / 8 * obj.width as usize + x) / 8
+                    } else { y / 8 * 0x80 / bit_depth + x / 8 };
+                    if 0x10000 + 8 * bit_depth * (tile_num + 1) > self.vram.len() { continue }
+                    let (palette_num, color_num) = self.get_color_from_tile(0x10000, tile_num,
+                        false, false, bit_depth, x % 8, y % 8, obj.palette.unwrap_or(0));
+                    if color_num == 0 { continue }
+                    let x = if obj.flip_x { obj.width as usize - 1 - x } else { x };
+                    let y = if obj.flip_y { obj.height as usize - 1 - y } else { y };
+                    pixels[start_i + y * width + x] = self.obj_palettes[palette_num * 16 + color_num] | 0x8000;
+                }
+            }
+        }
+        (pixels, width, cell_size * cells_y)
+    }
+}
+
+impl OBJInfo {
+    pub const THUMBNAIL_SIZE: usize = 64;
+
+    // Area on screen the OBJ can draw to, which is doubled for double size affine OBJs
+    pub fn bounds(&self) -> (i16, i16, i16, i16) {
+        let scale = if self.double_size { 2 } else { 1 };
+        (self.x, self.y, self.width * scale, self.height * scale)
+    }
+}
+
+impl PPU {
+    const WINDOW_COLORS: [u16; 3] = [0x801F, 0x83E0, 0xFC00];
+    // BG0-3, OBJ and backdrop
+    const LAYER_COLORS: [u16; 6] = [0x801F, 0x83E0, 0xFC00, 0x83FF, 0xFC1F, 0xC210];
+
+    pub(super) fn layer_pixel(&self, dot_x: usize, start_line: usize, end_line: usize, enabled: &[bool; 5],
+        windows: [bool; 3], top_layer: Layer) -> u16 {
+        let color = match self.debug_latch.layers_spec.layer {
+            // Line buffers of layers that weren't rendered are left over from earlier lines
+            bg_i @ 0..=3 if enabled[bg_i] && (start_line..=end_line).contains(&bg_i) => self.bg_lines[bg_i][dot_x],
+            4 if enabled[4] => self.objs_line[dot_x].color,
+            5 => return match windows.iter().position(|&in_window| in_window) {
+                Some(window_i) => PPU::WINDOW_COLORS[window_i],
+                None => 0,
+            },
+            6 => return PPU::LAYER_COLORS[top_layer as usize],
+            _ => PPU::TRANSPARENT_COLOR,
+        };
+        if color == PPU::TRANSPARENT_COLOR { 0 } else { color | 0x8000 }
+    }
 }
 
 impl DebugSpecification {
@@ -157,9 +324,13 @@ impl DebugSpecification {
             map_enable: false,
             tiles_enable: false,
             palettes_enable: false,
+            objs_enable: false,
+            layers_enable: false,
 
             map_spec: MapSpecification::new(),
             tiles_spec: TilesSpecification::new(),
+            layers_spec: LayersSpecification::new(),
+            layer_toggles: LayerToggles::new(),
         }
     }
 }
@@ -181,3 +352,22 @@ impl TilesSpecification {
         }
     }
 }
+
+impl LayersSpecification {
+    pub fn new() -> LayersSpecification {
+        LayersSpecification {
+            layer: 0,
+        }
+    }
+}
+
+impl LayerToggles {
+    pub fn new() -> LayerToggles {
+        LayerToggles {
+            bgs: [true; 4],
+            obj: true,
+            windows: true,
+            color_effects: true,
+        }
+    }
+}
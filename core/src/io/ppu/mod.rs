@@ -7,10 +7,11 @@ use flume::Sender;
 use crate::gba;
 use super::{Scheduler, IORegister};
 use super::interrupt_controller::InterruptRequest;
+use super::TimelineEvent;
 
 use registers::*;
 
-pub use debug::{DebugSpecification, DebugWindows};
+pub use debug::{DebugSpecification, DebugWindows, OBJInfo, OBJMode};
 
 pub struct PPU {
     // Registers
@@ -51,9 +52,12 @@ pub struct PPU {
     pub oam: Vec<u8>,
 
     // Important Rendering Variables
-    tx: Sender<DebugWindows>,
+    tx: Option<Sender<DebugWindows>>,
     pixels: Arc<Mutex<Vec<u16>>>,
     rendered_frame: bool,
+    // Frames can be skipped when running fast, but only whole ones
+    pub skip_rendering: bool,
+    rendering: bool,
     dot: u16,
     bg_lines: [[u16; gba::WIDTH]; 4],
     objs_line: [OBJPixel; gba::WIDTH],
@@ -62,15 +66,22 @@ pub struct PPU {
     // DMA
     hblank_called: bool,
     vblank_called: bool,
+    video_capture_called: bool,
+    video_capture_ended: bool,
 
     // Debug Windows
     debug_spec: Arc<Mutex<DebugSpecification>>,
+    // Taken at VBlank so that rendering doesn't lock the spec on every line
+    debug_latch: DebugSpecification,
+    layer_frame: Vec<u16>,
 }
 
 impl PPU {
     const TRANSPARENT_COLOR: u16 = 0x8000;
+    pub const HBLANK_CYCLES: usize = 68 * 4;
+    pub const VBLANK_CYCLES: usize = 68 * 308 * 4;
 
-    pub fn new(tx: Sender<DebugWindows>) -> (PPU, Arc<Mutex<Vec<u16>>>, Arc<Mutex<DebugSpecification>>) {
+    pub fn new(tx: Option<Sender<DebugWindows>>) -> (PPU, Arc<Mutex<Vec<u16>>>, Arc<Mutex<DebugSpecification>>) {
         let pixels = Arc::new(Mutex::new(vec![0; gba::WIDTH * gba::HEIGHT]));
         let display_pixels = Arc::clone(&pixels);
         let debug_spec = Arc::new(Mutex::new(DebugSpecification::new()));
@@ -116,6 +127,8 @@ impl PPU {
             tx,
             pixels,
             rendered_frame: false,
+            skip_rendering: false,
+            rendering: true,
             dot: 0,
             bg_lines: [[0; gba::WIDTH]; 4],
             objs_line: [OBJPixel::none(); gba::WIDTH],
@@ -124,9 +137,13 @@ impl PPU {
             // DMA
             hblank_called: false,
             vblank_called: false,
+            video_capture_called: false,
+            video_capture_ended: false,
 
             // Debug Windows
             debug_spec,
+            debug_latch: DebugSpecification::new(),
+            layer_frame: vec![0; gba::WIDTH * gba::HEIGHT],
         }, display_pixels, debug_windows_spec)
     }
 
@@ -143,11 +160,12 @@ impl PPU {
             if self.dot == 250 { // TODO: Take into account half
                 self.dispstat.insert(DISPSTATFlags::HBLANK);
                 if self.vcount < 160 { self.hblank_called = true } // HDMA only occurs on visible scanlines
+                if (2..162).contains(&self.vcount) { self.video_capture_called = true }
             }
         }
         if self.vcount < 160 && self.vcount != 227 { // Visible
             self.dispstat.remove(DISPSTATFlags::VBLANK);
-            if self.dot == 241 { self.render_line() }
+            if self.dot == 241 && self.rendering { self.render_line() }
         } else { // VBlank
             if self.vcount == 160 && self.dot == 0 {
                 self.vblank_called = true;
@@ -158,8 +176,13 @@ impl PPU {
             self.dispstat.insert(DISPSTATFlags::VBLANK);
         }
 
+        if self.vcount == 162 && self.dot == 0 { self.video_capture_ended = true }
+
         if self.vcount == 160 && self.dot == 0 {
-            self.tx.send(self.create_debug_windows()).unwrap();
+            if self.rendering {
+                self.debug_latch = *self.debug_spec.lock().unwrap();
+                if let Some(tx) = &self.tx { tx.send(self.create_debug_windows()).unwrap() }
+            }
             self.rendered_frame = true;
         }
 
@@ -171,6 +194,7 @@ impl PPU {
                 self.bgys_latch = self.bgys.clone();
             }
             self.vcount = (self.vcount + 1) % 228;
+            if self.vcount == 0 { self.rendering = !self.skip_rendering }
             if self.vcount == self.dispstat.vcount_setting {
                 self.dispstat.insert(DISPSTATFlags::VCOUNTER);
                 if self.dispstat.contains(DISPSTATFlags::VCOUNTER_IRQ_ENALBE) {
@@ -183,12 +207,46 @@ impl PPU {
         interrupts
     }
 
+    // How many dots from the next one only keep the status flags as the last dot left them. Nothing happens
+    // partway through the visible part of a line or HBlank, only at the start and end of each
+    pub fn idle_dots(&self) -> usize {
+        match self.dot {
+            1 ..= 239 => 240 - self.dot as usize,
+            242 ..= 249 => 250 - self.dot as usize,
+            251 ..= 306 => 307 - self.dot as usize,
+            _ => 0,
+        }
+    }
+
+    pub fn skip_dots(&mut self, dots: usize) {
+        assert!(dots <= self.idle_dots());
+        self.dot += dots as u16;
+    }
+
+    // Checked right after emulating a dot
+    pub fn blank_started(&self) -> Option<TimelineEvent> {
+        if self.dot == 241 { Some(TimelineEvent::HBlank) }
+        else if self.dot == 1 && self.vcount == 160 { Some(TimelineEvent::VBlank) }
+        else { None }
+    }
+
+    pub fn get_pixels(&self) -> Vec<u16> { self.pixels.lock().unwrap().clone() }
+
     pub fn rendered_frame(&mut self) -> bool {
         let rendered_frame = self.rendered_frame;
         self.rendered_frame = false;
         rendered_frame
     }
 
+    pub fn dma_requested(&self) -> bool {
+        self.hblank_called || self.vblank_called || self.video_capture_called || self.video_capture_ended
+    }
+
+    pub fn signal_frame(&mut self) {
+        if let Some(tx) = &self.tx { tx.send(self.create_debug_windows()).unwrap() }
+        self.rendered_frame = true;
+    }
+
     pub fn hblank_called(&mut self) -> bool {
         let hblank_called = self.hblank_called;
         self.hblank_called = false;
@@ -201,6 +259,18 @@ impl PPU {
         vblank_called
     }
 
+    pub fn video_capture_called(&mut self) -> bool {
+        let video_capture_called = self.video_capture_called;
+        self.video_capture_called = false;
+        video_capture_called
+    }
+
+    pub fn video_capture_ended(&mut self) -> bool {
+        let video_capture_ended = self.video_capture_ended;
+        self.video_capture_ended = false;
+        video_capture_ended
+    }
+
     const OBJ_SIZES: [[(i16, u16); 3]; 4] = [
         [(8, 8), (16, 8), (8, 16)],
         [(16, 16), (32, 8), (8, 32)],
@@ -301,16 +371,20 @@ impl PPU {
             if self.dispcnt.bits() & (1 << (8 + bg_i)) != 0 { bgs.push((bg_i, self.bgcnts[bg_i].priority)) }
         }
         bgs.sort_by_key(|a| a.1);
+        let toggles = self.debug_latch.layer_toggles;
         let master_enabled = [
-            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG0),
-            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG1),
-            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG2),
-            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG3),
-            self.dispcnt.contains(DISPCNTFlags::DISPLAY_OBJ),
+            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG0) && toggles.bgs[0],
+            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG1) && toggles.bgs[1],
+            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG2) && toggles.bgs[2],
+            self.dispcnt.contains(DISPCNTFlags::DISPLAY_BG3) && toggles.bgs[3],
+            self.dispcnt.contains(DISPCNTFlags::DISPLAY_OBJ) && toggles.obj,
         ];
         let mut pixels = self.pixels.lock().unwrap();
         for dot_x in 0..gba::WIDTH {
-            let window_control = if self.windows_lines[0][dot_x] {
+            let windows = [self.windows_lines[0][dot_x], self.windows_lines[1][dot_x], self.windows_lines[2][dot_x]];
+            let window_control = if !toggles.windows {
+                WindowControl::all()
+            } else if self.windows_lines[0][dot_x] {
                 self.win_0_cnt
             } else if self.windows_lines[1][dot_x] {
                 self.win_1_cnt
@@ -364,7 +438,7 @@ impl PPU {
             let trans_obj = layers[0] == Layer::OBJ && self.objs_line[dot_x].semitransparent;
             let target1_enabled = self.bldcnt.target_pixel1.enabled[layers[0] as usize] || trans_obj;
             let target2_enabled = self.bldcnt.target_pixel2.enabled[layers[1] as usize];
-            let final_color = if window_control.color_special_enable && target1_enabled {
+            let final_color = if toggles.color_effects && window_control.color_special_enable && target1_enabled {
                 let effect = if trans_obj && target2_enabled { ColorSFX::AlphaBlend } else { self.bldcnt.effect };
                 match effect {
                     ColorSFX::None => colors[0],
@@ -402,6 +476,10 @@ impl PPU {
                 }
             } else { colors[0] };
             pixels[start_index + dot_x] = final_color;
+            if self.debug_latch.layers_enable {
+                self.layer_frame[start_index + dot_x] = self.layer_pixel(dot_x, start_line, end_line,
+                    &master_enabled, windows, layers[0]);
+            }
         }
     }
 
@@ -777,6 +855,26 @@ impl PPU {
         }
     }
 
+    // Like read_register, but with what was written to write only registers
+    pub fn inspect_register(&self, addr: u32) -> u8 {
+        assert_eq!(addr >> 12, 0x04000);
+        match addr & 0xFFF {
+            0x020 ..= 0x03F => {
+                let (i, byte) = ((addr as usize & 0xFFF) / 0x10 - 2, addr as u8 & 0x1);
+                match addr & 0xF {
+                    0x0 | 0x1 => self.dxs[i].inspect(byte),
+                    0x2 | 0x3 => self.dmxs[i].inspect(byte),
+                    0x4 | 0x5 => self.dys[i].inspect(byte),
+                    0x6 | 0x7 => self.dmys[i].inspect(byte),
+                    0x8 ..= 0xB => self.bgxs[i].inspect(addr as u8 & 0x3),
+                    _ => self.bgys[i].inspect(addr as u8 & 0x3),
+                }
+            },
+            0x054 => self.bldy.evy,
+            _ => self.read_register(addr),
+        }
+    }
+
     pub fn write_register(&mut self, scheduler: &mut Scheduler, addr: u32, value: u8) {
         assert_eq!(addr >> 12, 0x04000);
         match addr & 0xFFF {
@@ -899,3 +997,12 @@ impl PPU {
         addr & 0x3FF
     }
 }
+
+impl_save_state!(PPU {
+    dispcnt, green_swap, dispstat, vcount,
+    bgcnts, hofs, vofs, dxs, dmxs, dys, dmys, bgxs, bgys, bgxs_latch, bgys_latch, mosaic,
+    winhs, winvs, win_0_cnt, win_1_cnt, win_out_cnt, win_obj_cnt,
+    bldcnt, bldalpha, bldy,
+    bg_palettes, obj_palettes, vram, oam,
+    rendered_frame, dot, hblank_called, vblank_called, video_capture_called, video_capture_ended,
+} where |ppu| ppu.vram.len() == 0x18000 && ppu.oam.len() == 0x400 && ppu.vcount < 228 && ppu.dot < 308);
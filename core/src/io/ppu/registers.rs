@@ -257,6 +257,9 @@ impl RotationScalingParameter {
     pub fn get_float_from_u16(value: u16) -> f64 {
         (value >> 8) as i8 as i32 as f64 + (value >> 0) as u8 as f64 / 256.0
     }
+
+    // What was written, which can't be read back
+    pub fn inspect(&self, byte: u8) -> u8 { (self.value as u16 >> (8 * byte)) as u8 }
 }
 
 impl IORegister for RotationScalingParameter {
@@ -286,6 +289,9 @@ impl ReferencePointCoord {
     pub fn integer(&self) -> i32 {
         self.value >> 8
     }
+
+    // What was written without the sign extension, which can't be read back
+    pub fn inspect(&self, byte: u8) -> u8 { ((self.value as u32 & 0x0FFF_FFFF) >> (8 * byte)) as u8 }
 }
 
 impl IORegister for ReferencePointCoord {
@@ -611,3 +617,23 @@ impl IORegister for BLDY {
         }
     }
 }
+
+impl_save_state_bitflags!(DISPCNTFlags, DISPSTATFlags);
+impl_save_state_enum!(BGMode { Mode0, Mode1, Mode2, Mode3, Mode4, Mode5 });
+impl_save_state_enum!(ColorSFX { None, AlphaBlend, BrightnessInc, BrightnessDec });
+impl_save_state!(DISPCNT { flags, mode });
+impl_save_state!(DISPSTAT { flags, vcount_setting });
+impl_save_state!(BGCNT { priority, tile_block, mosaic, bpp8, map_block, wrap, screen_size } where |bgcnt| bgcnt.screen_size < 4);
+impl_save_state!(OFS { offset });
+impl_save_state!(RotationScalingParameter { value });
+impl_save_state!(ReferencePointCoord { value });
+impl_save_state!(WindowDimensions { coord2, coord1 });
+impl_save_state!(WindowControl {
+    bg0_enable, bg1_enable, bg2_enable, bg3_enable, obj_enable, color_special_enable,
+});
+impl_save_state!(MosaicSize { h_size, v_size });
+impl_save_state!(MOSAIC { bg_size, obj_size });
+impl_save_state!(BLDCNTTargetPixelSelection { enabled });
+impl_save_state!(BLDCNT { target_pixel1, effect, target_pixel2 });
+impl_save_state!(BLDALPHA { raw_eva, raw_evb, eva, evb });
+impl_save_state!(BLDY { evy });
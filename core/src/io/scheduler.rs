@@ -2,8 +2,10 @@ use std::cmp::Reverse;
 
 use priority_queue::PriorityQueue;
 
-use super::IO;
+use super::{IO, InterruptRequest, TimelineEvent};
+use super::serial::Serial;
 use crate::gba;
+use crate::savestate::{SaveState, StateReader, StateError};
 
 impl IO {
     pub fn handle_events(&mut self) {
@@ -13,9 +15,17 @@ impl IO {
         }
     }
 
+    // Moves time forward without anything happening, as when the system clock is stopped
+    pub fn skip_cycles(&mut self, cycles: usize) {
+        self.scheduler.skip(cycles);
+        self.timers.skip(cycles);
+    }
+
     pub fn handle_event(&mut self, event: EventType) {
         match event {
             EventType::TimerOverflow(timer) => {
+                let cycle = self.scheduler.cycle;
+                self.record_timeline(TimelineEvent::TimerOverflow(timer), cycle, cycle);
                 if self.timers.timers[timer].cnt.irq {
                     self.interrupt_controller.request |= self.timers.timers[timer].interrupt
                 }
@@ -37,6 +47,28 @@ impl IO {
                     event_type: EventType::FrameSequencer((step + 1) % 8),
                 });
             },
+            EventType::SerialTransfer => {
+                if self.serial.transfer_complete() { self.interrupt_controller.request |= InterruptRequest::SERIAL }
+            },
+            EventType::SerialPoll => {
+                if self.serial.poll_link() { self.interrupt_controller.request |= InterruptRequest::SERIAL }
+                self.scheduler.add(Event {
+                    cycle: self.scheduler.cycle + Serial::POLL_INTERVAL,
+                    event_type: EventType::SerialPoll,
+                });
+            },
+        }
+    }
+
+    // The link is only polled while something is connected
+    pub fn schedule_serial_poll(&mut self) {
+        if !self.serial.linked() {
+            self.scheduler.remove(EventType::SerialPoll)
+        } else if !self.scheduler.is_scheduled(EventType::SerialPoll) {
+            self.scheduler.add(Event {
+                cycle: self.scheduler.cycle + Serial::POLL_INTERVAL,
+                event_type: EventType::SerialPoll,
+            });
         }
     }
 }
@@ -64,6 +96,18 @@ impl Scheduler {
         } else { None }
     }
 
+    pub fn next_event_cycle(&self) -> usize {
+        let (_event_type, Reverse(cycle)) = self.event_queue.peek().unwrap();
+        *cycle
+    }
+
+    // Moves time forward without firing anything, keeping pending events the same distance away. Timers count from
+    // a start cycle, so use IO::skip_cycles to keep them in step
+    pub fn skip(&mut self, cycles: usize) {
+        self.cycle += cycles;
+        for (_event_type, Reverse(cycle)) in self.event_queue.iter_mut() { *cycle += cycles }
+    }
+
     pub fn add(&mut self, event: Event) {
         self.event_queue.push(event.event_type, Reverse(event.cycle));
     }
@@ -71,6 +115,8 @@ impl Scheduler {
     pub fn remove(&mut self, event_type: EventType) {
         self.event_queue.remove(&event_type);
     }
+
+    pub fn is_scheduled(&self, event_type: EventType) -> bool { self.event_queue.get(&event_type).is_some() }
 }
 
 pub struct Event {
@@ -82,4 +128,54 @@ pub struct Event {
 pub enum EventType {
     TimerOverflow(usize),
     FrameSequencer(usize),
+    SerialTransfer,
+    SerialPoll,
+}
+
+impl SaveState for Scheduler {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        self.cycle.save_state(state);
+        self.event_queue.len().save_state(state);
+        for (event_type, Reverse(cycle)) in self.event_queue.iter() {
+            event_type.save_state(state);
+            cycle.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.cycle.load_state(state)?;
+        self.event_queue.clear();
+        for _ in 0..state.read::<usize>()? {
+            let mut event_type = EventType::FrameSequencer(0);
+            event_type.load_state(state)?;
+            self.event_queue.push(event_type, Reverse(state.read()?));
+        }
+        Ok(())
+    }
+}
+
+impl SaveState for EventType {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        let (tag, value) = match *self {
+            EventType::TimerOverflow(timer) => (0u8, timer),
+            EventType::FrameSequencer(step) => (1u8, step),
+            EventType::SerialTransfer => (2u8, 0),
+            EventType::SerialPoll => (3u8, 0),
+        };
+        tag.save_state(state);
+        value.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let tag = state.read::<u8>()?;
+        let value = state.read::<usize>()?;
+        *self = match tag {
+            0 if value < 4 => EventType::TimerOverflow(value),
+            1 if value < 8 => EventType::FrameSequencer(value),
+            2 => EventType::SerialTransfer,
+            3 => EventType::SerialPoll,
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
 }
@@ -0,0 +1,154 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use flume::{Receiver, Sender};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkMessage {
+    // Sent whenever a unit's outgoing data changes, so transfers never have to wait on the other side
+    Data { id: usize, value: u32 },
+    // Data shifted out by the master of a normal mode transfer
+    Normal(u32),
+    // Values every unit receives at the end of a multiplayer transfer
+    Multiplayer([u16; 4]),
+    Uart(u8),
+}
+
+impl LinkMessage {
+    const LEN: usize = 9;
+
+    fn to_bytes(self) -> [u8; LinkMessage::LEN] {
+        let mut bytes = [0; LinkMessage::LEN];
+        match self {
+            LinkMessage::Data { id, value } => {
+                bytes[0] = 0;
+                bytes[1..5].copy_from_slice(&value.to_le_bytes());
+                bytes[5] = id as u8;
+            },
+            LinkMessage::Normal(value) => {
+                bytes[0] = 1;
+                bytes[1..5].copy_from_slice(&value.to_le_bytes());
+            },
+            LinkMessage::Multiplayer(values) => {
+                bytes[0] = 2;
+                for (i, value) in values.iter().enumerate() {
+                    bytes[1 + i * 2..3 + i * 2].copy_from_slice(&value.to_le_bytes());
+                }
+            },
+            LinkMessage::Uart(value) => {
+                bytes[0] = 3;
+                bytes[1] = value;
+            },
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; LinkMessage::LEN]) -> Option<LinkMessage> {
+        let word = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        match bytes[0] {
+            0 => Some(LinkMessage::Data { id: bytes[5] as usize, value: word }),
+            1 => Some(LinkMessage::Normal(word)),
+            2 => {
+                let mut values = [0; 4];
+                for (i, value) in values.iter_mut().enumerate() {
+                    *value = u16::from_le_bytes([bytes[1 + i * 2], bytes[2 + i * 2]]);
+                }
+                Some(LinkMessage::Multiplayer(values))
+            },
+            3 => Some(LinkMessage::Uart(bytes[1])),
+            _ => None,
+        }
+    }
+}
+
+pub trait LinkTransport: Send {
+    // Unit 0 is the parent in multiplayer mode
+    fn id(&self) -> usize;
+    fn units(&self) -> usize;
+    fn send(&mut self, message: LinkMessage);
+    // Must not block
+    fn receive(&mut self) -> Option<LinkMessage>;
+}
+
+// Connects GBAs running in the same process
+pub struct LocalLink {
+    id: usize,
+    peers: Vec<Sender<LinkMessage>>,
+    rx: Receiver<LinkMessage>,
+}
+
+impl LocalLink {
+    pub fn new(units: usize) -> Vec<LocalLink> {
+        assert!((2..=4).contains(&units));
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..units).map(|_| flume::unbounded()).unzip();
+        rxs.into_iter().enumerate().map(|(id, rx)| LocalLink {
+            id,
+            peers: txs.iter().enumerate().filter(|(peer_id, _)| *peer_id != id).map(|(_, tx)| tx.clone()).collect(),
+            rx,
+        }).collect()
+    }
+}
+
+impl LinkTransport for LocalLink {
+    fn id(&self) -> usize { self.id }
+
+    fn units(&self) -> usize { self.peers.len() + 1 }
+
+    fn send(&mut self, message: LinkMessage) {
+        // A peer that was dropped just stops receiving
+        for peer in self.peers.iter() { peer.send(message).ok(); }
+    }
+
+    fn receive(&mut self) -> Option<LinkMessage> { self.rx.try_recv().ok() }
+}
+
+// Connects two emulator instances, the host being the parent
+pub struct TcpLink {
+    id: usize,
+    stream: TcpStream,
+    rx: Receiver<LinkMessage>,
+}
+
+impl TcpLink {
+    // Blocks until the other emulator connects
+    pub fn host<A: ToSocketAddrs>(addr: A) -> io::Result<TcpLink> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        TcpLink::new(0, stream)
+    }
+
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpLink> {
+        TcpLink::new(1, TcpStream::connect(addr)?)
+    }
+
+    fn new(id: usize, stream: TcpStream) -> io::Result<TcpLink> {
+        stream.set_nodelay(true)?;
+        let mut reader = stream.try_clone()?;
+        let (tx, rx) = flume::unbounded();
+        thread::spawn(move || {
+            let mut bytes = [0; LinkMessage::LEN];
+            while reader.read_exact(&mut bytes).is_ok() {
+                match LinkMessage::from_bytes(&bytes) {
+                    Some(message) => if tx.send(message).is_err() { break },
+                    None => warn!("Received invalid link message: {:?}", bytes),
+                }
+            }
+        });
+        Ok(TcpLink {
+            id,
+            stream,
+            rx,
+        })
+    }
+}
+
+impl LinkTransport for TcpLink {
+    fn id(&self) -> usize { self.id }
+
+    fn units(&self) -> usize { 2 }
+
+    fn send(&mut self, message: LinkMessage) {
+        if let Err(err) = self.stream.write_all(&message.to_bytes()) { warn!("Unable to send link message: {}", err) }
+    }
+
+    fn receive(&mut self) -> Option<LinkMessage> { self.rx.try_recv().ok() }
+}
@@ -0,0 +1,328 @@
+mod link;
+
+use super::{Event, EventType, Scheduler};
+use crate::gba;
+pub use link::{LinkMessage, LinkTransport, LocalLink, TcpLink};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Normal8,
+    Normal32,
+    Multiplayer,
+    Uart,
+    GeneralPurpose,
+    JoyBus,
+}
+
+pub struct Serial {
+    data: [u16; 4],
+    cnt: u16,
+    send: u16,
+    rcnt: u16,
+    // Latest outgoing data of every unit on the link
+    peer_data: [u32; 4],
+    transfer_start: usize,
+    uart_tx: u8,
+    uart_tx_pending: bool,
+    uart_rx: [u8; 4],
+    uart_rx_len: usize,
+    uart_error: bool,
+    link: Option<Box<dyn LinkTransport>>,
+}
+
+impl Serial {
+    pub const POLL_INTERVAL: usize = 1024;
+
+    // SIOCNT
+    const INTERNAL_CLOCK: u16 = 1 << 0;
+    const FAST_CLOCK: u16 = 1 << 1;
+    const START: u16 = 1 << 7;
+    const IRQ_ENABLE: u16 = 1 << 14;
+    const UART_PARITY: u16 = 1 << 9;
+    const UART_FIFO: u16 = 1 << 8;
+    const UART_SEND_ENABLE: u16 = 1 << 10;
+    const UART_RECEIVE_ENABLE: u16 = 1 << 11;
+
+    const BAUD_RATES: [usize; 4] = [9600, 38400, 57600, 115200];
+    // Measured on hardware, indexed by baud rate and number of connected units
+    const MULTIPLAYER_CYCLES: [[usize; 4]; 4] = [
+        [38326, 73003, 107680, 142356],
+        [9582, 18251, 26920, 35589],
+        [6388, 12167, 17946, 23725],
+        [3194, 6075, 8973, 11863],
+    ];
+
+    pub fn new() -> Serial {
+        Serial {
+            data: [0; 4],
+            cnt: 0,
+            send: 0,
+            rcnt: 0,
+            peer_data: [0xFFFF_FFFF; 4],
+            transfer_start: 0,
+            uart_tx: 0,
+            uart_tx_pending: false,
+            uart_rx: [0; 4],
+            uart_rx_len: 0,
+            uart_error: false,
+            link: None,
+        }
+    }
+
+    pub fn set_link(&mut self, link: Box<dyn LinkTransport>) {
+        self.link = Some(link);
+        self.publish();
+    }
+
+    pub fn linked(&self) -> bool { self.link.is_some() }
+
+    fn mode(&self) -> Mode {
+        if self.rcnt & 0x8000 != 0 {
+            if self.rcnt & 0x4000 != 0 { Mode::JoyBus } else { Mode::GeneralPurpose }
+        } else {
+            match self.cnt >> 12 & 0x3 {
+                0 => Mode::Normal8,
+                1 => Mode::Normal32,
+                2 => Mode::Multiplayer,
+                3 => Mode::Uart,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn id(&self) -> usize { self.link.as_ref().map_or(0, |link| link.id()) }
+
+    fn units(&self) -> usize { self.link.as_ref().map_or(1, |link| link.units()) }
+
+    fn busy(&self) -> bool { self.cnt & Serial::START != 0 }
+
+    // Whether this unit clocks the transfer rather than waiting for another unit to
+    fn drives_transfer(&self) -> bool {
+        match self.mode() {
+            Mode::Normal8 | Mode::Normal32 => self.cnt & Serial::INTERNAL_CLOCK != 0,
+            Mode::Multiplayer => self.id() == 0,
+            _ => false,
+        }
+    }
+
+    fn outgoing(&self) -> u32 {
+        match self.mode() {
+            Mode::Normal8 => self.send as u32 & 0xFF,
+            Mode::Normal32 => self.data[0] as u32 | (self.data[1] as u32) << 16,
+            _ => self.send as u32,
+        }
+    }
+
+    fn publish(&mut self) {
+        let (id, value) = (self.id(), self.outgoing());
+        if let Some(link) = &mut self.link { link.send(LinkMessage::Data { id, value }) }
+    }
+
+    fn send_message(&mut self, message: LinkMessage) {
+        if let Some(link) = &mut self.link { link.send(message) }
+    }
+
+    fn transfer_cycles(&self) -> usize {
+        let baud_rate = Serial::BAUD_RATES[self.cnt as usize & 0x3];
+        match self.mode() {
+            Mode::Normal8 | Mode::Normal32 => {
+                let bits = if self.mode() == Mode::Normal8 { 8 } else { 32 };
+                bits * if self.cnt & Serial::FAST_CLOCK != 0 { 8 } else { 64 }
+            },
+            Mode::Multiplayer => Serial::MULTIPLAYER_CYCLES[self.cnt as usize & 0x3][self.units() - 1],
+            // Start bit, 8 data bits, optional parity bit and stop bit
+            Mode::Uart => gba::CLOCK_FREQ / baud_rate * (10 + (self.cnt & Serial::UART_PARITY != 0) as usize),
+            Mode::GeneralPurpose | Mode::JoyBus => 0,
+        }
+    }
+
+    fn schedule_transfer(&self, scheduler: &mut Scheduler) {
+        scheduler.add(Event {
+            cycle: self.transfer_start + self.transfer_cycles(),
+            event_type: EventType::SerialTransfer,
+        });
+    }
+
+    fn set_received(&mut self, value: u32) {
+        match self.mode() {
+            Mode::Normal8 => self.send = value as u16 & 0xFF,
+            Mode::Normal32 => self.data = [value as u16, (value >> 16) as u16, self.data[2], self.data[3]],
+            _ => unreachable!(),
+        }
+    }
+
+    // Returns whether a serial interrupt should be requested
+    pub fn transfer_complete(&mut self) -> bool {
+        match self.mode() {
+            Mode::Normal8 | Mode::Normal32 if self.busy() && self.drives_transfer() => {
+                // Nothing connected leaves the line pulled high
+                let peer_id = (0..self.units()).find(|id| *id != self.id());
+                let received = peer_id.map_or(0xFFFF_FFFF, |id| self.peer_data[id]);
+                self.send_message(LinkMessage::Normal(self.outgoing()));
+                self.set_received(received);
+                self.publish();
+            },
+            Mode::Multiplayer if self.busy() && self.drives_transfer() => {
+                let mut values = [0xFFFF; 4];
+                values[0] = self.send;
+                for (id, value) in values.iter_mut().enumerate().take(self.units()).skip(1) {
+                    *value = self.peer_data[id] as u16;
+                }
+                self.data = values;
+                self.send_message(LinkMessage::Multiplayer(values));
+            },
+            Mode::Uart if self.uart_tx_pending => {
+                self.uart_tx_pending = false;
+                self.send_message(LinkMessage::Uart(self.uart_tx));
+                return self.cnt & Serial::IRQ_ENABLE != 0
+            },
+            _ => return false,
+        }
+        self.cnt &= !Serial::START;
+        self.cnt & Serial::IRQ_ENABLE != 0
+    }
+
+    // Returns whether a serial interrupt should be requested
+    pub fn poll_link(&mut self) -> bool {
+        let messages: Vec<LinkMessage> = match &mut self.link {
+            Some(link) => std::iter::from_fn(|| link.receive()).collect(),
+            None => return false,
+        };
+        let mut interrupt = false;
+        for message in messages {
+            interrupt |= self.handle_message(message);
+        }
+        interrupt
+    }
+
+    fn handle_message(&mut self, message: LinkMessage) -> bool {
+        match message {
+            LinkMessage::Data { id, value } => {
+                if id < self.peer_data.len() { self.peer_data[id] = value }
+                return false
+            },
+            LinkMessage::Normal(value) => {
+                let normal_mode = self.mode() == Mode::Normal8 || self.mode() == Mode::Normal32;
+                if !normal_mode || !self.busy() || self.drives_transfer() { return false }
+                self.set_received(value);
+                self.publish();
+            },
+            LinkMessage::Multiplayer(values) => {
+                if self.mode() != Mode::Multiplayer || self.drives_transfer() { return false }
+                self.data = values;
+            },
+            LinkMessage::Uart(value) => {
+                if self.mode() != Mode::Uart || self.cnt & Serial::UART_RECEIVE_ENABLE == 0 { return false }
+                let capacity = if self.cnt & Serial::UART_FIFO != 0 { 4 } else { 1 };
+                if self.uart_rx_len < capacity {
+                    self.uart_rx[self.uart_rx_len] = value;
+                    self.uart_rx_len += 1;
+                } else { self.uart_error = true }
+                return self.cnt & Serial::IRQ_ENABLE != 0
+            },
+        }
+        self.cnt &= !Serial::START;
+        self.cnt & Serial::IRQ_ENABLE != 0
+    }
+
+    fn read_cnt(&self) -> u16 {
+        match self.mode() {
+            Mode::Multiplayer => {
+                let all_ready = self.units() > 1;
+                self.cnt & !0x7C | ((self.id() != 0) as u16) << 2 | (all_ready as u16) << 3 | (self.id() as u16) << 4
+            },
+            Mode::Uart => {
+                self.cnt & !0x70 | (self.uart_tx_pending as u16) << 4 | ((self.uart_rx_len == 0) as u16) << 5 |
+                (self.uart_error as u16) << 6
+            },
+            _ => self.cnt & !0x4,
+        }
+    }
+
+    fn read_uart_data(&self) -> u8 { if self.uart_rx_len == 0 { 0 } else { self.uart_rx[0] } }
+
+    // Reading SIODATA8 in UART mode takes the byte out of the receive FIFO
+    pub fn pop_uart_data(&mut self) {
+        if self.mode() != Mode::Uart || self.uart_rx_len == 0 { return }
+        self.uart_rx = [self.uart_rx[1], self.uart_rx[2], self.uart_rx[3], 0];
+        self.uart_rx_len -= 1;
+    }
+
+    fn write_cnt(&mut self, scheduler: &mut Scheduler, byte: u8, value: u8) {
+        let was_busy = self.busy();
+        let read_only = match self.mode() {
+            Mode::Multiplayer => 0x7C,
+            Mode::Uart => 0x70,
+            _ => 0x04,
+        };
+        let (mask, value) = if byte == 0 { (0x00FF, value as u16) } else { (0xFF00, (value as u16) << 8) };
+        self.cnt = self.cnt & !(mask & !read_only) | value & mask & !read_only & 0x7FFF;
+        if self.mode() == Mode::Uart {
+            if self.cnt & Serial::UART_SEND_ENABLE == 0 { self.uart_tx_pending = false }
+            if self.cnt & Serial::UART_FIFO == 0 && self.uart_rx_len > 1 { self.uart_rx_len = 1 }
+            return
+        }
+
+        if self.busy() {
+            if !was_busy { self.transfer_start = scheduler.cycle }
+            // Writing SIOCNT as a halfword can change the mode after the start bit
+            if self.drives_transfer() { self.schedule_transfer(scheduler) }
+        } else if was_busy { scheduler.remove(EventType::SerialTransfer) }
+        self.publish();
+    }
+
+    pub fn read_register(&self, addr: u32) -> u8 {
+        assert_eq!(addr >> 12, 0x04000);
+        match addr & 0xFFF {
+            0x120 ..= 0x127 => (self.data[(addr as usize & 0x7) / 2] >> (8 * (addr & 0x1))) as u8,
+            0x128 => self.read_cnt() as u8,
+            0x129 => (self.read_cnt() >> 8) as u8,
+            0x12A if self.mode() == Mode::Uart => self.read_uart_data(),
+            0x12A => self.send as u8,
+            0x12B => (self.send >> 8) as u8,
+            0x12C ..= 0x12F => 0,
+            0x134 => self.rcnt as u8,
+            0x135 => (self.rcnt >> 8) as u8,
+            _ => unreachable!(),
+        }
+    }
+
+    // Same as a write, except that it can't start a transfer
+    pub fn poke_register(&mut self, scheduler: &mut Scheduler, addr: u32, value: u8) {
+        match addr & 0xFFF {
+            0x128 => self.write_cnt(scheduler, 0, value & !Serial::START as u8 | self.cnt as u8 & Serial::START as u8),
+            0x12A if self.mode() == Mode::Uart => (),
+            _ => self.write_register(scheduler, addr, value),
+        }
+    }
+
+    pub fn write_register(&mut self, scheduler: &mut Scheduler, addr: u32, value: u8) {
+        assert_eq!(addr >> 12, 0x04000);
+        match addr & 0xFFF {
+            0x120 ..= 0x127 => {
+                let (i, shift) = ((addr as usize & 0x7) / 2, 8 * (addr & 0x1));
+                self.data[i] = self.data[i] & !(0xFF << shift) | (value as u16) << shift;
+                self.publish();
+            },
+            0x128 => self.write_cnt(scheduler, 0, value),
+            0x129 => self.write_cnt(scheduler, 1, value),
+            0x12A if self.mode() == Mode::Uart => {
+                if self.cnt & Serial::UART_SEND_ENABLE == 0 { return }
+                self.uart_tx = value;
+                self.uart_tx_pending = true;
+                self.transfer_start = scheduler.cycle;
+                self.schedule_transfer(scheduler);
+            },
+            0x12A => { self.send = self.send & !0x00FF | value as u16; self.publish() },
+            0x12B => { self.send = self.send & !0xFF00 | (value as u16) << 8; self.publish() },
+            0x12C ..= 0x12F => (),
+            0x134 => self.rcnt = self.rcnt & !0x00FF | value as u16,
+            0x135 => self.rcnt = self.rcnt & !0xFF00 | (value as u16 & 0xC1) << 8,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl_save_state!(Serial {
+    data, cnt, send, rcnt, peer_data, transfer_start, uart_tx, uart_tx_pending, uart_rx, uart_rx_len, uart_error,
+} where |serial| serial.uart_rx_len <= 4);
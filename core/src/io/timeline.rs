@@ -0,0 +1,54 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimelineEvent {
+    HBlank,
+    VBlank,
+    TimerOverflow(usize),
+    DMA(usize),
+    // Interrupts that were both enabled and requested when the CPU took the IRQ
+    IRQ(u16),
+    // Halted or stopped, waiting for an interrupt
+    Halt,
+}
+
+// Cycles are relative to the start of the frame, and start and end are equal for instant events
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimelineEntry {
+    pub event: TimelineEvent,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct Timeline {
+    entries: Vec<TimelineEntry>,
+}
+
+impl Timeline {
+    pub fn new() -> Timeline {
+        Timeline {
+            entries: Vec::new(),
+        }
+    }
+
+    // Back to back spans of the same event are merged, since halting is run in pieces
+    pub fn record(&mut self, event: TimelineEvent, start: usize, end: usize) {
+        if start != end {
+            let last = self.entries.iter_mut().rev().find(|entry| entry.event == event);
+            if let Some(last) = last {
+                if last.end == start {
+                    last.end = end;
+                    return
+                }
+            }
+        }
+        self.entries.push(TimelineEntry { event, start, end });
+    }
+
+    // Everything recorded since the last call, relative to frame_start
+    pub fn take_frame(&mut self, frame_start: usize) -> Vec<TimelineEntry> {
+        self.entries.drain(..).map(|entry| TimelineEntry {
+            start: entry.start.saturating_sub(frame_start),
+            end: entry.end.saturating_sub(frame_start),
+            ..entry
+        }).collect()
+    }
+}
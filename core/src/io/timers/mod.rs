@@ -22,6 +22,20 @@ impl Timers {
             ],
         }
     }
+
+    // Freezes the counters of running timers while time is skipped
+    pub fn skip(&mut self, cycles: usize) {
+        for timer in self.timers.iter_mut() { timer.start_cycle += cycles }
+    }
+
+    // Whether the running timers' counters can be worked out at the given cycle, for checking loaded states
+    pub fn valid_at(&self, global_cycle: usize) -> bool {
+        self.timers.iter().filter(|timer| timer.cnt.start && !timer.is_count_up()).all(|timer|
+            timer.start_cycle <= global_cycle + 1 &&
+            global_cycle - timer.start_cycle.min(global_cycle) <
+                timer.time_till_first_clock + Timers::PRESCALERS[timer.cnt.prescaler as usize] * 0x1_0000
+        )
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -138,3 +152,6 @@ impl Timer {
         }
     }
 }
+
+impl_save_state!(Timers { timers });
+impl_save_state!(Timer { reload, cnt, counter, start_cycle, time_till_first_clock, timer_len });
@@ -41,3 +41,5 @@ impl TMCNT {
         }
     }
 }
+
+impl_save_state!(TMCNT { prescaler, count_up, irq, start } where |cnt| cnt.prescaler < 4);
@@ -4,6 +4,7 @@ extern crate num_traits as num;
 pub extern crate flume;
 pub extern crate simplelog;
 
+#[macro_use] mod savestate;
 mod cpu;
 mod io;
 
@@ -0,0 +1,219 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::fmt;
+
+pub trait SaveState {
+    fn save_state(&self, state: &mut Vec<u8>);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError>;
+}
+
+// Serializes the listed fields in order. The optional check runs once they're loaded, so that lengths and indices
+// the emulator relies on can't come from a corrupt state
+macro_rules! impl_save_state {
+    ($type:ty { $($field:ident),* $(,)? } $(where |$value:ident| $valid:expr)?) => {
+        impl crate::savestate::SaveState for $type {
+            fn save_state(&self, _state: &mut Vec<u8>) {
+                $( crate::savestate::SaveState::save_state(&self.$field, _state); )*
+            }
+
+            fn load_state(&mut self, _state: &mut crate::savestate::StateReader) ->
+                Result<(), crate::savestate::StateError> {
+                $( crate::savestate::SaveState::load_state(&mut self.$field, _state)?; )*
+                $( let $value = &*self; if !$valid { return Err(crate::savestate::StateError::InvalidValue) } )?
+                Ok(())
+            }
+        }
+    };
+}
+
+// Must be invoked in the module that defines the flags so the bits field is accessible
+macro_rules! impl_save_state_bitflags {
+    ($($type:ty),* $(,)?) => { $(
+        impl crate::savestate::SaveState for $type {
+            fn save_state(&self, state: &mut Vec<u8>) { crate::savestate::SaveState::save_state(&self.bits, state) }
+
+            fn load_state(&mut self, state: &mut crate::savestate::StateReader) ->
+                Result<(), crate::savestate::StateError> {
+                crate::savestate::SaveState::load_state(&mut self.bits, state)
+            }
+        }
+    )* };
+}
+
+// Fieldless enums are stored as their discriminant
+macro_rules! impl_save_state_enum {
+    ($type:ident { $($variant:ident),* $(,)? }) => {
+        impl crate::savestate::SaveState for $type {
+            fn save_state(&self, state: &mut Vec<u8>) { crate::savestate::SaveState::save_state(&(*self as u8), state) }
+
+            fn load_state(&mut self, state: &mut crate::savestate::StateReader) ->
+                Result<(), crate::savestate::StateError> {
+                let value = state.read::<u8>()?;
+                $( if value == $type::$variant as u8 { *self = $type::$variant; return Ok(()) } )*
+                Err(crate::savestate::StateError::InvalidValue)
+            }
+        }
+    };
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader {
+            data,
+            pos: 0,
+        }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() - self.pos < len { return Err(StateError::UnexpectedEnd) }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn read<T: SaveState + Default>(&mut self) -> Result<T, StateError> {
+        let mut value = T::default();
+        value.load_state(self)?;
+        Ok(value)
+    }
+
+    pub fn is_empty(&self) -> bool { self.pos == self.data.len() }
+}
+
+macro_rules! impl_save_state_num {
+    ($($type:ty),*) => { $(
+        impl SaveState for $type {
+            fn save_state(&self, state: &mut Vec<u8>) { state.extend_from_slice(&self.to_le_bytes()) }
+
+            fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+                let mut bytes = [0; std::mem::size_of::<$type>()];
+                bytes.copy_from_slice(state.read_bytes(std::mem::size_of::<$type>())?);
+                *self = <$type>::from_le_bytes(bytes);
+                Ok(())
+            }
+        }
+    )* };
+}
+
+impl_save_state_num!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+// Stored as u64 so states are portable between 32 and 64 bit hosts
+impl SaveState for usize {
+    fn save_state(&self, state: &mut Vec<u8>) { (*self as u64).save_state(state) }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        *self = state.read::<u64>()? as usize;
+        Ok(())
+    }
+}
+
+impl SaveState for bool {
+    fn save_state(&self, state: &mut Vec<u8>) { (*self as u8).save_state(state) }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        *self = match state.read::<u8>()? {
+            0 => false,
+            1 => true,
+            _ => return Err(StateError::InvalidValue),
+        };
+        Ok(())
+    }
+}
+
+impl SaveState for char {
+    fn save_state(&self, state: &mut Vec<u8>) { (*self as u32).save_state(state) }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        *self = std::char::from_u32(state.read::<u32>()?).ok_or(StateError::InvalidValue)?;
+        Ok(())
+    }
+}
+
+impl<T: SaveState, const N: usize> SaveState for [T; N] {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        for value in self.iter() { value.save_state(state) }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for value in self.iter_mut() { value.load_state(state)? }
+        Ok(())
+    }
+}
+
+impl SaveState for Vec<u8> {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        self.len().save_state(state);
+        state.extend_from_slice(self);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let len = state.read::<usize>()?;
+        self.clear();
+        self.extend_from_slice(state.read_bytes(len)?);
+        Ok(())
+    }
+}
+
+impl<T: SaveState + Default> SaveState for VecDeque<T> {
+    fn save_state(&self, state: &mut Vec<u8>) {
+        self.len().save_state(state);
+        for value in self.iter() { value.save_state(state) }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let len = state.read::<usize>()?;
+        self.clear();
+        for _ in 0..len { self.push_back(state.read()?) }
+        Ok(())
+    }
+}
+
+impl<T: SaveState + Copy> SaveState for Cell<T> {
+    fn save_state(&self, state: &mut Vec<u8>) { self.get().save_state(state) }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.get_mut().load_state(state)
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 0x1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+        *entry = crc;
+    }
+    !data.iter().fold(!0u32, |crc, byte| table[((crc ^ *byte as u32) & 0xFF) as usize] ^ crc >> 8)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    RomMismatch { title: String },
+    UnexpectedEnd,
+    InvalidValue,
+    MovieActive,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::InvalidHeader => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "Unsupported save state version {}", version),
+            StateError::RomMismatch { title } => write!(f, "Save state was created with a different ROM ({})", title),
+            StateError::UnexpectedEnd => write!(f, "Save state is truncated"),
+            StateError::InvalidValue => write!(f, "Save state contains an invalid value"),
+            StateError::MovieActive => write!(f, "Save states can't be loaded while a movie is recording or playing"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
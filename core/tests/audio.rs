@@ -0,0 +1,172 @@
+mod harness;
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use core::gba::{self, AudioChannel, AudioChannelState, GBA, RingBufferSink};
+use harness::{Assembler, CollectingSink};
+
+// Plays a 440 Hz square wave on the first tone channel, mixed at the rate amplitude_res selects in SOUNDBIAS
+fn tone_rom(amplitude_res: u32) -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0084).load(1, 0x80).str(1, 0);
+    asm.load(0, 0x0400_0088).load(1, 0x0200 | amplitude_res << 14).str(1, 0);
+    asm.load(0, 0x0400_0080).load(1, 0x0002_1177).str(1, 0);
+    asm.load(0, 0x0400_0060).load(1, 0xF080_0000).str(1, 0);
+    asm.load(0, 0x0400_0064).load(1, 0x86D6).str(1, 0);
+    let label = asm.label();
+    asm.b(label).finish()
+}
+
+#[test]
+fn resample() {
+    let frames = 60;
+    let expected = 48000 * frames * gba::CLOCKS_PER_FRAME / gba::CLOCK_FREQ;
+    for &amplitude_res in [0, 3].iter() {
+        let rom = tone_rom(amplitude_res);
+        let (sink, buffer) = RingBufferSink::new(0x40000, 48000);
+        let (mut gba, _, _) = GBA::builder(&rom).audio_sink(Box::new(sink)).build().unwrap();
+        for _ in 0..frames { gba.emulate_frame() }
+        let samples: Vec<(i16, i16)> = buffer.lock().unwrap().drain(..).collect();
+        // Rate control only nudges the rate up, since the buffer's well below where it aims to be, and a few
+        // samples are still in the resampler
+        assert!((expected - 16..=expected * 1005 / 1000).contains(&samples.len()));
+        assert!(samples.iter().all(|&(left, right)| left == right));
+        assert!(samples.iter().any(|&(left, _)| left > 0) && samples.iter().any(|&(left, _)| left < 0));
+    }
+}
+
+#[test]
+fn mute_and_solo() {
+    let rom = tone_rom(0);
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let (mut gba, _, _) = GBA::builder(&rom).audio_sink(Box::new(CollectingSink(Arc::clone(&samples)))).build().unwrap();
+    gba.enable_audio_scope(true);
+    let audible = |gba: &mut GBA| {
+        samples.lock().unwrap().clear();
+        gba.emulate_frame();
+        let samples = samples.lock().unwrap();
+        samples.iter().any(|&sample| sample != samples[0])
+    };
+    audible(&mut gba);
+    assert!(audible(&mut gba));
+    match gba.audio_state().channels[0] {
+        AudioChannelState::Tone { playing, frequency, duty, volume } => {
+            assert!(playing);
+            assert!((frequency - 440.0).abs() < 1.0);
+            assert_eq!((duty, volume), (0.5, 15));
+        },
+        ref state => panic!("Expected a tone channel, got {:?}", state),
+    }
+
+    gba.mute_audio_channel(AudioChannel::Tone1, true);
+    assert!(!audible(&mut gba));
+    // The scope still shows muted channels
+    let state = gba.audio_state();
+    assert_eq!(state.muted, [true, false, false, false, false, false]);
+    assert!(state.scope.iter().any(|samples| samples[0] != 0));
+    assert!(state.scope.iter().all(|samples| samples[1] == 0));
+
+    gba.mute_audio_channel(AudioChannel::Tone1, false);
+    gba.solo_audio_channel(AudioChannel::Noise, true);
+    assert!(!audible(&mut gba));
+    gba.solo_audio_channel(AudioChannel::Tone1, true);
+    assert!(audible(&mut gba));
+}
+
+#[test]
+fn channel_readouts() {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0084).load(1, 0x80).str(1, 0);
+    // 512 Hz through one bank, so 256 Hz through both, at 50% volume
+    asm.load(0, 0x0400_0070).load(1, 0x4000_00A0).str(1, 0);
+    asm.load(0, 0x0400_0074).load(1, 0x8780).str(1, 0);
+    // 16384 Hz from a ratio of 2 and shift of 3, with the 7 bit LFSR
+    asm.load(0, 0x0400_0078).load(1, 0xF000).str(1, 0);
+    asm.load(0, 0x0400_007C).load(1, 0x803A).str(1, 0);
+    // 12 bytes into FIFO A and 36 into FIFO B, which holds 32
+    asm.load(0, 0x0400_00A0).load(1, 0x0102_0304);
+    for _ in 0..3 { asm.str(1, 0); }
+    asm.load(0, 0x0400_00A4);
+    for _ in 0..9 { asm.str(1, 0); }
+    let label = asm.label();
+    let rom = asm.b(label).finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    gba.emulate_frame();
+
+    let state = gba.audio_state();
+    match state.channels[2] {
+        AudioChannelState::Wave { playing, frequency, volume, two_banks, .. } => {
+            assert!(playing && two_banks);
+            assert!((frequency - 256.0).abs() < 0.01);
+            assert_eq!(volume, 50);
+        },
+        ref state => panic!("Expected a wave channel, got {:?}", state),
+    }
+    match state.channels[3] {
+        AudioChannelState::Noise { playing, frequency, volume, lfsr_width } => {
+            assert!(playing);
+            assert!((frequency - 16384.0).abs() < 0.01);
+            assert_eq!((volume, lfsr_width), (15, 7));
+        },
+        ref state => panic!("Expected a noise channel, got {:?}", state),
+    }
+    let fifo_lens: Vec<usize> = state.channels[4..].iter().map(|state| match *state {
+        AudioChannelState::DMASound { fifo_len, .. } => fifo_len,
+        ref state => panic!("Expected a DMA sound channel, got {:?}", state),
+    }).collect();
+    assert_eq!(fifo_lens, [12, 32]);
+}
+
+#[test]
+fn record_wav() {
+    let rom = tone_rom(0);
+    let path = std::env::temp_dir().join(format!("record_wav_{}.wav", std::process::id()));
+    let (mut gba, _, _) = GBA::builder(&rom).record_audio(path.clone(), true).build().unwrap();
+    // Recordings are at full speed whatever the emulator runs at
+    gba.set_audio_speed(2.0);
+    for _ in 0..10 { gba.emulate_frame() }
+    assert!(gba.audio_state().recording);
+    gba.stop_audio_recording().unwrap();
+    assert!(!gba.audio_state().recording);
+
+    let read_u16 = |wav: &[u8], offset: usize| u16::from_le_bytes([wav[offset], wav[offset + 1]]);
+    let read_u32 = |wav: &[u8], offset: usize| read_u16(wav, offset) as u32 | (read_u16(wav, offset + 2) as u32) << 16;
+    let read_samples = |wav: &[u8]| -> Vec<i16> {
+        wav[44..].chunks(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]])).collect()
+    };
+    let mixed = fs::read(&path).unwrap();
+    assert_eq!((&mixed[..4], &mixed[8..16], &mixed[36..40]), (&b"RIFF"[..], &b"WAVEfmt "[..], &b"data"[..]));
+    assert_eq!((read_u16(&mixed, 22), read_u32(&mixed, 24), read_u16(&mixed, 34)), (2, gba::AUDIO_SAMPLE_RATE as u32, 16));
+    assert_eq!(read_u32(&mixed, 4) as usize, mixed.len() - 8);
+    assert_eq!(read_u32(&mixed, 40) as usize, mixed.len() - 44);
+    let frames = (mixed.len() - 44) / 4;
+    let expected = 10 * gba::CLOCKS_PER_FRAME / (gba::CLOCK_FREQ / gba::AUDIO_SAMPLE_RATE);
+    assert!((expected - 1..=expected + 1).contains(&frames));
+
+    for &channel in AudioChannel::ALL.iter() {
+        let channel_path = channel.recording_path(&path);
+        let wav = fs::read(&channel_path).unwrap();
+        assert_eq!((read_u16(&wav, 22), wav.len() - 44), (1, frames * 2));
+        let playing = read_samples(&wav).iter().any(|&sample| sample != 0);
+        assert_eq!(playing, channel == AudioChannel::Tone1);
+        fs::remove_file(channel_path).unwrap();
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn record_wav_until_dropped() {
+    let rom = tone_rom(0);
+    let path = std::env::temp_dir().join(format!("record_wav_until_dropped_{}.wav", std::process::id()));
+    let (mut gba, _, _) = GBA::builder(&rom).record_audio(path.clone(), false).build().unwrap();
+    for _ in 0..2 { gba.emulate_frame() }
+    drop(gba);
+
+    // The sizes are still filled in without stopping the recording
+    let wav = fs::read(&path).unwrap();
+    let read_u32 = |offset: usize| u32::from_le_bytes([wav[offset], wav[offset + 1], wav[offset + 2], wav[offset + 3]]);
+    assert!(wav.len() > 44);
+    assert_eq!((read_u32(4) as usize, read_u32(40) as usize), (wav.len() - 8, wav.len() - 44));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,28 @@
+mod harness;
+
+use std::path::PathBuf;
+
+use core::flume;
+use core::gba::{GBA, GBAError, NullSink};
+use harness::busy_rom;
+
+#[test]
+fn invalid_inputs() {
+    let rom = busy_rom();
+    match GBA::builder(&rom).bios(&[0; 0x100]).build() {
+        Err(GBAError::InvalidBiosSize(0x100)) => (),
+        result => panic!("Expected an invalid BIOS size, got {:?}", result.err()),
+    }
+    match GBA::builder(&[]).build() {
+        Err(GBAError::InvalidRomSize(0)) => (),
+        result => panic!("Expected an invalid ROM size, got {:?}", result.err()),
+    }
+
+    // Loading from a file only takes .gba files, before even trying to read it
+    let (render_tx, _) = flume::unbounded();
+    let (_, keypad_rx) = flume::unbounded();
+    match GBA::new(PathBuf::from("missing.zip"), render_tx, keypad_rx, Box::new(NullSink)) {
+        Err(err @ GBAError::InvalidRomExtension(_)) => assert_eq!(err.to_string(), "missing.zip is not a .gba file"),
+        result => panic!("Expected an invalid ROM extension, got {:?}", result.err()),
+    }
+}
@@ -0,0 +1,109 @@
+mod harness;
+
+use core::gba::{GBA, Cheat, CheatFormat, CheatOp, VisibleMemoryRegion};
+use harness::Assembler;
+
+// The device's TEA encryption, to make codes out of known decrypted values
+fn encrypt(mut op1: u32, mut op2: u32, seeds: [u32; 4]) -> String {
+    let mut sum = 0u32;
+    for _ in 0..32 {
+        sum = sum.wrapping_add(0x9E37_79B9);
+        op1 = op1.wrapping_add((op2 << 4).wrapping_add(seeds[0]) ^ op2.wrapping_add(sum) ^
+            (op2 >> 5).wrapping_add(seeds[1]));
+        op2 = op2.wrapping_add((op1 << 4).wrapping_add(seeds[2]) ^ op1.wrapping_add(sum) ^
+            (op1 >> 5).wrapping_add(seeds[3]));
+    }
+    format!("{:08X} {:08X}", op1, op2)
+}
+
+#[test]
+fn decrypt_codes() {
+    let v1_seeds = [0x09F4_FBBD, 0x9681_884A, 0x3520_27E9, 0xF3DE_E5A7];
+    let v3_seeds = [0x7AA9_648F, 0x7FAE_6994, 0xC0EF_AAD5, 0x4271_2C57];
+
+    let cheat = Cheat::new("Write", CheatFormat::GameSharkV1, &[&encrypt(0x1200_0004, 0xBEEF, v1_seeds)]).unwrap();
+    assert_eq!(cheat.ops(), [CheatOp::Write { addr: 0x0200_0004, size: 2, value: 0xBEEF }]);
+    // Addresses are packed, so 0x00300010 is 0x03000010
+    let cheat = Cheat::new("Write", CheatFormat::GameSharkV3, &[&encrypt(0x0230_0010, 0x1234, v3_seeds)]).unwrap();
+    assert_eq!(cheat.ops(), [CheatOp::Write { addr: 0x0300_0010, size: 2, value: 0x1234 }]);
+    let cheat = Cheat::new("Write", CheatFormat::GameSharkV3, &[&encrypt(0x0234_0010, 0x1234, v3_seeds)]).unwrap();
+    assert_eq!(cheat.ops(), [CheatOp::Write { addr: 0x0300_0010, size: 2, value: 0x1234 }]);
+    let cheat = Cheat::new("Master", CheatFormat::GameSharkV1, &[&encrypt(0xF800_0100, 0x1, v1_seeds)]).unwrap();
+    assert!(cheat.is_master());
+    // Reseeding isn't supported
+    assert!(Cheat::new("Reseed", CheatFormat::GameSharkV1, &[&encrypt(0xDEAD_FACE, 0x1, v1_seeds)]).is_err());
+    assert!(Cheat::new("Too Short", CheatFormat::GameSharkV1, &["12345678 1234"]).is_err());
+    // CodeBreaker codes after a 9 code are encrypted with a key made from it
+    let cheat = Cheat::new("Encrypted", CheatFormat::CodeBreaker, &["9123ABCD 4567", "B7326EF0 E4EA", "0BDD265A B09B"]).unwrap();
+    assert_eq!(cheat.ops(), [
+        CheatOp::Write { addr: 0x0200_0034, size: 1, value: 0x42 },
+        CheatOp::Write { addr: 0x0200_0036, size: 2, value: 0x1234 },
+    ]);
+
+    let patch = encrypt(0x6000_0080, 0xBEEF, v1_seeds);
+    let text = format!("# Comment\n[Master Code]\n00001234 5678\n[Patch] GameSharkV1\n{}\n", patch);
+    let cheats = Cheat::parse_list(&text).unwrap();
+    assert_eq!(cheats.len(), 2);
+    assert_eq!(cheats[0].format, CheatFormat::CodeBreaker);
+    assert!(cheats[0].is_master());
+    assert_eq!(cheats[1].name, "Patch");
+    assert_eq!(cheats[1].ops(), [CheatOp::Patch { addr: 0x0800_0100, value: 0xBEEF }]);
+    assert!(Cheat::parse_list("82000000 0001\n").is_err());
+    assert!(Cheat::parse_list("[Cheat] PocketMonster\n82000000 0001\n").is_err());
+}
+
+#[test]
+fn apply_cheats() {
+    // Keeps writing 5 to 0x02000020
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0200_0020).load(1, 5);
+    let label = asm.label();
+    let mut rom = asm.str(1, 0).b(label).finish();
+    rom.resize(0x200, 0);
+
+    let patch = encrypt(0x6000_0080, 0xBEEF, [0x09F4_FBBD, 0x9681_884A, 0x3520_27E9, 0xF3DE_E5A7]);
+    let text = format!("[Patch] GameSharkV1\n{}\n\
+        [Freeze]\n82000020 0063\n\
+        [Condition Met]\n72000040 1234\n32000030 0042\n\
+        [Condition Not Met]\n72000040 4321\n32000031 0042\n\
+        [Last Condition Not Met]\n72000040 4321\n\
+        [After Condition]\n32000033 0042\n\
+        [Disabled]\n32000032 0042\n", patch);
+    let mut cheats = Cheat::parse_list(&text).unwrap();
+    cheats[6].enabled = false;
+    let (mut gba, _, _) = GBA::builder(&rom).cheats(cheats).build().unwrap();
+    gba.poke_mem(VisibleMemoryRegion::EWRAM, 0x40, 0x34);
+    gba.poke_mem(VisibleMemoryRegion::EWRAM, 0x41, 0x12);
+    gba.emulate_frame();
+
+    // Reads see the patch, but the ROM itself isn't changed
+    assert_eq!([gba.read_mem(0x0800_0100), gba.read_mem(0x0800_0101)], [0xEF, 0xBE]);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::PakROM, 0x100), 0x00);
+    // The game's own writes don't stick
+    let ewram = |gba: &GBA, addr| gba.peek_mem(VisibleMemoryRegion::EWRAM, addr);
+    assert_eq!([ewram(&gba, 0x20), ewram(&gba, 0x21)], [0x63, 0x00]);
+    assert_eq!([ewram(&gba, 0x30), ewram(&gba, 0x31), ewram(&gba, 0x32)], [0x42, 0x00, 0x00]);
+    // Conditions don't skip the next cheat's ops
+    assert_eq!(ewram(&gba, 0x33), 0x42);
+
+    assert!(gba.set_cheat_enabled(1, false));
+    gba.emulate_frame();
+    assert_eq!(ewram(&gba, 0x20), 0x05);
+    // Past the end of the list, as when it's reloaded while the frontend still shows the old one
+    let len = gba.cheats().len();
+    assert!(!gba.set_cheat_enabled(len, true));
+}
+
+#[test]
+fn cheat_file() {
+    let rom = Assembler::new().finish();
+    let path = std::env::temp_dir().join(format!("cheat_file_{}.cht", std::process::id()));
+    std::fs::write(&path, "[Freeze]\n82000020 0063\n").unwrap();
+    let (gba, _, _) = GBA::builder(&rom).cheat_file(path.clone()).build().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(gba.cheats().len(), 1);
+    assert_eq!(gba.cheats()[0].name, "Freeze");
+    // No file just means no cheats
+    let (gba, _, _) = GBA::builder(&rom).cheat_file(path).build().unwrap();
+    assert!(gba.cheats().is_empty());
+}
@@ -0,0 +1,276 @@
+mod harness;
+
+use core::flume;
+use core::gba::{
+    GBA, IO_REGISTERS, OBJInfo, OBJMode, RAMSearch, SearchComparison, SearchFilter, StopReason, TimelineEvent,
+    VisibleMemoryRegion, WatchKind, Watchpoint,
+};
+use harness::{Assembler, busy_rom};
+
+#[test]
+fn call_stack() {
+    let rom = Assembler::new()
+        .word(0xEB00_0000) // 08000000: bl 0x08000008
+        .word(0xEAFF_FFFE) // 08000004: b .
+        .word(0xE92D_4000) // 08000008: push {lr}
+        .word(0xEB00_0000) // 0800000C: bl 0x08000014
+        .word(0xEAFF_FFFE) // 08000010: b .
+        .finish();         // 08000014: b .
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+
+    assert_eq!(gba.call_return_addr(), Some(0x0800_0004));
+    gba.step();
+    assert_eq!(gba.call_return_addr(), None);
+    assert_eq!(gba.call_stack(), [0x0800_0000]);
+    gba.step();
+    gba.step();
+    assert_eq!(gba.get_reg(15), 0x0800_0014);
+    assert_eq!(gba.call_stack(), [0x0800_000C, 0x0800_0000]);
+    assert_eq!(gba.disassemble(0x0800_0000, false), "bl 0x08000008");
+}
+
+#[test]
+fn peek_poke_memory() {
+    let rom = Assembler::new().finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+
+    // Reads the BIOS itself rather than the last value fetched from it
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::BIOS, 0x1B), 0xEA);
+    // 8 bit writes don't get mirrored to both bytes or ignored like they would from the CPU
+    gba.poke_mem(VisibleMemoryRegion::VRAM, 0x11, 0xAB);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::VRAM, 0x10), 0x00);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::VRAM, 0x11), 0xAB);
+    gba.poke_mem(VisibleMemoryRegion::OAM, 0x3, 0xCD);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::OAM, 0x3), 0xCD);
+    gba.poke_mem(VisibleMemoryRegion::IWRAM, 0x7FFF, 0x12);
+    assert_eq!(gba.read_mem(0x0300_7FFF), 0x12);
+    // The ROM can be patched
+    gba.poke_mem(VisibleMemoryRegion::PakROM, 0x0, 0xFF);
+    assert_eq!(gba.disassemble(0x0800_0000, false), "b 0x08000004");
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::PakROM, 0x100_0000), 0x00);
+}
+
+#[test]
+fn poke_io_without_side_effects() {
+    let rom = busy_rom();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    let poke = |gba: &mut GBA, addr: usize, bytes: &[u8]| {
+        for (i, byte) in bytes.iter().enumerate() { gba.poke_mem(VisibleMemoryRegion::IO, addr + i, *byte) }
+    };
+    let vram = |gba: &GBA| (0..0x100).map(|addr| gba.peek_mem(VisibleMemoryRegion::VRAM, addr)).collect::<Vec<_>>();
+
+    // IF gets set instead of acknowledged
+    poke(&mut gba, 0x202, &[0x01]);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IO, 0x202), 0x01);
+    poke(&mut gba, 0x202, &[0x00]);
+    // Neither DMA 3 nor timer 3 starts, and HALTCNT doesn't stop the CPU
+    poke(&mut gba, 0xD4, &[0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x84]);
+    poke(&mut gba, 0x10C, &[0x00, 0x00, 0x80]);
+    poke(&mut gba, 0x301, &[0x80]);
+    let before = vram(&gba);
+    gba.emulate_frame();
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IO, 0xDF), 0x04);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IWRAM, 0x0), 0x00);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IO, 0x10E), 0x00);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IO, 0x10C), 0x00);
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IO, 0x301), 0x80);
+    assert_ne!(vram(&gba), before);
+}
+
+#[test]
+fn oam_inspector() {
+    let rom = Assembler::new().finish();
+    let (render_tx, render_rx) = flume::unbounded();
+    let (mut gba, _, debug_spec) = GBA::builder(&rom).render_tx(render_tx).build().unwrap();
+    debug_spec.lock().unwrap().objs_enable = true;
+    let mut poke = |region, addr, bytes: &[u8]| {
+        for (i, byte) in bytes.iter().enumerate() { gba.poke_mem(region, addr + i, *byte) }
+    };
+    // Double size affine 16x16 OBJ at (-8, 240) using parameter group 1
+    poke(VisibleMemoryRegion::OAM, 0x0, &[0xF0, 0x07, 0xF8, 0x43, 0x02, 0x5C]);
+    poke(VisibleMemoryRegion::OAM, 0x26, &[0x00, 0x01]);
+    poke(VisibleMemoryRegion::OAM, 0x2E, &[0x80, 0x00]);
+    poke(VisibleMemoryRegion::VRAM, 0x10040, &[0x01]);
+    poke(VisibleMemoryRegion::Palette, 0x200 + (5 * 16 + 1) * 2, &[0x1F, 0x00]);
+    gba.emulate_frame();
+
+    let windows = render_rx.try_iter().last().unwrap();
+    assert_eq!(windows.objs.len(), 0x80);
+    let obj = &windows.objs[0];
+    assert_eq!((obj.x, obj.y, obj.width, obj.height), (-8, -16, 16, 16));
+    assert_eq!(obj.bounds(), (-8, -16, 32, 32));
+    assert_eq!((obj.priority, obj.palette, obj.tile, obj.mode), (3, Some(5), 2, OBJMode::SemiTransparent));
+    assert_eq!(obj.affine, Some((1, [1.0, 0.5, 0.0, 0.0])));
+    assert!(obj.double_size && !obj.disabled);
+    assert_eq!(windows.objs[1].mode, OBJMode::Normal);
+
+    let (pixels, width, height) = &windows.images[0];
+    assert_eq!((*width, *height), (OBJInfo::THUMBNAIL_SIZE * 16, OBJInfo::THUMBNAIL_SIZE * 8));
+    assert_eq!(pixels[0], 0x801F);
+    assert_eq!(pixels[1], 0x0000);
+}
+
+#[test]
+fn layer_toggles() {
+    let rom = Assembler::new().finish();
+    let (render_tx, render_rx) = flume::unbounded();
+    let (mut gba, pixels, debug_spec) = GBA::builder(&rom).render_tx(render_tx).build().unwrap();
+    let mut poke = |region, addr, bytes: &[u8]| {
+        for (i, byte) in bytes.iter().enumerate() { gba.poke_mem(region, addr + i, *byte) }
+    };
+    // BG0 filled with color 1 of its tile, with the map at 0x4000
+    poke(VisibleMemoryRegion::IO, 0x0, &[0x00, 0x01]);
+    poke(VisibleMemoryRegion::IO, 0x8, &[0x00, 0x08]);
+    poke(VisibleMemoryRegion::VRAM, 0x0, &[0x11; 0x20]);
+    poke(VisibleMemoryRegion::Palette, 0x0, &[0x00, 0x7C, 0x1F, 0x00]);
+    {
+        let mut spec = debug_spec.lock().unwrap();
+        spec.layers_enable = true;
+        spec.layers_spec.layer = 6;
+    }
+    // The spec is only picked up at VBlank
+    gba.emulate_frame();
+    gba.emulate_frame();
+    assert_eq!(pixels.lock().unwrap()[0], 0x001F);
+    let (layer, _, _) = render_rx.try_iter().last().unwrap().images.pop_front().unwrap();
+    assert!(layer.iter().all(|&pixel| pixel == 0x801F));
+
+    debug_spec.lock().unwrap().layer_toggles.bgs[0] = false;
+    gba.emulate_frame();
+    gba.emulate_frame();
+    assert_eq!(pixels.lock().unwrap()[0], 0x7C00);
+    let (layer, _, _) = render_rx.try_iter().last().unwrap().images.pop_front().unwrap();
+    assert!(layer.iter().all(|&pixel| pixel == 0xC210));
+}
+
+#[test]
+fn io_registers() {
+    for (register, next) in IO_REGISTERS.iter().zip(IO_REGISTERS.iter().skip(1)) {
+        assert!(register.addr + register.size <= next.addr, "{} overlaps {}", register.name, next.name);
+    }
+    for register in IO_REGISTERS.iter() {
+        let mut bits = 0u64;
+        for &(name, bit, width) in register.fields.iter() {
+            let mask = ((1u64 << width) - 1) << bit;
+            assert!(bits & mask == 0 && bit + width <= register.size * 8, "{} {}", register.name, name);
+            bits |= mask;
+        }
+    }
+
+    let rom = Assembler::new().finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    let register = |name| IO_REGISTERS.iter().find(|register| register.name == name).unwrap();
+    let dispcnt = register("DISPCNT");
+    gba.poke_io_register(dispcnt, 0, 3);
+    gba.poke_io_register(dispcnt, 8, 1);
+    assert_eq!(gba.peek_io_register(dispcnt), 0x0403);
+    assert_eq!(dispcnt.get_field(0x0403, 0), 3);
+    assert_eq!(dispcnt.get_field(0x0403, 10), 0);
+    assert_eq!(gba.read_mem(0x0400_0001), 0x04);
+
+    // Clearing one IF flag leaves the others pending
+    let if_ = register("IF");
+    gba.poke_io_register(if_, 0, 1);
+    gba.poke_io_register(if_, 2, 1);
+    gba.poke_io_register(if_, 0, 0);
+    assert_eq!(gba.peek_io_register(if_), 0x0004);
+
+    // What was written to write only registers and fields shows, and editing a field keeps the others
+    for (addr, value) in [
+        (0x0400_0028, 0x34), (0x0400_0029, 0x12), (0x0400_002B, 0x08), (0x0400_0020, 0x80), (0x0400_0021, 0x01),
+        (0x0400_0054, 0x0C), (0x0400_0062, 0x85), (0x0400_0064, 0x23), (0x0400_0065, 0x41),
+        (0x0400_00D4, 0x10), (0x0400_00D7, 0x08), (0x0400_00DC, 0x10),
+    ].iter() { gba.write_mem(*addr, *value) }
+    assert_eq!(gba.peek_io_register(register("BG2X")), 0x0800_1234);
+    gba.poke_io_register(register("BG2X"), 0, 0x56);
+    assert_eq!(gba.peek_io_register(register("BG2X")), 0x0800_1256);
+    gba.poke_io_register(register("BG2PA"), 1, 2);
+    assert_eq!(gba.peek_io_register(register("BG2PA")), 0x0280);
+    assert_eq!(gba.peek_io_register(register("BLDY")), 0x0C);
+    gba.poke_io_register(register("SOUND1CNT_H"), 1, 1);
+    assert_eq!(gba.peek_io_register(register("SOUND1CNT_H")), 0x0045);
+    gba.poke_io_register(register("SOUND1CNT_X"), 1, 0);
+    assert_eq!(gba.peek_io_register(register("SOUND1CNT_X")), 0x0123);
+    assert_eq!(gba.peek_io_register(register("DMA3SAD")), 0x0800_0010);
+    assert_eq!(gba.peek_io_register(register("DMA3CNT_L")), 0x0010);
+    assert_eq!(gba.read_mem(0x0400_0064), 0x00);
+}
+
+#[test]
+fn timeline() {
+    let rom = Assembler::new().finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    // Written like the CPU would, since poking registers doesn't start anything
+    let mut write = |name, value: u32| {
+        let register = IO_REGISTERS.iter().find(|register| register.name == name).unwrap();
+        for i in 0..register.size { gba.write_mem(register.addr + i, (value >> (8 * i)) as u8) }
+    };
+    // Timer 0 overflowing every 256 cycles and an immediate 16 word DMA
+    write("TM0CNT_L", 0xFF00);
+    write("TM0CNT_H", 0x0080);
+    write("DMA3SAD", 0x0800_0000);
+    write("DMA3DAD", 0x0300_0000);
+    write("DMA3CNT_L", 16);
+    write("DMA3CNT_H", 0x8000);
+    gba.enable_timeline(true);
+    gba.emulate_frame();
+
+    let timeline = gba.take_timeline().unwrap();
+    assert!(gba.take_timeline().is_none());
+    let count = |event| timeline.iter().filter(|entry| entry.event == event).count();
+    assert_eq!(count(TimelineEvent::HBlank), 228);
+    assert_eq!(count(TimelineEvent::VBlank), 1);
+    assert!((1096..=1097).contains(&count(TimelineEvent::TimerOverflow(0))));
+    let vblank = timeline.iter().find(|entry| entry.event == TimelineEvent::VBlank).unwrap();
+    // Dots are only emulated once the CPU has used up their cycles
+    assert!((160 * 1232..160 * 1232 + 16).contains(&vblank.start));
+    assert_eq!(vblank.end - vblank.start, 68 * 1232);
+    let dma = timeline.iter().find(|entry| entry.event == TimelineEvent::DMA(3)).unwrap();
+    assert!(dma.start < 100 && dma.end - dma.start > 32);
+}
+
+#[test]
+fn ram_search() {
+    let rom = Assembler::new().finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    let mut search = RAMSearch::new(&gba, 2, false);
+    assert_eq!(search.candidates().len(), (0x4_0000 + 0x8000) / 2);
+
+    gba.poke_mem(VisibleMemoryRegion::EWRAM, 0x100, 0x34);
+    gba.poke_mem(VisibleMemoryRegion::EWRAM, 0x101, 0x12);
+    gba.poke_mem(VisibleMemoryRegion::IWRAM, 0x20, 0x34);
+    gba.poke_mem(VisibleMemoryRegion::IWRAM, 0x21, 0x12);
+    search.filter(&gba, SearchFilter::Value(SearchComparison::Equal, 0x1234));
+    assert_eq!(search.candidates(), [(0x0200_0100, 0x1234), (0x0300_0020, 0x1234)]);
+    search.filter(&gba, SearchFilter::Unchanged);
+    assert_eq!(search.candidates().len(), 2);
+    gba.poke_mem(VisibleMemoryRegion::IWRAM, 0x21, 0x13);
+    search.filter(&gba, SearchFilter::Increased);
+    assert_eq!(search.candidates(), [(0x0300_0020, 0x1334)]);
+
+    // 0xFF is -1 when signed
+    gba.poke_mem(VisibleMemoryRegion::EWRAM, 0x200, 0xFF);
+    let mut search = RAMSearch::new(&gba, 1, true);
+    search.filter(&gba, SearchFilter::Value(SearchComparison::Less, 0));
+    assert!(search.candidates().contains(&(0x0200_0200, 0xFF)));
+    assert_eq!(search.value(0xFF), -1);
+    let mut search = RAMSearch::new(&gba, 1, false);
+    search.filter(&gba, SearchFilter::Value(SearchComparison::Less, 0));
+    assert!(search.candidates().is_empty());
+}
+
+#[test]
+fn watchpoints_at_top_of_memory() {
+    let mut asm = Assembler::new();
+    asm.load(0, 0xFFFF_FFFC).word(0xE590_1000); // ldr r1, [r0]
+    asm.load(0, 0x0300_0000).str(1, 0);
+    let rom = asm.finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    // Overlapping ranges are found even when the access runs up to the end of the address space
+    let top = Watchpoint { addr: 0xFFFF_FFFE, len: 2, kind: WatchKind::Read };
+    let iwram = Watchpoint { addr: 0x0300_0000, len: 4, kind: WatchKind::Write };
+    gba.add_watchpoint(top);
+    gba.add_watchpoint(iwram);
+    assert_eq!(gba.emulate_frame_debug(), Some(StopReason::Watchpoint(top, 0xFFFF_FFFC)));
+    assert_eq!(gba.emulate_frame_debug(), Some(StopReason::Watchpoint(iwram, 0x0300_0000)));
+}
@@ -0,0 +1,76 @@
+mod harness;
+
+use std::fs;
+
+use core::gba::{GBA, disassemble_arm, disassemble_thumb};
+use harness::Assembler;
+
+#[test]
+fn arm_encodings() {
+    let cases = [
+        (0xE12F_FF1E, "bx lr"),
+        (0xE003_0291, "mul r3, r1, r2"),
+        (0xE0A5_4392, "umlal r4, r5, r2, r3"),
+        (0xE102_0091, "swp r0, r1, [r2]"),
+        (0xE1D1_00B2, "ldrh r0, [r1, #0x2]"),
+        (0xE10F_0000, "mrs r0, cpsr"),
+        (0xE129_F000, "msr cpsr_cf, r0"),
+        (0xE3A0_0403, "mov r0, #0x3000000"),
+        (0x1091_1102, "addnes r1, r1, r2, lsl #2"),
+        (0xE355_0000, "cmp r5, #0x0"),
+        (0xE5B0_1004, "ldr r1, [r0, #0x4]!"),
+        (0xE92D_400F, "stmdb sp!, {r0-r3, lr}"),
+        (0xEBFF_FFFE, "bl 0x08000000"),
+        (0xEF05_0000, "swi #0x50000"),
+        (0xEE01_0F10, "mcr"),
+    ];
+    for &(instr, text) in cases.iter() {
+        assert_eq!(disassemble_arm(instr, 0x0800_0000), text, "{:08X}", instr);
+    }
+}
+
+#[test]
+fn thumb_encodings() {
+    let cases = [
+        (0x1C48, "add r0, r1, #0x1"),
+        (0x0089, "lsl r1, r1, #2"),
+        (0x2080, "mov r0, #0x80"),
+        (0x4348, "mul r0, r1"),
+        (0x4770, "bx lr"),
+        (0x4801, "ldr r0, [pc, #0x4]"),
+        (0x5E88, "ldrsh r0, [r1, r2]"),
+        (0x6848, "ldr r0, [r1, #0x4]"),
+        (0xB081, "add sp, #-0x4"),
+        (0xB5F0, "push {r4-r7, lr}"),
+        (0xC903, "ldmia r1!, {r0, r1}"),
+        (0xDF06, "swi #0x6"),
+        (0xD0FE, "beq 0x08000000"),
+        (0xE7FE, "b 0x08000000"),
+        (0xF7FF, "bl 0x08000000"),
+    ];
+    for &(instr, text) in cases.iter() {
+        assert_eq!(disassemble_thumb(instr, 0xFFFE, 0x0800_0000), text, "{:04X}", instr);
+    }
+}
+
+#[test]
+fn instruction_trace() {
+    let rom = Assembler::new().load(0, 0x1234).subs(1, 0, 0x34).finish();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    let path = std::env::temp_dir().join(format!("instruction_trace_{}.txt", std::process::id()));
+    gba.start_trace(&path).unwrap();
+    gba.step();
+    gba.step();
+    gba.step();
+    gba.stop_trace();
+
+    let trace = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let lines: Vec<Vec<&str>> = trace.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0][1..], ["08000000", "E3A00034", "mov", "r0,", "#0x34", "r0=00000034"]);
+    assert_eq!(lines[1][1..], ["08000004", "E3800C12", "orr", "r0,", "r0,", "#0x1200", "r0=00001234"]);
+    assert_eq!(lines[2][1..], ["08000008", "E2501034", "subs", "r1,", "r0,", "#0x34", "r1=00001200", "cpsr=2000001F"]);
+    let cycles: Vec<usize> = lines.iter().map(|line| line[0].parse().unwrap()).collect();
+    assert!(cycles[0] < cycles[1] && cycles[1] < cycles[2]);
+}
@@ -0,0 +1,98 @@
+mod harness;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use core::gba::{GBA, GDBStub};
+use harness::Assembler;
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn command(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+        assert_eq!(self.read_byte(), b'$');
+        let mut reply = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+        self.read_byte();
+        self.read_byte();
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+// Registers go over the wire in little endian
+fn reg(value: u32) -> String { format!("{:08x}", value.swap_bytes()) }
+
+#[test]
+fn gdb_session() {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0300_0000) // 0x08000000
+        .load(1, 0x1234) // 0x08000008
+        .str(1, 0) // 0x08000010
+        .add(1, 1, 1) // 0x08000014
+        .b(6) // 0x08000018
+        .word(0x3201_2205); // 0x0800001C: movs r2, #5; adds r2, #1
+    let rom = asm.finish();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        GDBStub::new(stream).unwrap().run(&mut gba).unwrap();
+    });
+    let mut client = Client { stream: TcpStream::connect(addr).unwrap() };
+
+    assert_eq!(client.command("?"), "S05");
+    assert_eq!(client.command("pf"), reg(0x0800_0000));
+    assert_eq!(client.command("s"), "S05");
+    assert_eq!(client.command("s"), "S05");
+    assert_eq!(client.command("pf"), reg(0x0800_0008));
+    assert_eq!(client.command("p0"), reg(0x0300_0000));
+
+    assert_eq!(client.command("Z0,08000014,4"), "OK");
+    assert_eq!(client.command("c"), "S05");
+    assert_eq!(client.command("pf"), reg(0x0800_0014));
+    assert_eq!(client.command("m03000000,4"), "34120000");
+    assert_eq!(client.command("z0,08000014,4"), "OK");
+
+    assert_eq!(client.command("P1=00000000"), "OK");
+    assert_eq!(client.command("Z2,03000000,4"), "OK");
+    assert_eq!(client.command("Pf=10000008"), "OK");
+    assert_eq!(client.command("c"), "T05watch:03000000;");
+    assert_eq!(client.command("m03000000,2"), "0000");
+    assert_eq!(client.command("M03000000,2:abcd"), "OK");
+    assert_eq!(client.command("m03000000,2"), "abcd");
+
+    assert_eq!(client.command("Pf=1c000008"), "OK");
+    assert_eq!(client.command("P19=3f000000"), "OK");
+    assert_eq!(client.command("s"), "S05");
+    assert_eq!(client.command("pf"), reg(0x0800_001E));
+    assert_eq!(client.command("p2"), reg(5));
+    assert_eq!(client.command("s"), "S05");
+    assert_eq!(client.command("p2"), reg(6));
+    assert_eq!(client.command("P19=1f000000"), "OK");
+
+    let regs = client.command("g");
+    assert_eq!(regs.len(), 22 * 8);
+    assert_eq!(&regs[16 * 8..17 * 8], reg(0x1F));
+    assert!(client.command("qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+    assert_eq!(client.command("D"), "OK");
+    server.join().unwrap();
+}
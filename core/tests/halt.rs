@@ -0,0 +1,86 @@
+mod harness;
+
+use std::sync::{Arc, Mutex};
+
+use core::gba::{GBA, KEYINPUT, VisibleMemoryRegion};
+use harness::{Assembler, CollectingSink};
+
+// Plays all four PSG channels and FIFO A, then waits forever either halted or in a loop
+fn sound_rom(halt: bool) -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0084).load(1, 0x80).str(1, 0);
+    asm.load(0, 0x0400_0080).load(1, 0x0B06_FF77).str(1, 0);
+    asm.load(0, 0x0400_0060).load(1, 0xF080_0000).str(1, 0);
+    asm.load(0, 0x0400_0064).load(1, 0x86D6).str(1, 0);
+    asm.load(0, 0x0400_0068).load(1, 0xA040).str(1, 0);
+    asm.load(0, 0x0400_006C).load(1, 0x8783).str(1, 0);
+    asm.load(0, 0x0400_0090).load(1, 0x0123_4567).str(1, 0);
+    asm.load(0, 0x0400_0094).load(1, 0x89AB_CDEF).str(1, 0);
+    asm.load(0, 0x0400_0070).load(1, 0x2000_0080).str(1, 0);
+    asm.load(0, 0x0400_0074).load(1, 0x8600).str(1, 0);
+    asm.load(0, 0x0400_0078).load(1, 0xF000).str(1, 0);
+    asm.load(0, 0x0400_007C).load(1, 0x8011).str(1, 0);
+    // DMA1 feeds FIFO A from the ROM at the 16384 Hz timer 0 overflows at
+    asm.load(0, 0x0400_00BC).load(1, 0x0800_1000).str(1, 0);
+    asm.load(0, 0x0400_00C0).load(1, 0x0400_00A0).str(1, 0);
+    asm.load(0, 0x0400_00C4).load(1, 0xB600_0000).str(1, 0);
+    asm.load(0, 0x0400_0100).load(1, 0x0080_FC00).str(1, 0);
+    // A timer and the VCOUNT interrupt flag keep changing as well
+    asm.load(0, 0x0400_0104).load(1, 0x0081_0000).str(1, 0);
+    asm.load(0, 0x0400_0004).load(1, 0x5020).str(1, 0);
+    if halt { asm.load(0, 0x0400_0301).load(1, 0).strb_inc(1, 0); }
+    let mut rom = asm.finish();
+    // Samples for FIFO A, past the code so that they're the same either way
+    rom.resize(0x1000, 0);
+    rom.extend((0..0x1000).map(|i| (i * 37) as u8));
+    rom
+}
+
+#[test]
+fn halt_matches_running() {
+    let run = |halt: bool| {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let rom = sound_rom(halt);
+        let (mut gba, pixels, _) = GBA::builder(&rom).audio_sink(Box::new(CollectingSink(Arc::clone(&samples)))).build()
+            .unwrap();
+        for _ in 0..10 { gba.emulate_frame() }
+        // The running CPU finishes its last instruction past the end of the frame, so DISPSTAT can differ
+        let io = (0x06..0x08).chain(0x104..0x108).chain(0x202..0x204)
+            .map(|addr| gba.peek_mem(VisibleMemoryRegion::IO, addr)).collect::<Vec<_>>();
+        let samples = samples.lock().unwrap().clone();
+        let pixels = pixels.lock().unwrap().clone();
+        (samples, pixels, io)
+    };
+    let running = run(false);
+    assert!(running.0.iter().any(|&sample| sample != running.0[0]));
+    // Halting skips ahead in bulk, which has to come out the same as going cycle by cycle
+    assert!(run(true) == running);
+}
+
+#[test]
+fn stop_with_timer_running() {
+    // Stops with timer 0 counting every cycle until A is pressed, then keeps copying the timer to IWRAM
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0100).load(1, 0x0080_0000).str(1, 0);
+    asm.load(0, 0x0400_0130).load(1, 0x4001_0000).str(1, 0);
+    asm.load(0, 0x0400_0200).load(1, 0x1000).str(1, 0);
+    asm.load(0, 0x0400_0301).load(1, 0x80).strb_inc(1, 0);
+    asm.load(0, 0x0300_0004).load(1, 1).str(1, 0);
+    let label = asm.label();
+    asm.load(0, 0x0400_0100).ldrh(1, 0).load(0, 0x0300_0000).str(1, 0);
+    let rom = asm.b(label).finish();
+
+    let (keypad_tx, keypad_rx) = flume::unbounded();
+    let (mut gba, _, _) = GBA::builder(&rom).keypad_rx(keypad_rx).build().unwrap();
+    let iwram = |gba: &GBA, addr: usize| gba.peek_mem(VisibleMemoryRegion::IWRAM, addr) as u16 |
+        (gba.peek_mem(VisibleMemoryRegion::IWRAM, addr + 1) as u16) << 8;
+    for _ in 0..3 { gba.emulate_frame() }
+    assert_eq!(iwram(&gba, 4), 0);
+
+    keypad_tx.send((KEYINPUT::A, true)).unwrap();
+    gba.emulate_frame();
+    assert_eq!(iwram(&gba, 4), 1);
+    let counter = iwram(&gba, 0);
+    gba.emulate_frame();
+    assert_ne!(iwram(&gba, 0), counter);
+}
@@ -0,0 +1,163 @@
+// Shared by test binaries that each use only part of it
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use core::gba::{self, AudioSink, GBA, MGBALogLevel};
+
+// Boots a ROM without a window, BIOS or audio
+pub struct Harness {
+    gba: GBA,
+    pixels: Arc<Mutex<Vec<u16>>>,
+}
+
+impl Harness {
+    pub fn new(rom: &[u8]) -> Harness {
+        let (gba, pixels, _) = GBA::builder(rom).capture_debug_messages().build().unwrap();
+        Harness {
+            gba,
+            pixels,
+        }
+    }
+
+    pub fn run_frames(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames { self.gba.emulate_frame() }
+        self
+    }
+
+    // FNV-1a over the framebuffer
+    pub fn frame_hash(&self) -> u64 {
+        let pixels = self.pixels.lock().unwrap();
+        pixels.iter().flat_map(|pixel| pixel.to_le_bytes().to_vec()).fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+    }
+
+    pub fn debug_messages(&mut self) -> Vec<(MGBALogLevel, String)> { self.gba.take_debug_messages() }
+}
+
+// Keeps every sample
+pub struct CollectingSink(pub Arc<Mutex<Vec<(i16, i16)>>>);
+
+impl AudioSink for CollectingSink {
+    fn push_sample(&mut self, left_sample: i16, right_sample: i16) {
+        self.0.lock().unwrap().push((left_sample, right_sample));
+    }
+
+    fn sample_rate(&self) -> usize { gba::AUDIO_SAMPLE_RATE }
+}
+
+// Just enough of an ARM assembler to write test ROMs, which start executing at 0x08000000
+pub struct Assembler {
+    code: Vec<u32>,
+}
+
+impl Assembler {
+    pub fn new() -> Assembler { Assembler { code: Vec::new() } }
+
+    // Built out of one MOV and an ORR for every other nonzero byte
+    pub fn load(&mut self, rd: u32, value: u32) -> &mut Self {
+        self.code.push(0xE3A0_0000 | rd << 12 | (value & 0xFF));
+        for byte in 1..4 {
+            let imm = value >> (byte * 8) & 0xFF;
+            if imm != 0 { self.code.push(0xE380_0000 | rd << 16 | rd << 12 | (16 - byte * 4) << 8 | imm) }
+        }
+        self
+    }
+
+    pub fn add(&mut self, rd: u32, rn: u32, imm: u32) -> &mut Self {
+        assert!(imm <= 0xFF);
+        self.code.push(0xE280_0000 | rn << 16 | rd << 12 | imm);
+        self
+    }
+
+    pub fn subs(&mut self, rd: u32, rn: u32, imm: u32) -> &mut Self {
+        assert!(imm <= 0xFF);
+        self.code.push(0xE250_0000 | rn << 16 | rd << 12 | imm);
+        self
+    }
+
+    // str rd, [rn]
+    pub fn str(&mut self, rd: u32, rn: u32) -> &mut Self {
+        self.code.push(0xE580_0000 | rn << 16 | rd << 12);
+        self
+    }
+
+    // ldrh rd, [rn]
+    pub fn ldrh(&mut self, rd: u32, rn: u32) -> &mut Self {
+        self.code.push(0xE1D0_00B0 | rn << 16 | rd << 12);
+        self
+    }
+
+    // strh rd, [rn], #2
+    pub fn strh_inc(&mut self, rd: u32, rn: u32) -> &mut Self {
+        self.code.push(0xE0C0_00B2 | rn << 16 | rd << 12);
+        self
+    }
+
+    // strb rd, [rn], #1
+    pub fn strb_inc(&mut self, rd: u32, rn: u32) -> &mut Self {
+        self.code.push(0xE4C0_0001 | rn << 16 | rd << 12);
+        self
+    }
+
+    // swi #function, which the HLE BIOS handles directly
+    pub fn swi(&mut self, function: u32) -> &mut Self {
+        self.code.push(0xEF00_0000 | function << 16);
+        self
+    }
+
+    // Raw data, e.g. Thumb code
+    pub fn word(&mut self, word: u32) -> &mut Self {
+        self.code.push(word);
+        self
+    }
+
+    // Position to branch back to
+    pub fn label(&self) -> usize { self.code.len() }
+
+    pub fn bne(&mut self, label: usize) -> &mut Self { self.branch(0x1A00_0000, label) }
+
+    pub fn b(&mut self, label: usize) -> &mut Self { self.branch(0xEA00_0000, label) }
+
+    fn branch(&mut self, opcode: u32, label: usize) -> &mut Self {
+        let offset = label as i32 - (self.code.len() as i32 + 2);
+        self.code.push(opcode | offset as u32 & 0x00FF_FFFF);
+        self
+    }
+
+    // Prints through the mGBA debug registers
+    pub fn debug_print(&mut self, level: u32, message: &str) -> &mut Self {
+        self.load(0, 0x04FF_F780).load(1, 0xC0DE).strh_inc(1, 0);
+        self.load(0, 0x04FF_F600);
+        for byte in message.bytes().chain(std::iter::once(0)) {
+            self.load(1, byte as u32).strb_inc(1, 0);
+        }
+        self.load(0, 0x04FF_F700).load(1, 0x100 | level).strh_inc(1, 0)
+    }
+
+    // Loops forever
+    pub fn finish(&mut self) -> Vec<u8> {
+        let label = self.label();
+        self.b(label);
+        self.code.iter().flat_map(|instr| instr.to_le_bytes().to_vec()).collect()
+    }
+}
+
+// Keeps drawing into the mode 3 bitmap with a timer and a tone running, so that every frame is different
+pub fn busy_rom() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0000).load(1, 0x0403).str(1, 0);
+    asm.load(0, 0x0400_0100).load(1, 0x0080_0000).str(1, 0);
+    asm.load(0, 0x0400_0084).load(1, 0x80).str(1, 0);
+    asm.load(0, 0x0400_0080).load(1, 0x0002_1177).str(1, 0);
+    asm.load(0, 0x0400_0060).load(1, 0xF080_0000).str(1, 0);
+    asm.load(0, 0x0400_0064).load(1, 0x86D6).str(1, 0);
+    asm.load(1, 0);
+    let restart = asm.label();
+    asm.load(0, 0x0600_0000).load(2, 240 * 160);
+    let fill = asm.label();
+    asm.strh_inc(1, 0).add(1, 1, 3).subs(2, 2, 1).bne(fill);
+    asm.b(restart);
+    asm.finish()
+}
@@ -0,0 +1,173 @@
+mod harness;
+
+use core::gba::{GBA, VisibleMemoryRegion};
+use harness::Assembler;
+
+// Data placed after the code in ROM
+const DATA: u32 = 0x0800_1000;
+
+fn build(asm: &mut Assembler, data: &[(u32, &[u8])]) -> GBA {
+    let mut rom = asm.finish();
+    for (addr, bytes) in data.iter() {
+        let offset = (addr - 0x0800_0000) as usize;
+        assert!(rom.len() <= offset);
+        rom.resize(offset, 0);
+        rom.extend_from_slice(bytes);
+    }
+    GBA::builder(&rom).build().unwrap().0
+}
+
+fn peek(gba: &GBA, region: VisibleMemoryRegion, addr: usize, len: usize) -> Vec<u8> {
+    (addr..addr + len).map(|addr| gba.peek_mem(region, addr)).collect()
+}
+
+fn peek_words(gba: &GBA, addr: usize, len: usize) -> Vec<u32> {
+    peek(gba, VisibleMemoryRegion::IWRAM, addr, len * 4).chunks(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect()
+}
+
+#[test]
+fn math() {
+    let mut asm = Assembler::new();
+    let mut out = 0x0300_0000;
+    let mut call = |asm: &mut Assembler, function: u32, args: &[u32], results: &[u32]| {
+        for (reg, arg) in args.iter().enumerate() { asm.load(reg as u32, *arg); }
+        asm.swi(function);
+        for reg in results.iter() {
+            asm.load(4, out).str(*reg, 4);
+            out += 4;
+        }
+    };
+    call(&mut asm, 0x06, &[-7i32 as u32, 2], &[0, 1, 3]);
+    // DivArm takes the denominator first
+    call(&mut asm, 0x07, &[9, -100i32 as u32], &[0, 1, 3]);
+    call(&mut asm, 0x08, &[1000], &[0]);
+    call(&mut asm, 0x08, &[0x4000_0000], &[0]);
+    call(&mut asm, 0x09, &[0x4000], &[0]);
+    call(&mut asm, 0x0A, &[0x100, 0x100], &[0]);
+    call(&mut asm, 0x0A, &[-0x100i32 as u32, 0x100], &[0]);
+    call(&mut asm, 0x0A, &[0, -0x100i32 as u32], &[0]);
+    call(&mut asm, 0x0A, &[-0x100i32 as u32, 0], &[0]);
+    let mut gba = build(&mut asm, &[]);
+    gba.emulate_frame();
+
+    assert_eq!(peek_words(&gba, 0, 14), [
+        -3i32 as u32, -1i32 as u32, 3,
+        -11i32 as u32, -1i32 as u32, 11,
+        31, 0x8000,
+        0x2000, 0x2000, 0x6000, 0xC000, 0x8000, 0,
+    ]);
+}
+
+#[test]
+fn cpu_set() {
+    let data = (0..64).collect::<Vec<u8>>();
+    let mut asm = Assembler::new();
+    // Copies 5 halfwords
+    asm.load(0, DATA).load(1, 0x0200_0000).load(2, 5).swi(0x0B);
+    // Fills 3 words with the first one
+    asm.load(0, DATA).load(1, 0x0200_0100).load(2, 1 << 26 | 1 << 24 | 3).swi(0x0B);
+    // Copies a whole block of 8 words even though only 3 were asked for
+    asm.load(0, DATA).load(1, 0x0200_0200).load(2, 3).swi(0x0C);
+    // Fills 9 words, which rounds up to 16
+    asm.load(0, DATA + 4).load(1, 0x0200_0300).load(2, 1 << 24 | 9).swi(0x0C);
+    let mut gba = build(&mut asm, &[(DATA, &data)]);
+    gba.emulate_frame();
+
+    let ewram = |addr: usize, len: usize| peek(&gba, VisibleMemoryRegion::EWRAM, addr, len);
+    assert_eq!(ewram(0, 12), [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 0]);
+    assert_eq!(ewram(0x100, 16), [0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 0, 0, 0]);
+    assert_eq!(ewram(0x200, 36), [&data[..32], &[0; 4]].concat());
+    assert_eq!(ewram(0x300, 68), [[4, 5, 6, 7].repeat(16), vec![0; 4]].concat());
+}
+
+#[test]
+fn decompress() {
+    // "ABC" as literals, then 9 bytes copied from 3 back, then "X"
+    let lz77 = [0x10, 13, 0, 0, 0x10, b'A', b'B', b'C', 0x60, 0x02, b'X', 0];
+    // A run of 5 "A"s, then "xyz" as literals
+    let rl = [0x30, 8, 0, 0, 0x82, b'A', 0x02, b'x', b'y', b'z', 0, 0];
+    // A root whose children are the leaves "a" and "b", then the bits 0110
+    let huffman = [0x28, 4, 0, 0, 0x01, 0xC0, b'a', b'b', 0x00, 0x00, 0x00, 0x60];
+    // Every node points past the end of the tree
+    let bad_huffman = [0x28, 4, 0, 0, 0x01, 0x00, 0x3F, 0x3F, 0xFF, 0xFF, 0xFF, 0xFF];
+    let diff_8bit = [0x81, 4, 0, 0, 1, 1, 1, 0xFE];
+    let diff_16bit = [0x82, 4, 0, 0, 0x00, 0x01, 0x01, 0x00];
+
+    let mut asm = Assembler::new();
+    let calls = [
+        (0x11, 0x0200_0000), (0x12, 0x0600_0000), (0x14, 0x0200_0100), (0x15, 0x0600_0100),
+        (0x13, 0x0600_0200), (0x13, 0x0600_0300), (0x16, 0x0200_0200), (0x18, 0x0600_0400),
+    ];
+    let sources = [0, 0, 0x10, 0x10, 0x20, 0x30, 0x40, 0x50];
+    for ((function, dest), src) in calls.iter().zip(sources.iter()) {
+        asm.load(0, DATA + src).load(1, *dest).swi(*function);
+    }
+    asm.load(0, 0x0300_0000).load(1, 1).str(1, 0);
+    let mut gba = build(&mut asm, &[
+        (DATA, &lz77), (DATA + 0x10, &rl), (DATA + 0x20, &huffman),
+        (DATA + 0x30, &bad_huffman), (DATA + 0x40, &diff_8bit), (DATA + 0x50, &diff_16bit),
+    ]);
+    gba.emulate_frame();
+
+    let ewram = |addr: usize, len: usize| peek(&gba, VisibleMemoryRegion::EWRAM, addr, len);
+    let vram = |addr: usize, len: usize| peek(&gba, VisibleMemoryRegion::VRAM, addr, len);
+    assert_eq!(ewram(0, 14), b"ABCABCABCABCX\0");
+    assert_eq!(vram(0, 14), b"ABCABCABCABCX\0");
+    assert_eq!(ewram(0x100, 9), b"AAAAAxyz\0");
+    assert_eq!(vram(0x100, 9), b"AAAAAxyz\0");
+    assert_eq!(vram(0x200, 5), b"abba\0");
+    // Gives up on the broken tree instead of walking off into memory
+    assert_eq!(vram(0x300, 4), [0; 4]);
+    assert_eq!(peek_words(&gba, 0, 1), [1]);
+    assert_eq!(ewram(0x200, 5), [1, 2, 3, 1, 0]);
+    assert_eq!(vram(0x400, 5), [0x00, 0x01, 0x01, 0x01, 0]);
+}
+
+#[test]
+fn intr_wait() {
+    let mut asm = Assembler::new();
+    // IRQ handler acknowledging the interrupt in IF and the BIOS flags
+    asm.load(0, 0x0300_7FFC).load(1, 0x0800_1000).str(1, 0);
+    // No flags to wait for returns straight away
+    asm.load(0, 1).load(1, 0).swi(0x04);
+    asm.load(4, 0x0300_0000).load(5, 1).str(5, 4);
+    // Flags already set without discarding them return straight away and get cleared
+    asm.load(0, 0x0300_7FF8).load(1, 0x5).str(1, 0);
+    asm.load(0, 0).load(1, 0x4).swi(0x04);
+    asm.load(0, 0x0300_7FF8).ldrh(1, 0).load(4, 0x0300_0004).str(1, 4);
+    // Count frames with VBlankIntrWait
+    asm.load(0, 0x0400_0004).load(1, 0x8).str(1, 0);
+    asm.load(0, 0x0400_0200).load(1, 0x1).str(1, 0);
+    asm.load(0, 0x0400_0208).load(1, 0x1).str(1, 0);
+    asm.load(5, 0);
+    let wait = asm.label();
+    asm.swi(0x05).add(5, 5, 1).load(4, 0x0300_0008).str(5, 4).b(wait);
+
+    let mut handler = Assembler::new();
+    handler.load(2, 0x0400_0202).ldrh(1, 2).strh_inc(1, 2);
+    handler.load(2, 0x0300_7FF8).ldrh(3, 2);
+    handler.word(0xE183_3001); // orr r3, r3, r1
+    handler.strh_inc(3, 2);
+    handler.word(0xE12F_FF1E); // bx lr
+    let handler = handler.finish();
+    let mut gba = build(&mut asm, &[(0x0800_1000, &handler)]);
+
+    for _ in 0..5 { gba.emulate_frame() }
+    let [marker, bios_if, frames] = peek_words(&gba, 0, 3)[..] else { unreachable!() };
+    assert_eq!((marker, bios_if), (1, 0x1));
+    for _ in 0..10 { gba.emulate_frame() }
+    assert_eq!(peek_words(&gba, 8, 1), [frames + 10]);
+}
+
+#[test]
+fn wrapping_addresses() {
+    let mut asm = Assembler::new();
+    // Parameters that run past the top of the address space wrap around to the BIOS instead of overflowing
+    asm.load(0, 0xFFFF_FFF0).load(1, 0xFFFF_FFF8).load(2, 1).swi(0x0E);
+    asm.load(0, 0xFFFF_FFFC).load(1, 0xFFFF_FFFE).load(2, 1).load(3, 2).swi(0x0F);
+    asm.load(4, 0x0300_0000).load(5, 1).str(5, 4);
+    let mut gba = build(&mut asm, &[]);
+    gba.emulate_frame();
+    assert_eq!(peek_words(&gba, 0, 1), [1]);
+}
@@ -0,0 +1,80 @@
+mod harness;
+
+use core::gba::{GBA, LocalLink, VisibleMemoryRegion};
+use harness::Assembler;
+
+// Each unit writes its registers as (address, halfword) pairs in order and then waits
+fn linked_units(setups: &[&[(u32, u16)]]) -> Vec<GBA> {
+    let links = LocalLink::new(setups.len());
+    setups.iter().zip(links).map(|(setup, link)| {
+        let mut asm = Assembler::new();
+        for (addr, value) in setup.iter() { asm.load(0, *addr).load(1, *value as u32).strh_inc(1, 0); }
+        let rom = asm.finish();
+        GBA::builder(&rom).link(Box::new(link)).build().unwrap().0
+    }).collect()
+}
+
+fn io16(gba: &GBA, addr: usize) -> u16 {
+    gba.peek_mem(VisibleMemoryRegion::IO, addr) as u16 | (gba.peek_mem(VisibleMemoryRegion::IO, addr + 1) as u16) << 8
+}
+
+// Children first, so that they are waiting by the time the parent starts the transfer
+fn run_frames(units: &mut [GBA], frames: usize) {
+    for _ in 0..frames {
+        for gba in units.iter_mut().rev() { gba.emulate_frame() }
+    }
+}
+
+#[test]
+fn normal_mode() {
+    // 32 bit transfers with an interrupt at the end, the parent supplying the clock
+    let mut units = linked_units(&[
+        &[(0x0400_0120, 0x5678), (0x0400_0122, 0x1234), (0x0400_0128, 0x5001), (0x0400_0128, 0x5081)],
+        &[(0x0400_0120, 0xBABE), (0x0400_0122, 0xCAFE), (0x0400_0128, 0x5000), (0x0400_0128, 0x5080)],
+    ]);
+    run_frames(&mut units, 2);
+
+    let received = |gba: &GBA| (io16(gba, 0x122) as u32) << 16 | io16(gba, 0x120) as u32;
+    assert_eq!(received(&units[0]), 0xCAFE_BABE);
+    assert_eq!(received(&units[1]), 0x1234_5678);
+    for gba in units.iter() {
+        assert_eq!(io16(gba, 0x128) & 0x80, 0);
+        assert_eq!(io16(gba, 0x202) & 0x80, 0x80);
+    }
+}
+
+#[test]
+fn multiplayer() {
+    let mut units = linked_units(&[
+        &[(0x0400_012A, 0x1111), (0x0400_0128, 0x6003), (0x0400_0128, 0x6083)],
+        &[(0x0400_012A, 0x2222), (0x0400_0128, 0x6003)],
+        &[(0x0400_012A, 0x3333), (0x0400_0128, 0x6003)],
+    ]);
+    run_frames(&mut units, 2);
+
+    for (id, gba) in units.iter().enumerate() {
+        let data = [0x120, 0x122, 0x124, 0x126].map(|addr| io16(gba, addr));
+        // Nothing is connected as the fourth unit
+        assert_eq!(data, [0x1111, 0x2222, 0x3333, 0xFFFF]);
+        let cnt = io16(gba, 0x128);
+        assert_eq!(cnt & 0x80, 0);
+        // All units ready, along with this one's ID
+        assert_eq!(cnt & 0x38, 0x8 | (id as u16) << 4);
+    }
+}
+
+#[test]
+fn peeking_uart_data() {
+    // The first unit sends a byte for the second one to receive
+    let mut units = linked_units(&[
+        &[(0x0400_0128, 0x3403), (0x0400_012A, 0x42)],
+        &[(0x0400_0128, 0x3803)],
+    ]);
+    run_frames(&mut units, 2);
+
+    // The byte stays in the FIFO however often it is looked at
+    for _ in 0..3 {
+        assert_eq!(units[1].peek_mem(VisibleMemoryRegion::IO, 0x12A), 0x42);
+        assert_eq!(io16(&units[1], 0x128) & 0x20, 0);
+    }
+}
@@ -0,0 +1,70 @@
+mod harness;
+
+use core::flume;
+use core::gba::{GBA, GBAError, KEYINPUT, Movie, MovieError, StateError, VisibleMemoryRegion};
+use harness::Assembler;
+
+// Keeps copying KEYINPUT to 0x02000000
+fn rom() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0130).load(2, 0x0200_0000);
+    let label = asm.label();
+    asm.ldrh(1, 0).str(1, 2).b(label).finish()
+}
+
+#[test]
+fn record_and_play() {
+    let rom = rom();
+    let (keypad_tx, keypad_rx) = flume::unbounded();
+    let (mut gba, _, _) = GBA::builder(&rom).keypad_rx(keypad_rx).record_movie().build().unwrap();
+    for frame in 0..70 {
+        if frame == 3 { keypad_tx.send((KEYINPUT::A, true)).unwrap() }
+        if frame == 40 { keypad_tx.send((KEYINPUT::A, false)).unwrap() }
+        gba.emulate_frame();
+    }
+    let status = gba.movie_status().unwrap();
+    assert!(status.recording);
+    assert_eq!((status.frame, status.len), (70, 70));
+    let data = gba.finish_movie().unwrap().save();
+    assert!(gba.movie_status().is_none());
+
+    // Keys from the channel are ignored while playing back
+    let (keypad_tx, keypad_rx) = flume::unbounded();
+    let movie = Movie::load(&data).unwrap();
+    assert_eq!(movie.len(), 70);
+    let (mut gba, _, _) = GBA::builder(&rom).keypad_rx(keypad_rx).play_movie(movie).build().unwrap();
+    keypad_tx.send((KEYINPUT::B, true)).unwrap();
+    let state = gba.save_state();
+    for frame in 0..70 {
+        gba.emulate_frame();
+        let ewram = gba.peek_mem(VisibleMemoryRegion::EWRAM, 0);
+        assert_eq!(ewram & 0x3, if (3..40).contains(&frame) { 0x2 } else { 0x3 }, "frame {}", frame);
+    }
+    let status = gba.movie_status().unwrap();
+    assert!(status.finished());
+    assert_eq!(status.desync, None);
+    assert_eq!(gba.load_state(&state), Ok(()));
+
+    // Changing RAM is caught by the next checksum
+    let (mut gba, _, _) = GBA::builder(&rom).play_movie(Movie::load(&data).unwrap()).build().unwrap();
+    assert_eq!(gba.load_state(&state), Err(StateError::MovieActive));
+    for frame in 0..70 {
+        if frame == 10 { gba.poke_mem(VisibleMemoryRegion::EWRAM, 0x100, 0xFF) }
+        gba.emulate_frame();
+    }
+    assert_eq!(gba.movie_status().unwrap().desync, Some(59));
+
+    let other_rom = Assembler::new().finish();
+    let result = GBA::builder(&other_rom).play_movie(Movie::load(&data).unwrap()).build();
+    assert!(matches!(result, Err(GBAError::MovieRomMismatch)));
+    let bios = vec![0; 0x4000];
+    let result = GBA::builder(&rom).bios(&bios).play_movie(Movie::load(&data).unwrap()).build();
+    assert!(matches!(result, Err(GBAError::MovieBiosMismatch(true))));
+    assert!(Movie::load(&data[..data.len() - 1]).is_err());
+    // The RTC's date and time come right after the magic, version, ROM checksum and BIOS flag
+    for (byte, value) in [(0, 0x1A), (1, 0x13), (4, 0x24), (6, 0x60)].iter() {
+        let mut data = data.clone();
+        data[13 + byte] = *value;
+        assert!(matches!(Movie::load(&data), Err(MovieError::Corrupt)), "byte {} = {:02X}", byte, value);
+    }
+}
@@ -0,0 +1,56 @@
+mod harness;
+
+use core::gba::MGBALogLevel;
+use harness::{Assembler, Harness};
+
+// Fills the mode 3 bitmap with every color in order
+fn bitmap_rom() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0000).load(1, 0x0403).str(1, 0);
+    asm.load(0, 0x0600_0000).load(1, 0).load(2, 240 * 160);
+    let fill = asm.label();
+    asm.strh_inc(1, 0).add(1, 1, 1).subs(2, 2, 1).bne(fill);
+    asm.finish()
+}
+
+// Mode 0 with no backgrounds, so only the backdrop color shows
+fn backdrop_rom() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0500_0000).load(1, 0x7C1F).strh_inc(1, 0);
+    asm.load(0, 0x0400_0000).load(1, 0).str(1, 0);
+    asm.finish()
+}
+
+#[test]
+fn framebuffer_goldens() {
+    let goldens: [(&str, fn() -> Vec<u8>, usize, u64); 2] = [
+        ("bitmap", bitmap_rom, 8, 0x2B54_5003_AF79_91A5),
+        ("backdrop", backdrop_rom, 2, 0x520C_3ACA_3FC1_1325),
+    ];
+    for (name, rom, frames, golden) in goldens.iter() {
+        let hash = Harness::new(&rom()).run_frames(*frames).frame_hash();
+        assert_eq!(hash, *golden, "{} framebuffer hash changed", name);
+    }
+}
+
+#[test]
+fn frames_are_deterministic() {
+    let rom = bitmap_rom();
+    let hashes: Vec<_> = (0..2).map(|_| Harness::new(&rom).run_frames(3).frame_hash()).collect();
+    assert_eq!(hashes[0], hashes[1]);
+}
+
+#[test]
+fn mgba_debug_messages() {
+    let rom = Assembler::new()
+        .debug_print(3, "Test 1 PASS")
+        .debug_print(1, "Test 2 FAIL")
+        .finish();
+    let mut harness = Harness::new(&rom);
+    harness.run_frames(1);
+    assert_eq!(harness.debug_messages(), vec![
+        (MGBALogLevel::Info, "Test 1 PASS".to_string()),
+        (MGBALogLevel::Error, "Test 2 FAIL".to_string()),
+    ]);
+    assert!(harness.debug_messages().is_empty());
+}
@@ -0,0 +1,49 @@
+mod harness;
+
+use std::sync::{Arc, Mutex};
+
+use core::gba::GBA;
+use harness::busy_rom;
+
+// The state and framebuffer after each frame
+fn run(gba: &mut GBA, pixels: &Arc<Mutex<Vec<u16>>>, frames: usize) -> Vec<(Vec<u8>, Vec<u16>)> {
+    (0..frames).map(|_| {
+        gba.emulate_frame();
+        (gba.save_state(), pixels.lock().unwrap().clone())
+    }).collect()
+}
+
+#[test]
+fn rewind_and_replay() {
+    let rom = busy_rom();
+    let (mut gba, pixels, _) = GBA::builder(&rom).build().unwrap();
+    gba.enable_rewind(8, 1);
+    let frames = run(&mut gba, &pixels, 10);
+
+    // Every snapshot but the newest is decoded from a chain of deltas
+    assert_eq!(gba.rewind(3), 3);
+    assert_eq!(gba.save_state(), frames[6].0);
+    assert_eq!(gba.rewind(2), 2);
+    assert_eq!(gba.save_state(), frames[4].0);
+    assert_eq!(run(&mut gba, &pixels, 5), frames[5..].to_vec());
+}
+
+#[test]
+fn rewind_intervals_and_capacity() {
+    let rom = busy_rom();
+    let (mut gba, pixels, _) = GBA::builder(&rom).build().unwrap();
+    // Snapshots after frames 1, 5 and 9, so rewinding 3 frames has to go back 5
+    gba.enable_rewind(8, 4);
+    let frames = run(&mut gba, &pixels, 10);
+    assert_eq!(gba.rewind(3), 5);
+    assert_eq!(gba.save_state(), frames[4].0);
+    assert_eq!(run(&mut gba, &pixels, 5), frames[5..].to_vec());
+
+    // Only the newest few snapshots are kept
+    let (mut gba, pixels, _) = GBA::builder(&rom).build().unwrap();
+    gba.enable_rewind(4, 1);
+    let frames = run(&mut gba, &pixels, 10);
+    assert_eq!(gba.rewind(100), 3);
+    assert_eq!(gba.save_state(), frames[6].0);
+    assert_eq!(gba.rewind(1), 0);
+}
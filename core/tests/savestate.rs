@@ -0,0 +1,75 @@
+mod harness;
+
+use core::gba::{GBA, StateError};
+use harness::busy_rom;
+
+#[test]
+fn round_trip() {
+    let rom = busy_rom();
+    let (mut gba, pixels, _) = GBA::builder(&rom).build().unwrap();
+    for _ in 0..3 { gba.emulate_frame() }
+    let state = gba.save_state();
+    let run = |gba: &mut GBA| {
+        for _ in 0..5 { gba.emulate_frame() }
+        (gba.save_state(), pixels.lock().unwrap().clone())
+    };
+    let expected = run(&mut gba);
+
+    gba.load_state(&state).unwrap();
+    assert_eq!(gba.save_state(), state);
+    assert_eq!(run(&mut gba), expected);
+
+    // Loads into a different instance just the same
+    let (mut other, _, _) = GBA::builder(&rom).build().unwrap();
+    other.load_state(&state).unwrap();
+    assert_eq!(other.save_state(), state);
+}
+
+#[test]
+fn corrupt_states() {
+    let rom = busy_rom();
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+    for _ in 0..3 { gba.emulate_frame() }
+    let state = gba.save_state();
+    gba.emulate_frame();
+    let current = gba.save_state();
+
+    assert_eq!(gba.load_state(&state[..state.len() / 2]), Err(StateError::UnexpectedEnd));
+    assert_eq!(gba.load_state(&state[..8]), Err(StateError::UnexpectedEnd));
+    assert_eq!(gba.load_state(b"not a state"), Err(StateError::InvalidHeader));
+    let mut trailing = state.clone();
+    trailing.push(0);
+    assert_eq!(gba.load_state(&trailing), Err(StateError::InvalidValue));
+
+    // EWRAM one byte short, with everything after it still lined up
+    let len = 0x40000u64.to_le_bytes();
+    let pos = state.windows(len.len()).position(|bytes| bytes == len).unwrap();
+    let mut short = state.clone();
+    short[pos..pos + len.len()].copy_from_slice(&0x3FFFFu64.to_le_bytes());
+    short.remove(pos + len.len());
+    assert_eq!(gba.load_state(&short), Err(StateError::InvalidValue));
+
+    // Fields that registers can only write in range, found by writing one and seeing which byte changes
+    let field_pos = |gba: &mut GBA, addr: u32, value: u8| {
+        let before = gba.save_state();
+        gba.write_mem(addr, value);
+        let after = gba.save_state();
+        gba.load_state(&before).unwrap();
+        let diffs = (0..before.len()).filter(|&i| before[i] != after[i]).collect::<Vec<_>>();
+        assert_eq!(diffs.len(), 1);
+        diffs[0]
+    };
+    // The noise shift and DMA0 start timing
+    for &(addr, value, invalid) in [(0x0400_007C, 0x50, 0x10), (0x0400_00BB, 0x10, 4)].iter() {
+        gba.load_state(&current).unwrap();
+        let pos = field_pos(&mut gba, addr, value);
+        let mut corrupt = current.clone();
+        corrupt[pos] = invalid;
+        assert_eq!(gba.load_state(&corrupt), Err(StateError::InvalidValue));
+    }
+
+    // Failed loads leave the emulator as it was
+    assert_eq!(gba.save_state(), current);
+    gba.emulate_frame();
+    gba.load_state(&state).unwrap();
+}
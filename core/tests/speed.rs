@@ -0,0 +1,97 @@
+mod harness;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use core::flume;
+use core::gba::{self, AudioSink, GBA, VisibleMemoryRegion};
+use harness::{Assembler, CollectingSink};
+
+// Turns on sound and makes the backdrop white
+fn rom() -> Vec<u8> {
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0084).load(1, 0x80).str(1, 0);
+    asm.load(0, 0x0500_0000).load(1, 0x7FFF).str(1, 0);
+    let label = asm.label();
+    asm.b(label).finish()
+}
+
+#[test]
+fn skip_rendering() {
+    let rom = rom();
+    let (render_tx, render_rx) = flume::unbounded();
+    let (mut gba, pixels, _) = GBA::builder(&rom).render_tx(render_tx).build().unwrap();
+    // Only whole frames are skipped, so it may take until the next one
+    gba.skip_rendering(true);
+    for _ in 0..2 { gba.emulate_frame() }
+    let last_frame = pixels.lock().unwrap().clone();
+    render_rx.try_iter().count();
+    gba.poke_mem(VisibleMemoryRegion::Palette, 1, 0x00);
+    for _ in 0..3 { gba.emulate_frame() }
+    assert_eq!(*pixels.lock().unwrap(), last_frame);
+    assert_eq!(render_rx.try_iter().count(), 0);
+
+    gba.skip_rendering(false);
+    for _ in 0..2 { gba.emulate_frame() }
+    assert_ne!(*pixels.lock().unwrap(), last_frame);
+    assert!(render_rx.try_iter().count() > 0);
+}
+
+struct CountingSink(Arc<AtomicUsize>);
+
+impl AudioSink for CountingSink {
+    fn push_sample(&mut self, _left_sample: i16, _right_sample: i16) { self.0.fetch_add(1, Ordering::Relaxed); }
+
+    fn sample_rate(&self) -> usize { gba::AUDIO_SAMPLE_RATE }
+}
+
+#[test]
+fn audio_speed() {
+    let rom = rom();
+    let count = Arc::new(AtomicUsize::new(0));
+    let (mut gba, _, _) = GBA::builder(&rom).audio_sink(Box::new(CountingSink(Arc::clone(&count)))).build().unwrap();
+    let samples_per_frame = gba::CLOCKS_PER_FRAME * gba::AUDIO_SAMPLE_RATE / gba::CLOCK_FREQ;
+    let count_frame = |gba: &mut GBA| {
+        count.store(0, Ordering::Relaxed);
+        gba.emulate_frame();
+        count.load(Ordering::Relaxed)
+    };
+    count_frame(&mut gba);
+    assert!((samples_per_frame..=samples_per_frame + 1).contains(&count_frame(&mut gba)));
+
+    // Twice as fast leaves half as many samples for each frame
+    gba.set_audio_speed(2.0);
+    count_frame(&mut gba);
+    assert!((samples_per_frame / 2..=samples_per_frame / 2 + 1).contains(&count_frame(&mut gba)));
+    gba.set_audio_speed(0.5);
+    count_frame(&mut gba);
+    assert!((samples_per_frame * 2..=samples_per_frame * 2 + 1).contains(&count_frame(&mut gba)));
+}
+
+#[test]
+fn turbo_audio_low_pass() {
+    // A square wave on the first tone channel that's high for one full speed sample and low for the next
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0084).load(1, 0x80).str(1, 0);
+    asm.load(0, 0x0400_0080).load(1, 0x0002_1177).str(1, 0);
+    asm.load(0, 0x0400_0060).load(1, 0xF080_0000).str(1, 0);
+    asm.load(0, 0x0400_0064).load(1, 0x87F8).str(1, 0);
+    let label = asm.label();
+    let rom = asm.b(label).finish();
+    let samples = Arc::new(Mutex::new(Vec::new()));
+    let (mut gba, _, _) = GBA::builder(&rom).audio_sink(Box::new(CollectingSink(Arc::clone(&samples)))).build().unwrap();
+    let frame_samples = |gba: &mut GBA| {
+        gba.emulate_frame();
+        samples.lock().unwrap().drain(..).map(|(left, _)| left).collect::<Vec<_>>()
+    };
+    frame_samples(&mut gba);
+    let full_speed = frame_samples(&mut gba);
+    let (low, high) = (*full_speed.iter().min().unwrap(), *full_speed.iter().max().unwrap());
+    assert!(high - low > 0x100);
+
+    // Taking every other sample would only ever see the high or the low half of the wave
+    gba.set_audio_speed(2.0);
+    frame_samples(&mut gba);
+    let middle = (low + high) / 2;
+    assert!(frame_samples(&mut gba).iter().all(|&sample| (sample - middle).abs() <= 2));
+}
@@ -0,0 +1,39 @@
+mod harness;
+
+use core::gba::{GBA, VisibleMemoryRegion};
+use harness::Assembler;
+
+// Short enough for the DMA out of ROM to keep up with the display
+const LINE_HALFWORDS: usize = 64;
+const LINES: usize = 160;
+
+#[test]
+fn captures_one_frame() {
+    // Waits for a line after the last capture line so that the capture covers a whole frame
+    let mut asm = Assembler::new();
+    asm.load(0, 0x0400_0006);
+    let wait = asm.label();
+    asm.ldrh(1, 0).word(0xE351_00C8).bne(wait); // cmp r1, #200
+    // DMA3 copies a line of "video" from ROM to EWRAM on every capture line
+    asm.load(0, 0x0400_00D4).load(1, 0x0800_1000).str(1, 0);
+    asm.load(0, 0x0400_00D8).load(1, 0x0200_0000).str(1, 0);
+    asm.load(0, 0x0400_00DC).load(1, 0xB200_0000 | LINE_HALFWORDS as u32).str(1, 0);
+    let mut rom = asm.finish();
+    rom.resize(0x1000, 0);
+    let video = (0..(LINES + 1) * LINE_HALFWORDS).map(|i| i as u16 | 0x8000).collect::<Vec<_>>();
+    rom.extend(video.iter().flat_map(|pixel| pixel.to_le_bytes()));
+    let (mut gba, _, _) = GBA::builder(&rom).build().unwrap();
+
+    let captured = |gba: &GBA| (0..(LINES + 1) * LINE_HALFWORDS).map(|i| {
+        gba.peek_mem(VisibleMemoryRegion::EWRAM, i * 2) as u16 |
+            (gba.peek_mem(VisibleMemoryRegion::EWRAM, i * 2 + 1) as u16) << 8
+    }).collect::<Vec<_>>();
+    for _ in 0..3 { gba.emulate_frame() }
+    // Lines 2 through 161 give exactly one line per visible row of the frame
+    let expected = [&video[..LINES * LINE_HALFWORDS], &[0; LINE_HALFWORDS]].concat();
+    assert_eq!(captured(&gba), expected);
+    // Then the channel turns itself off instead of capturing the next frame
+    assert_eq!(gba.peek_mem(VisibleMemoryRegion::IO, 0xDF) & 0x80, 0);
+    for _ in 0..2 { gba.emulate_frame() }
+    assert_eq!(captured(&gba), expected);
+}
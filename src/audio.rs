@@ -0,0 +1,58 @@
+extern crate sdl2;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use core::gba::{self, RingBufferSink, SampleBuffer};
+
+pub struct Audio {
+    buffer: SampleBuffer,
+    last_sample: (i16, i16),
+}
+
+impl Audio {
+    const DESIRED_SPEC: AudioSpecDesired = AudioSpecDesired {
+        freq: Some(48000),
+        channels: Some(2),
+        samples: Some(1024),
+    };
+
+    const VOLUME_FACTOR: i16 = 8;
+    // Slow motion produces a whole frame's samples at once, several times as many as at full speed
+    pub const BUFFER_CAPACITY: usize = gba::AUDIO_BUFFER_LEN * 2;
+
+    // The returned buffer is shared with the device so that the GBA thread can keep pace with it
+    pub fn new() -> (AudioDevice<Audio>, RingBufferSink, SampleBuffer) {
+        let sdl_ctx = sdl2::init().unwrap();
+        let audio_subsystem = sdl_ctx.audio().unwrap();
+
+        // The device may not play at the rate that was asked for
+        let mut sink = None;
+        let device = audio_subsystem
+        .open_playback(None, &Audio::DESIRED_SPEC, |spec| {
+            let (ring_buffer_sink, buffer) = RingBufferSink::new(Audio::BUFFER_CAPACITY, spec.freq as usize);
+            sink = Some((ring_buffer_sink, buffer.clone()));
+            Audio {
+                buffer,
+                last_sample: (0, 0),
+            }
+        }).unwrap();
+        device.resume();
+        let (sink, shared_buffer) = sink.unwrap();
+        (device, sink, shared_buffer)
+    }
+}
+
+impl AudioCallback for Audio {
+    type Channel = i16;
+
+    fn callback(&mut self, out: &mut [i16]) {
+        let mut buffer = self.buffer.lock().unwrap();
+        for x in out.chunks_exact_mut(2) {
+            // Holding the last sample when running dry avoids the pop of dropping to silence
+            if let Some(sample) = buffer.pop_front() { self.last_sample = sample }
+            let (left_sample, right_sample) = self.last_sample;
+            x[0] = Audio::VOLUME_FACTOR * left_sample;
+            x[1] = Audio::VOLUME_FACTOR * right_sample;
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use imgui::*;
+
+use core::flume::Sender;
+use core::gba::{GBA, AudioChannel, AudioChannelState, AudioState};
+
+#[derive(Debug)]
+pub enum AudioChannelCommand {
+    Show,
+    Hide,
+    Mute(AudioChannel, bool),
+    Solo(AudioChannel, bool),
+    StartRecording { path: PathBuf, channels: bool },
+    StopRecording,
+}
+
+// Runs on the GBA thread and sends the state of every channel after each frame while shown
+pub struct AudioChannelWatcher {
+    state_tx: Sender<AudioState>,
+    shown: bool,
+}
+
+impl AudioChannelWatcher {
+    pub fn new(state_tx: Sender<AudioState>) -> AudioChannelWatcher {
+        AudioChannelWatcher {
+            state_tx,
+            shown: false,
+        }
+    }
+
+    pub fn handle_command(&mut self, gba: &mut GBA, command: AudioChannelCommand) {
+        match command {
+            AudioChannelCommand::Show => { self.shown = true; gba.enable_audio_scope(true) },
+            AudioChannelCommand::Hide => { self.shown = false; gba.enable_audio_scope(false) },
+            AudioChannelCommand::Mute(channel, muted) => gba.mute_audio_channel(channel, muted),
+            AudioChannelCommand::Solo(channel, soloed) => gba.solo_audio_channel(channel, soloed),
+            AudioChannelCommand::StartRecording { path, channels } => gba.start_audio_recording(&path, channels)
+                .unwrap_or_else(|err| eprintln!("Unable to record audio to {}: {}", path.display(), err)),
+            AudioChannelCommand::StopRecording => gba.stop_audio_recording()
+                .unwrap_or_else(|err| eprintln!("Unable to finish audio recording: {}", err)),
+        }
+        self.update(gba);
+    }
+
+    pub fn update(&self, gba: &GBA) {
+        if self.shown { self.state_tx.send(gba.audio_state()).ok(); }
+    }
+}
+
+pub struct AudioChannelWindow {
+    pub open: bool,
+    shown: bool,
+    state: Option<AudioState>,
+    recording_path: ImString,
+    record_channels: bool,
+}
+
+impl AudioChannelWindow {
+    const SCOPE_HEIGHT: f32 = 48.0;
+    const FIFO_LEN: usize = 32;
+
+    const MAX_PATH_LEN: usize = 1024;
+
+    pub fn new(recording_path: PathBuf) -> AudioChannelWindow {
+        let mut path = ImString::new(recording_path.to_string_lossy());
+        path.reserve(AudioChannelWindow::MAX_PATH_LEN);
+        AudioChannelWindow {
+            open: false,
+            shown: false,
+            state: None,
+            recording_path: path,
+            record_channels: false,
+        }
+    }
+
+    pub fn update(&mut self, state: AudioState) { self.state = Some(state) }
+
+    fn describe(state: &AudioChannelState) -> String {
+        match *state {
+            AudioChannelState::Tone { playing: false, .. } | AudioChannelState::Wave { playing: false, .. } |
+            AudioChannelState::Noise { playing: false, .. } => "Off".to_string(),
+            AudioChannelState::Tone { frequency, duty, volume, .. } =>
+                format!("{:.1} Hz, {}% duty, volume {}/15", frequency, duty * 100.0, volume),
+            AudioChannelState::Wave { frequency, volume, two_banks, .. } =>
+                format!("{:.1} Hz, {}% volume{}", frequency, volume, if two_banks { ", both banks" } else { "" }),
+            AudioChannelState::Noise { frequency, volume, lfsr_width, .. } =>
+                format!("{:.0} Hz, {}-bit LFSR, volume {}/15", frequency, lfsr_width, volume),
+            AudioChannelState::DMASound { volume, timer, .. } => format!("Timer {}, {}% volume", timer, volume),
+        }
+    }
+
+    pub fn render(&mut self, ui: &Ui) -> Vec<AudioChannelCommand> {
+        let mut commands = Vec::new();
+        if self.open != self.shown {
+            self.shown = self.open;
+            commands.push(if self.open { AudioChannelCommand::Show } else { AudioChannelCommand::Hide });
+        }
+        if !self.open { return commands }
+
+        let mut open = true;
+        let (state, recording_path, record_channels) = (&mut self.state, &mut self.recording_path, &mut self.record_channels);
+        Window::new(im_str!("Audio Channels"))
+        .size([480.0, 640.0], Condition::FirstUseEver)
+        .opened(&mut open)
+        .build(ui, || {
+            let state = match state {
+                Some(state) => state,
+                None => { ui.text("Waiting for the next frame"); return },
+            };
+            ui.input_text(im_str!("WAV File"), recording_path).build();
+            ui.checkbox(im_str!("Separate Channels"), record_channels);
+            ui.same_line(0.0);
+            if state.recording {
+                if ui.button(im_str!("Stop Recording"), [0.0, 0.0]) { commands.push(AudioChannelCommand::StopRecording) }
+            } else if ui.button(im_str!("Start Recording"), [0.0, 0.0]) {
+                let path = PathBuf::from(recording_path.to_str());
+                commands.push(AudioChannelCommand::StartRecording { path, channels: *record_channels });
+            }
+            if !state.master_enable { ui.text_colored([1.0, 0.3, 0.3, 1.0], "Sound is turned off in SOUNDCNT_X") }
+            for (&channel, channel_state) in AudioChannel::ALL.iter().zip(state.channels.iter()) {
+                let i = channel as usize;
+                ui.separator();
+                ui.text(channel.name());
+                ui.same_line(120.0);
+                if ui.checkbox(&ImString::new(format!("Mute##{}", i)), &mut state.muted[i]) {
+                    commands.push(AudioChannelCommand::Mute(channel, state.muted[i]));
+                }
+                ui.same_line(0.0);
+                if ui.checkbox(&ImString::new(format!("Solo##{}", i)), &mut state.soloed[i]) {
+                    commands.push(AudioChannelCommand::Solo(channel, state.soloed[i]));
+                }
+                ui.text(AudioChannelWindow::describe(channel_state));
+
+                // Samples are at most 128 either way before each channel's volume is applied
+                let samples: Vec<f32> = state.scope.iter().map(|samples| samples[i] as f32).collect();
+                ui.plot_lines(&ImString::new(format!("##Scope{}", i)), &samples)
+                .scale_min(-128.0)
+                .scale_max(128.0)
+                .graph_size([ui.content_region_avail()[0], AudioChannelWindow::SCOPE_HEIGHT])
+                .build();
+
+                match channel_state {
+                    AudioChannelState::Wave { wave_ram, playing_bank, .. } => for (bank, ram) in wave_ram.iter().enumerate() {
+                        let bytes: Vec<String> = ram.iter().map(|byte| format!("{:02X}", byte)).collect();
+                        let playing = if bank == *playing_bank { " (playing)" } else { "" };
+                        ui.text(format!("Bank {}: {}{}", bank, bytes.join(" "), playing));
+                    },
+                    AudioChannelState::DMASound { fifo_len, .. } => {
+                        let overlay = ImString::new(format!("FIFO {}/{}", fifo_len, AudioChannelWindow::FIFO_LEN));
+                        ProgressBar::new(*fifo_len as f32 / AudioChannelWindow::FIFO_LEN as f32)
+                        .overlay_text(&overlay)
+                        .build(ui);
+                    },
+                    _ => (),
+                }
+            }
+        });
+        self.open = open;
+        commands
+    }
+}
@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use imgui::*;
+
+use core::flume::Sender;
+use core::gba::{GBA, Cheat};
+
+#[derive(Debug)]
+pub enum CheatCommand {
+    Show,
+    Hide,
+    // Index into the cheat list
+    SetEnabled(usize, bool),
+}
+
+// Runs on the GBA thread and sends the cheat list whenever it's shown or changed, since it doesn't change by itself
+pub struct CheatWatcher {
+    cheats_tx: Sender<Vec<Cheat>>,
+    shown: bool,
+}
+
+impl CheatWatcher {
+    pub fn new(cheats_tx: Sender<Vec<Cheat>>) -> CheatWatcher {
+        CheatWatcher {
+            cheats_tx,
+            shown: false,
+        }
+    }
+
+    pub fn handle_command(&mut self, gba: &mut GBA, command: CheatCommand) {
+        match command {
+            CheatCommand::Show => self.shown = true,
+            CheatCommand::Hide => self.shown = false,
+            CheatCommand::SetEnabled(index, enabled) => { gba.set_cheat_enabled(index, enabled); },
+        }
+        if self.shown { self.cheats_tx.send(gba.cheats().to_vec()).ok(); }
+    }
+}
+
+pub struct CheatWindow {
+    pub open: bool,
+    shown: bool,
+    cheats: Option<Vec<Cheat>>,
+    cheat_file: PathBuf,
+}
+
+impl CheatWindow {
+    pub fn new(cheat_file: PathBuf) -> CheatWindow {
+        CheatWindow {
+            open: false,
+            shown: false,
+            cheats: None,
+            cheat_file,
+        }
+    }
+
+    pub fn update(&mut self, cheats: Vec<Cheat>) { self.cheats = Some(cheats) }
+
+    pub fn render(&mut self, ui: &Ui) -> Vec<CheatCommand> {
+        let mut commands = Vec::new();
+        if self.open != self.shown {
+            self.shown = self.open;
+            commands.push(if self.open { CheatCommand::Show } else { CheatCommand::Hide });
+        }
+        if !self.open { return commands }
+
+        let mut open = true;
+        let (cheats, cheat_file) = (&mut self.cheats, &self.cheat_file);
+        Window::new(im_str!("Cheats"))
+        .size([360.0, 320.0], Condition::FirstUseEver)
+        .opened(&mut open)
+        .build(ui, || {
+            let cheats = match cheats {
+                Some(cheats) => cheats,
+                None => { ui.text("Loading..."); return },
+            };
+            if cheats.is_empty() { ui.text(format!("No cheats, they're listed in {}", cheat_file.display())) }
+            for (i, cheat) in cheats.iter_mut().enumerate() {
+                if ui.checkbox(&ImString::new(format!("##{}", i)), &mut cheat.enabled) {
+                    commands.push(CheatCommand::SetEnabled(i, cheat.enabled));
+                }
+                ui.same_line(0.0);
+                let label = ImString::new(format!("{} ({:?})", cheat.name, cheat.format));
+                TreeNode::new(&ImString::new(format!("Cheat{}", i))).label(&label).build(ui, || {
+                    for code in cheat.codes() { ui.text(code) }
+                });
+            }
+        });
+        self.open = open;
+        commands
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use core::gba;
+use core::simplelog::*;
+
+pub const USAGE: &str = "\
+Usage: gba-emulator [OPTIONS] <ROM>
+
+Options:
+    --bios <FILE>             BIOS dump to boot from, BIOS calls are emulated when omitted
+    --save-dir <DIR>          Directory for cart saves and save states, defaults to the ROM's directory
+    --scale <N>               Initial window scale
+    --speed <PERCENT>         Initial emulation speed (10-1000), e.g. 50 for slow motion, hold Tab for turbo
+    --log-level <LEVEL>       off, error, warn, info, debug or trace
+    --log <MODULE>=<LEVEL>    Log level for a single module, e.g. core::cpu=trace, can be repeated
+    --log-file <FILE>         Write the log to a file instead of the terminal
+    --headless                Run without a window or audio
+    --frames <N>              Number of frames to run in headless mode
+    --screenshot <FILE>       Save the last frame as a PNG in headless mode
+    --gdb <ADDR>              Wait for GDB to connect and debug without a window
+    --trace <FILE>            Write every executed instruction and the registers it changed to a file
+    --link-host <ADDR>        Wait for another emulator to connect a link cable
+    --link-connect <ADDR>     Connect a link cable to a hosting emulator
+    --record-movie <FILE>     Record inputs from power on to a movie, saved on exit
+    --play-movie <FILE>       Play back a movie, in headless mode until it ends if --frames is omitted
+    --record-audio <FILE>     Record audio from power on to a 16-bit WAV file, finished on exit
+    --record-channels         Also record each audio channel to its own file next to --record-audio
+    --help                    Print this message";
+
+pub enum LinkOption {
+    Host(String),
+    Connect(String),
+}
+
+pub struct Options {
+    pub rom: PathBuf,
+    pub bios: Option<PathBuf>,
+    pub save_dir: PathBuf,
+    pub scale: usize,
+    pub speed: usize,
+    pub log_level: LevelFilter,
+    pub log_filters: Vec<(String, LevelFilter)>,
+    pub log_file: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<usize>,
+    pub screenshot: Option<PathBuf>,
+    pub gdb: Option<String>,
+    pub trace: Option<PathBuf>,
+    pub link: Option<LinkOption>,
+    pub record_movie: Option<PathBuf>,
+    pub play_movie: Option<PathBuf>,
+    pub record_audio: Option<PathBuf>,
+    pub record_channels: bool,
+}
+
+impl Options {
+    // Speeds outside of these are clamped
+    const MIN_SPEED: usize = 10;
+    const MAX_SPEED: usize = 1000;
+
+    // Returns None if only the usage was asked for
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+        let mut rom = None;
+        let mut bios = None;
+        let mut save_dir = None;
+        let mut scale = gba::SCALE;
+        let mut speed = 100;
+        let mut log_level = LevelFilter::Error;
+        let mut log_filters = Vec::new();
+        let mut log_file = None;
+        let mut headless = false;
+        let mut frames = None;
+        let mut screenshot = None;
+        let mut gdb = None;
+        let mut trace = None;
+        let mut link = None;
+        let mut record_movie = None;
+        let mut play_movie = None;
+        let mut record_audio = None;
+        let mut record_channels = false;
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--bios" => bios = Some(PathBuf::from(value()?)),
+                "--save-dir" => save_dir = Some(PathBuf::from(value()?)),
+                "--scale" => scale = match value()?.parse() {
+                    Ok(scale) if scale > 0 => scale,
+                    _ => return Err("--scale must be a positive integer".to_string()),
+                },
+                "--speed" => speed = match value()?.parse::<usize>() {
+                    Ok(speed) if speed > 0 => speed.clamp(Options::MIN_SPEED, Options::MAX_SPEED),
+                    _ => return Err("--speed must be a positive integer".to_string()),
+                },
+                "--log-level" => log_level = Options::parse_level(&value()?)?,
+                "--log" => {
+                    let filter = value()?;
+                    let (module, level) = match filter.find('=') {
+                        Some(i) => (&filter[..i], &filter[i + 1..]),
+                        None => return Err(format!("Expected <MODULE>=<LEVEL> for --log, got {}", filter)),
+                    };
+                    log_filters.push((module.to_string(), Options::parse_level(level)?));
+                },
+                "--log-file" => log_file = Some(PathBuf::from(value()?)),
+                "--headless" => headless = true,
+                "--frames" => frames = Some(value()?.parse().map_err(|_| "--frames must be an integer".to_string())?),
+                "--screenshot" => screenshot = Some(PathBuf::from(value()?)),
+                "--gdb" => gdb = Some(value()?),
+                "--trace" => trace = Some(PathBuf::from(value()?)),
+                "--link-host" => link = Some(LinkOption::Host(value()?)),
+                "--link-connect" => link = Some(LinkOption::Connect(value()?)),
+                "--record-movie" => record_movie = Some(PathBuf::from(value()?)),
+                "--play-movie" => play_movie = Some(PathBuf::from(value()?)),
+                "--record-audio" => record_audio = Some(PathBuf::from(value()?)),
+                "--record-channels" => record_channels = true,
+                "--help" | "-h" => return Ok(None),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+                _ if rom.is_some() => return Err(format!("Unexpected argument {}", arg)),
+                _ => rom = Some(PathBuf::from(arg)),
+            }
+        }
+
+        let rom = rom.ok_or_else(|| "No ROM given".to_string())?;
+        if !rom.is_file() { return Err(format!("ROM {} does not exist", rom.display())) }
+        if let Some(bios) = &bios {
+            if !bios.is_file() { return Err(format!("BIOS {} does not exist", bios.display())) }
+        }
+        let save_dir = match save_dir {
+            Some(save_dir) if !save_dir.is_dir() => {
+                return Err(format!("Save directory {} does not exist", save_dir.display()))
+            },
+            Some(save_dir) => save_dir,
+            None => rom.parent().map(PathBuf::from).unwrap_or_default(),
+        };
+        if headless && frames.is_none() && play_movie.is_none() {
+            return Err("--headless needs --frames or --play-movie".to_string())
+        }
+        if record_movie.is_some() && play_movie.is_some() {
+            return Err("--record-movie and --play-movie can't be used together".to_string())
+        }
+        if record_channels && record_audio.is_none() {
+            return Err("--record-channels needs --record-audio".to_string())
+        }
+        if headless && gdb.is_some() { return Err("--gdb already runs without a window".to_string()) }
+        if !headless && (frames.is_some() || screenshot.is_some()) {
+            return Err("--frames and --screenshot need --headless".to_string())
+        }
+        if speed != 100 && (headless || gdb.is_some()) { return Err("--speed needs a window".to_string()) }
+
+        Ok(Some(Options {
+            rom,
+            bios,
+            save_dir,
+            scale,
+            speed,
+            log_level,
+            log_filters,
+            log_file,
+            headless,
+            frames,
+            screenshot,
+            gdb,
+            trace,
+            link,
+            record_movie,
+            play_movie,
+            record_audio,
+            record_channels,
+        }))
+    }
+
+    fn parse_level(level: &str) -> Result<LevelFilter, String> {
+        LevelFilter::from_str(level).map_err(|_| format!("Unknown log level {}", level))
+    }
+
+    // File names for saves are derived from the ROM's
+    pub fn save_path(&self, extension: &str) -> PathBuf {
+        let mut name = self.rom.file_stem().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(extension);
+        self.save_dir.join(name)
+    }
+
+    // Cheats are listed in a text file next to the ROM
+    pub fn cheat_path(&self) -> PathBuf { self.rom.with_extension("cht") }
+
+    pub fn init_logger(&self) -> Result<(), String> {
+        let config = |filter: Option<&str>| {
+            let mut config = ConfigBuilder::new();
+            config
+                .set_time_level(LevelFilter::Off)
+                .set_thread_level(LevelFilter::Off)
+                .set_target_level(LevelFilter::Off)
+                .set_location_level(LevelFilter::Off);
+            match filter {
+                Some(module) => { config.add_filter_allow(module.to_string()); },
+                // Modules with their own level are left to their loggers
+                None => for (module, _) in self.log_filters.iter() { config.add_filter_ignore(module.clone()); },
+            }
+            config.build()
+        };
+        let levels = std::iter::once((None, self.log_level))
+            .chain(self.log_filters.iter().map(|(module, level)| (Some(module.as_str()), *level)));
+
+        let loggers: Vec<Box<dyn SharedLogger>> = match &self.log_file {
+            Some(path) => {
+                let file = File::create(path)
+                    .map_err(|err| format!("Unable to create log file {}: {}", path.display(), err))?;
+                let mut loggers: Vec<Box<dyn SharedLogger>> = Vec::new();
+                for (module, level) in levels {
+                    let file = file.try_clone().map_err(|err| format!("Unable to open log file: {}", err))?;
+                    loggers.push(WriteLogger::new(level, config(module), file));
+                }
+                loggers
+            },
+            None => levels.map(|(module, level)| -> Box<dyn SharedLogger> {
+                TermLogger::new(level, config(module), TerminalMode::Mixed)
+            }).collect(),
+        };
+        CombinedLogger::init(loggers).map_err(|err| format!("Unable to initialize logger: {}", err))
+    }
+}
@@ -81,6 +81,8 @@ impl Texture {
         }
     }
 
+    pub fn size(&self) -> [f32; 2] { [self.width, self.height] }
+
     pub fn render(&self, scale: f32) -> Image {
         Image::new(TextureId::from(self.tex as usize), [self.width * scale, self.height * scale])
     }
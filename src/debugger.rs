@@ -0,0 +1,267 @@
+use imgui::*;
+use glfw::Key;
+
+use std::collections::HashSet;
+
+use core::flume::Sender;
+use core::gba::{GBA, CPUMode, StopReason};
+
+#[derive(Debug)]
+pub enum DebugCommand {
+    Break,
+    Continue,
+    StepInto,
+    StepOver,
+    StepFrame,
+    RunTo(u32),
+    ToggleBreakpoint(u32),
+}
+
+// Everything the debugger window shows, captured on the GBA thread
+pub struct DebuggerState {
+    stopped: bool,
+    stop_reason: Option<StopReason>,
+    regs: [u32; 16],
+    cpsr: u32,
+    banked_regs: Vec<(CPUMode, usize, Vec<u32>, Option<u32>)>,
+    disassembly: Vec<(u32, String)>,
+    breakpoints: Vec<u32>,
+    call_stack: Vec<u32>,
+}
+
+impl DebuggerState {
+    const INSTRS_BEFORE_PC: u32 = 8;
+    const INSTRS_AFTER_PC: u32 = 24;
+    // Mode and first banked register
+    const BANKS: [(CPUMode, usize); 6] = [
+        (CPUMode::USR, 8), (CPUMode::FIQ, 8), (CPUMode::SVC, 13),
+        (CPUMode::ABT, 13), (CPUMode::IRQ, 13), (CPUMode::UND, 13),
+    ];
+
+    fn capture(gba: &GBA, stopped: bool, stop_reason: Option<StopReason>) -> DebuggerState {
+        let mut regs = [0; 16];
+        for (reg, value) in regs.iter_mut().enumerate() { *value = gba.get_reg(reg) }
+        let thumb = gba.in_thumb();
+        let instr_size = if thumb { 2 } else { 4 };
+        let start_addr = regs[15].wrapping_sub(DebuggerState::INSTRS_BEFORE_PC * instr_size);
+        DebuggerState {
+            stopped,
+            stop_reason,
+            regs,
+            cpsr: gba.get_cpsr(),
+            banked_regs: DebuggerState::BANKS.iter().map(|&(mode, first_reg)| {
+                (mode, first_reg, (first_reg..15).map(|reg| gba.get_banked_reg(mode, reg)).collect(), gba.get_spsr(mode))
+            }).collect(),
+            disassembly: (0..DebuggerState::INSTRS_BEFORE_PC + DebuggerState::INSTRS_AFTER_PC).map(|i| {
+                let addr = start_addr.wrapping_add(i * instr_size);
+                (addr, gba.disassemble(addr, thumb))
+            }).collect(),
+            breakpoints: gba.get_breakpoints().iter().copied().collect(),
+            call_stack: gba.call_stack(),
+        }
+    }
+}
+
+// Runs on the GBA thread, which stops emulating frames while the debugger has it stopped
+pub struct Debugger {
+    state_tx: Sender<DebuggerState>,
+    stopped: bool,
+    // Added for step over and run to cursor, removed on the next stop
+    temp_breakpoint: Option<u32>,
+}
+
+impl Debugger {
+    pub fn new(state_tx: Sender<DebuggerState>) -> Debugger {
+        Debugger {
+            state_tx,
+            stopped: false,
+            temp_breakpoint: None,
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool { self.stopped }
+
+    pub fn handle_command(&mut self, gba: &mut GBA, command: DebugCommand) {
+        match command {
+            DebugCommand::ToggleBreakpoint(addr) => {
+                if !gba.remove_breakpoint(addr) { gba.add_breakpoint(addr) }
+                if self.temp_breakpoint == Some(addr) { self.temp_breakpoint = None }
+                self.publish(gba, None);
+            },
+            DebugCommand::Break => if !self.stopped { self.stop(gba, None) },
+            // Everything else only makes sense while stopped
+            _ if !self.stopped => (),
+            DebugCommand::Continue => {
+                self.stopped = false;
+                self.publish(gba, None);
+            },
+            DebugCommand::StepInto => {
+                let stop_reason = gba.step();
+                self.stop(gba, Some(stop_reason));
+            },
+            DebugCommand::StepOver => match gba.call_return_addr() {
+                Some(return_addr) => self.run_to(gba, return_addr),
+                None => {
+                    let stop_reason = gba.step();
+                    self.stop(gba, Some(stop_reason));
+                },
+            },
+            DebugCommand::StepFrame => {
+                let stop_reason = gba.emulate_frame_debug();
+                self.stop(gba, stop_reason);
+            },
+            DebugCommand::RunTo(addr) => self.run_to(gba, addr),
+        }
+    }
+
+    // Republishes after the GBA changed underneath the debugger, e.g. from loading a state
+    pub fn refresh(&self, gba: &GBA) {
+        if self.stopped { self.publish(gba, None) }
+    }
+
+    pub fn emulate_frame(&mut self, gba: &mut GBA) {
+        if gba.get_breakpoints().is_empty() { gba.emulate_frame(); return }
+        if let Some(stop_reason) = gba.emulate_frame_debug() { self.stop(gba, Some(stop_reason)) }
+    }
+
+    fn run_to(&mut self, gba: &mut GBA, addr: u32) {
+        if !gba.get_breakpoints().contains(&addr) {
+            gba.add_breakpoint(addr);
+            self.temp_breakpoint = Some(addr);
+        }
+        self.stopped = false;
+        self.publish(gba, None);
+    }
+
+    fn stop(&mut self, gba: &mut GBA, stop_reason: Option<StopReason>) {
+        let stop_reason = match self.temp_breakpoint.take() {
+            Some(addr) => {
+                gba.remove_breakpoint(addr);
+                if stop_reason == Some(StopReason::Breakpoint(addr)) { Some(StopReason::Step) } else { stop_reason }
+            },
+            None => stop_reason,
+        };
+        self.stopped = true;
+        self.publish(gba, stop_reason);
+    }
+
+    fn publish(&self, gba: &GBA, stop_reason: Option<StopReason>) {
+        // The window is gone if this fails
+        self.state_tx.send(DebuggerState::capture(gba, self.stopped, stop_reason)).ok();
+    }
+}
+
+pub struct DebuggerWindow {
+    pub open: bool,
+    state: Option<DebuggerState>,
+    cursor: Option<u32>,
+    scroll_to_pc: bool,
+}
+
+impl DebuggerWindow {
+    pub fn new() -> DebuggerWindow {
+        DebuggerWindow {
+            open: false,
+            state: None,
+            cursor: None,
+            scroll_to_pc: false,
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool { matches!(&self.state, Some(state) if state.stopped) }
+
+    pub fn update(&mut self, state: DebuggerState) {
+        self.scroll_to_pc = state.stopped;
+        self.state = Some(state);
+    }
+
+    pub fn render(&mut self, ui: &Ui, keys_pressed: &HashSet<Key>) -> Option<DebugCommand> {
+        if !self.open { return None }
+        let mut command = None;
+        let (cursor, scroll_to_pc) = (&mut self.cursor, &mut self.scroll_to_pc);
+        let state = self.state.as_ref();
+        Window::new(im_str!("Debugger"))
+        .size([640.0, 480.0], Condition::FirstUseEver)
+        .build(ui, || {
+            let stopped = matches!(state, Some(state) if state.stopped);
+            if stopped {
+                if ui.button(im_str!("Continue"), [0.0, 0.0]) { command = Some(DebugCommand::Continue) }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Step Into"), [0.0, 0.0]) || keys_pressed.contains(&Key::F11) {
+                    command = Some(DebugCommand::StepInto)
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Step Over"), [0.0, 0.0]) || keys_pressed.contains(&Key::F10) {
+                    command = Some(DebugCommand::StepOver)
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Step Frame"), [0.0, 0.0]) { command = Some(DebugCommand::StepFrame) }
+                if let Some(addr) = *cursor {
+                    ui.same_line(0.0);
+                    if ui.button(im_str!("Run to Cursor"), [0.0, 0.0]) { command = Some(DebugCommand::RunTo(addr)) }
+                }
+            } else if ui.button(im_str!("Break"), [0.0, 0.0]) { command = Some(DebugCommand::Break) }
+
+            let state = match state {
+                Some(state) => state,
+                None => return,
+            };
+            ui.text(match (state.stopped, state.stop_reason) {
+                (false, _) => "Running".to_string(),
+                (true, Some(StopReason::Breakpoint(addr))) => format!("Stopped at breakpoint 0x{:08X}", addr),
+                (true, Some(StopReason::Watchpoint(_, addr))) => format!("Stopped at watchpoint on 0x{:08X}", addr),
+                (true, _) => "Stopped".to_string(),
+            });
+            ui.separator();
+
+            ui.columns(2, im_str!("Debugger Columns"), true);
+            ChildWindow::new(im_str!("Disassembly")).build(ui, || {
+                for (i, (addr, text)) in state.disassembly.iter().enumerate() {
+                    let id = ui.push_id(i as i32);
+                    // Clicking the checkbox toggles a breakpoint, clicking the line places the cursor
+                    let mut breakpoint = state.breakpoints.contains(addr);
+                    if ui.checkbox(im_str!("##breakpoint"), &mut breakpoint) { command = Some(DebugCommand::ToggleBreakpoint(*addr)) }
+                    ui.same_line(0.0);
+                    let is_pc = *addr == state.regs[15];
+                    let label = ImString::new(format!("{} {:08X}  {}", if is_pc { ">" } else { " " }, addr, text));
+                    if Selectable::new(&label).selected(*cursor == Some(*addr)).build(ui) { *cursor = Some(*addr) }
+                    if is_pc && *scroll_to_pc {
+                        ui.set_scroll_here_y();
+                        *scroll_to_pc = false;
+                    }
+                    id.pop(ui);
+                }
+            });
+            ui.next_column();
+
+            for reg in 0..8 {
+                ui.text(format!("r{:<2} {:08X}    r{:<2} {:08X}", reg, state.regs[reg], reg + 8, state.regs[reg + 8]));
+            }
+            let flags: String = ["N", "Z", "C", "V"].iter().enumerate().map(|(i, flag)| {
+                if state.cpsr & 1 << (31 - i) != 0 { flag.to_string() } else { flag.to_lowercase() }
+            }).chain(["I", "F", "T"].iter().enumerate().map(|(i, flag)| {
+                if state.cpsr & 1 << (7 - i) != 0 { flag.to_string() } else { flag.to_lowercase() }
+            })).collect();
+            ui.text(format!("cpsr {:08X} {} {}", state.cpsr, flags,
+                CPUMode::from_bits(state.cpsr).map_or("???".to_string(), |mode| format!("{:?}", mode))));
+
+            if CollapsingHeader::new(im_str!("Banked Registers")).build(ui) {
+                for (mode, first_reg, regs, spsr) in state.banked_regs.iter() {
+                    let regs: Vec<String> = regs.iter().enumerate()
+                        .map(|(i, value)| format!("r{} {:08X}", first_reg + i, value)).collect();
+                    ui.text(format!("{:?}: {}", mode, regs.join(" ")));
+                    if let Some(spsr) = spsr { ui.text(format!("     spsr {:08X}", spsr)) }
+                }
+            }
+            if CollapsingHeader::new(im_str!("Call Stack")).default_open(true).build(ui) {
+                // Clicking a call puts the cursor where it returns to, so running to it steps out
+                for call_addr in state.call_stack.iter() {
+                    let label = ImString::new(format!("{:08X}", call_addr));
+                    if Selectable::new(&label).build(ui) { *cursor = Some(call_addr.wrapping_add(4)) }
+                }
+            }
+            ui.columns(1, im_str!("Debugger Columns"), false);
+        });
+        command
+    }
+}
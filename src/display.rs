@@ -23,12 +23,12 @@ pub struct Display {
 }
 
 impl Display {
-    pub fn new(imgui: &mut imgui::Context) -> Display {
+    pub fn new(imgui: &mut imgui::Context, scale: usize) -> Display {
         let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
         glfw.set_error_callback(glfw::FAIL_ON_ERRORS);
 
-        let width = (gba::WIDTH * gba::SCALE) as u32;
-        let height = (gba::HEIGHT * gba::SCALE) as u32;
+        let width = (gba::WIDTH * scale) as u32;
+        let height = (gba::HEIGHT * scale) as u32;
         let (mut window, events) = glfw.create_window(width, height,
             "GBA Emulator", glfw::WindowMode::Windowed).expect("Failed to create GLFW window!");
         window.make_current();
@@ -113,6 +113,8 @@ impl Display {
 
     pub fn should_close(&self) -> bool { self.window.should_close() }
 
+    pub fn is_key_held(&self, key: Key) -> bool { self.window.get_key(key) == Action::Press }
+
     fn prepare_frame(&mut self, io: &mut imgui::Io) {
         if io.want_set_mouse_pos {
             self.window.set_cursor_pos(io.mouse_pos[0] as f64, io.mouse_pos[1] as f64);
@@ -214,7 +216,6 @@ impl Display {
         self.prepare_render(&ui);
         self.imgui_renderer.render(ui);
 
-        // while Instant::now().duration_since(self.prev_frame_time) < gba::FRAME_PERIOD {}
         self.window.swap_buffers();
         self.prev_frame_time = Instant::now();
         self.frames_passed += 1;
@@ -1,104 +1,369 @@
 extern crate imgui;
-// extern crate imgui_memory_editor;
 
+mod audio;
+mod audio_channels;
+mod cheats;
+mod cli;
 mod display;
 mod debug;
+mod debugger;
+mod memory;
+mod objs;
+mod ram_search;
+mod registers;
+mod screenshot;
+mod speed;
+mod timeline;
 
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::thread;
-use std::collections::VecDeque;
 
-use core::flume;
-use core::simplelog::*;
-//use core::gba::{GBA, VisibleMemoryRegion};
-use core::gba::GBA;
+use core::flume::{self, Selector};
+use core::gba::{GBA, GBABuilder, DebugWindows, GDBStub, LinkTransport, Movie, TcpLink};
+use audio::Audio;
+use audio_channels::{AudioChannelCommand, AudioChannelWatcher, AudioChannelWindow};
+use cheats::{CheatCommand, CheatWatcher, CheatWindow};
+use cli::{LinkOption, Options};
 use display::Display;
 
 use debug::TextureWindow;
+use debugger::{DebugCommand, Debugger, DebuggerWindow};
+use memory::{MemoryCommand, MemoryWatcher, MemoryWindow};
+use objs::OBJWindow;
+use ram_search::{RAMSearchCommand, RAMSearchWatcher, RAMSearchWindow};
+use registers::{RegisterCommand, RegisterWatcher, RegisterWindow};
+use speed::SpeedController;
+use timeline::TimelineWindow;
 use glfw::Key;
 use imgui::*;
-//use imgui_memory_editor::MemoryEditor;
+
+#[derive(Debug)]
+enum EmulatorCommand {
+    SaveState(PathBuf),
+    LoadState(PathBuf),
+    SetRewinding(bool),
+    // Percentage of full speed
+    SetSpeed(usize),
+    SetTurbo(bool),
+    Debug(DebugCommand),
+    Memory(MemoryCommand),
+    Registers(RegisterCommand),
+    EnableTimeline(bool),
+    RAMSearch(RAMSearchCommand),
+    AudioChannels(AudioChannelCommand),
+    Cheats(CheatCommand),
+    // Sent when the window closes
+    Exit,
+}
+
+// One emulator runs with --link-host <addr> and the other with --link-connect <addr>
+fn connect_link(link: &LinkOption) -> Result<Box<dyn LinkTransport>, String> {
+    let link = match link {
+        LinkOption::Host(addr) => {
+            println!("Waiting for the other emulator to connect to {}", addr);
+            TcpLink::host(addr.as_str())
+        },
+        LinkOption::Connect(addr) => TcpLink::connect(addr.as_str()),
+    };
+    match link {
+        Ok(link) => Ok(Box::new(link)),
+        Err(err) => Err(format!("Unable to connect link cable: {}", err)),
+    }
+}
+
+// Everything a GBA is built from, loaded up front so that missing files are reported right away
+struct GBAConfig {
+    rom: Vec<u8>,
+    bios: Option<Vec<u8>>,
+    save_file: PathBuf,
+    save_data: Option<Vec<u8>>,
+    cheat_file: PathBuf,
+    link: Option<Box<dyn LinkTransport>>,
+    trace: Option<PathBuf>,
+    record_movie: Option<PathBuf>,
+    play_movie: Option<Movie>,
+    record_audio: Option<PathBuf>,
+    record_channels: bool,
+}
+
+impl GBAConfig {
+    fn load(options: &Options) -> GBAConfig {
+        let save_file = options.save_path("sav");
+        GBAConfig {
+            rom: read_file("ROM", &options.rom),
+            bios: options.bios.as_ref().map(|bios| read_file("BIOS", bios)),
+            save_data: fs::read(&save_file).ok(),
+            save_file,
+            cheat_file: options.cheat_path(),
+            link: options.link.as_ref().map(|link| connect_link(link).unwrap_or_else(|err| exit_with_error(&err))),
+            trace: options.trace.clone(),
+            record_movie: options.record_movie.clone(),
+            play_movie: options.play_movie.as_ref().map(|path| Movie::load(&read_file("movie", path))
+                .unwrap_or_else(|err| exit_with_error(&format!("Unable to load movie {}: {}", path.display(), err)))),
+            record_audio: options.record_audio.clone(),
+            record_channels: options.record_channels,
+        }
+    }
+
+    fn builder(&mut self) -> GBABuilder<'_> {
+        let link = self.link.take();
+        let mut builder = GBA::builder(&self.rom).save_file(self.save_file.clone()).cheat_file(self.cheat_file.clone());
+        if let Some(bios) = &self.bios { builder = builder.bios(bios) }
+        if let Some(save_data) = &self.save_data { builder = builder.save_data(save_data) }
+        if let Some(link) = link { builder = builder.link(link) }
+        if let Some(trace) = &self.trace { builder = builder.trace(trace.clone()) }
+        if self.record_movie.is_some() { builder = builder.record_movie() }
+        if let Some(movie) = self.play_movie.take() { builder = builder.play_movie(movie) }
+        if let Some(path) = &self.record_audio { builder = builder.record_audio(path.clone(), self.record_channels) }
+        builder
+    }
+
+    // Saves the movie if one was being recorded, and finishes any audio recording
+    fn finish(&self, gba: &mut GBA) {
+        let movie = gba.finish_movie();
+        if let (Some(path), Some(movie)) = (&self.record_movie, movie) {
+            fs::write(path, movie.save()).unwrap_or_else(|err| eprintln!("Unable to save movie {}: {}", path.display(), err));
+        }
+        gba.stop_audio_recording().unwrap_or_else(|err| eprintln!("Unable to finish audio recording: {}", err));
+    }
+}
+
+fn read_file(description: &str, path: &Path) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|err| exit_with_error(&format!("Unable to read {} {}: {}", description, path.display(), err)))
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
 
 fn main() {
-    std::env::set_current_dir("ROMs").unwrap();
-    CombinedLogger::init(vec![
-        TermLogger::new(LevelFilter::Error, Config::default(), TerminalMode::Mixed),
-        TermLogger::new(LevelFilter::Off,
-            ConfigBuilder::new()
-            .set_time_level(LevelFilter::Off)
-            .set_thread_level(LevelFilter::Off)
-            .set_target_level(LevelFilter::Off)
-            .set_location_level(LevelFilter::Off)
-            .set_time_level(LevelFilter::Off)
-            .set_max_level(LevelFilter::Off)
-            .add_filter_allow_str("core::cpu")
-            .build(),
-            TerminalMode::Mixed),//std::fs::File::create("stdout.log").unwrap()),
-        WriteLogger::new(LevelFilter::Debug,
-            ConfigBuilder::new()
-            .set_time_level(LevelFilter::Off)
-            .set_thread_level(LevelFilter::Off)
-            .set_target_level(LevelFilter::Off)
-            .set_location_level(LevelFilter::Off)
-            .set_time_level(LevelFilter::Off)
-            .add_filter_allow_str("core::io::mgba_test_suite")
-            .build(),
-            File::create("suite.log").unwrap()),
-    ]).unwrap();
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => { println!("{}", cli::USAGE); return },
+        Err(err) => exit_with_error(&format!("{}\n\n{}", err, cli::USAGE)),
+    };
+    options.init_logger().unwrap_or_else(|err| exit_with_error(&err));
+
+    let config = GBAConfig::load(&options);
+    if let Some(addr) = &options.gdb {
+        run_gdb(config, addr).unwrap_or_else(|err| exit_with_error(&err))
+    } else if options.headless {
+        run_headless(config, &options).unwrap_or_else(|err| exit_with_error(&err))
+    } else {
+        run_windowed(config, &options)
+    }
+}
 
+fn run_headless(mut config: GBAConfig, options: &Options) -> Result<(), String> {
+    let (mut gba, pixels, _) = config.builder().build().map_err(|err| err.to_string())?;
+    let frames = match (options.frames, gba.movie_status()) {
+        (Some(frames), _) => frames,
+        (None, Some(status)) => status.len,
+        (None, None) => 0,
+    };
+    for _ in 0..frames { gba.emulate_frame() }
+    if let Some(path) = &options.screenshot {
+        screenshot::save_png(path, &pixels.lock().unwrap())
+            .map_err(|err| format!("Unable to save screenshot {}: {}", path.display(), err))?;
+    }
+    let status = gba.movie_status();
+    config.finish(&mut gba);
+    match status {
+        Some(status) if !status.recording => match status.desync {
+            Some(frame) => Err(format!("Movie desynced at frame {}", frame)),
+            None => { println!("Movie played back {} of {} frames without desyncing", status.frame, status.len); Ok(()) },
+        },
+        _ => Ok(()),
+    }
+}
+
+fn run_gdb(mut config: GBAConfig, addr: &str) -> Result<(), String> {
+    let (mut gba, _, _) = config.builder().build().map_err(|err| err.to_string())?;
+    println!("Waiting for GDB to connect to {}", addr);
+    let mut stub = GDBStub::listen(addr).map_err(|err| format!("Unable to start GDB server: {}", err))?;
+    let result = stub.run(&mut gba).map_err(|err| format!("GDB connection failed: {}", err));
+    config.finish(&mut gba);
+    result
+}
+
+fn run_windowed(mut config: GBAConfig, options: &Options) {
     let (render_tx, render_rx) = flume::unbounded();
     let (keypad_tx, keypad_rx) = flume::unbounded();
     let (mutexes_tx, mutexes_rx) = flume::unbounded();
-    let _gba_thread = thread::spawn(move || {
-        let (mut gba, pixels_mutex, debug_windows_spec_mutex) =
-        GBA::new(PathBuf::from("suite.gba"), render_tx, keypad_rx);
-        mutexes_tx.send((pixels_mutex, debug_windows_spec_mutex)).unwrap();
-        loop { gba.emulate_frame() }
+    let (command_tx, command_rx) = flume::unbounded();
+    let (debugger_tx, debugger_rx) = flume::unbounded();
+    let (memory_tx, memory_rx) = flume::unbounded();
+    let (registers_tx, registers_rx) = flume::unbounded();
+    let (timeline_tx, timeline_rx) = flume::unbounded();
+    let (ram_search_tx, ram_search_rx) = flume::unbounded();
+    let (audio_channels_tx, audio_channels_rx) = flume::unbounded();
+    let (cheats_tx, cheats_rx) = flume::unbounded();
+    let state_file = options.save_path("ss0");
+    let (_audio_device, audio_sink, audio_buffer) = Audio::new();
+    let initial_speed = options.speed;
+    let gba_thread = thread::spawn(move || {
+        let (mut gba, pixels_mutex, debug_windows_spec_mutex) = match config.builder()
+            .render_tx(render_tx)
+            .keypad_rx(keypad_rx)
+            .audio_sink(Box::new(audio_sink))
+            .build() {
+            Ok(gba) => gba,
+            Err(err) => { mutexes_tx.send(Err(err.to_string())).unwrap(); return },
+        };
+        mutexes_tx.send(Ok((pixels_mutex, debug_windows_spec_mutex))).unwrap();
+        // 10 seconds of rewind
+        gba.enable_rewind(600, 1);
+        let mut rewinding = false;
+        let mut debugger = Debugger::new(debugger_tx);
+        let mut memory_watcher = MemoryWatcher::new(memory_tx);
+        let mut register_watcher = RegisterWatcher::new(registers_tx);
+        let mut ram_search_watcher = RAMSearchWatcher::new(ram_search_tx);
+        let mut audio_channel_watcher = AudioChannelWatcher::new(audio_channels_tx);
+        let mut cheat_watcher = CheatWatcher::new(cheats_tx);
+        let mut speed_controller = SpeedController::new(initial_speed, Some(audio_buffer));
+        let mut reported_desync = false;
+        loop {
+            // Nothing runs while stopped in the debugger, so wait for the next command
+            let mut commands = Vec::new();
+            if debugger.is_stopped() {
+                match command_rx.recv() {
+                    Ok(command) => commands.push(command),
+                    Err(_) => return,
+                }
+            }
+            commands.extend(command_rx.try_iter());
+            for command in commands {
+                match command {
+                    EmulatorCommand::SaveState(path) => fs::write(&path, gba.save_state())
+                        .unwrap_or_else(|err| eprintln!("Unable to save state to {}: {}", path.display(), err)),
+                    EmulatorCommand::LoadState(path) => match fs::read(&path) {
+                        Ok(state) => match gba.load_state(&state) {
+                            Ok(()) => debugger.refresh(&gba),
+                            Err(err) => eprintln!("Unable to load state {}: {}", path.display(), err),
+                        },
+                        Err(err) => eprintln!("Unable to read state {}: {}", path.display(), err),
+                    },
+                    EmulatorCommand::SetRewinding(value) => rewinding = value,
+                    EmulatorCommand::SetSpeed(speed) => speed_controller.set_speed(speed),
+                    EmulatorCommand::SetTurbo(turbo) => speed_controller.set_turbo(turbo),
+                    EmulatorCommand::Debug(command) => debugger.handle_command(&mut gba, command),
+                    EmulatorCommand::Memory(command) => memory_watcher.handle_command(&mut gba, command),
+                    EmulatorCommand::Registers(command) => register_watcher.handle_command(&mut gba, command),
+                    EmulatorCommand::EnableTimeline(enable) => gba.enable_timeline(enable),
+                    EmulatorCommand::RAMSearch(command) => ram_search_watcher.handle_command(&gba, command),
+                    EmulatorCommand::AudioChannels(command) => audio_channel_watcher.handle_command(&mut gba, command),
+                    EmulatorCommand::Cheats(command) => cheat_watcher.handle_command(&mut gba, command),
+                    EmulatorCommand::Exit => { config.finish(&mut gba); return },
+                }
+            }
+            if !debugger.is_stopped() {
+                // Step back two frames so that emulating one still moves backwards
+                if rewinding { gba.rewind(2); }
+                speed_controller.start_frame(&mut gba);
+                debugger.emulate_frame(&mut gba);
+                speed_controller.end_frame();
+            }
+            memory_watcher.update(&gba);
+            register_watcher.update(&gba);
+            ram_search_watcher.update(&gba);
+            audio_channel_watcher.update(&gba);
+            if let Some(frame) = gba.movie_status().and_then(|status| status.desync) {
+                if !reported_desync { eprintln!("Movie desynced at frame {}", frame) }
+                reported_desync = true;
+            }
+            // Frames can also finish while stepping in the debugger
+            if let Some(timeline) = gba.take_timeline() { timeline_tx.send(timeline).ok(); }
+        }
     });
-    let (pixels_mutex, debug_windows_spec_mutex) = mutexes_rx.recv().unwrap();
-    let mut pixels_lock = None; 
+    let (pixels_mutex, debug_windows_spec_mutex) = mutexes_rx.recv().unwrap()
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let mut pixels_lock = None;
 
     let mut imgui = Context::create();
-    let mut display = Display::new(&mut imgui);
+    let mut display = Display::new(&mut imgui, options.scale);
     let mut paused = false;
+    let mut rewinding = false;
+    let mut speed = options.speed;
+    let mut turbo = false;
 
     let mut map_window = TextureWindow::new("BG Map");
     let mut tiles_window = TextureWindow::new("Tiles");
     let mut palettes_window = TextureWindow::new("Palettes");
+    let mut objs_window = OBJWindow::new();
+    let mut layers_window = TextureWindow::new("Layers");
+    let mut debugger_window = DebuggerWindow::new();
+    let mut memory_window = MemoryWindow::new();
+    let mut register_window = RegisterWindow::new();
+    let mut timeline_window = TimelineWindow::new();
+    let mut ram_search_window = RAMSearchWindow::new();
+    let mut audio_channel_window = AudioChannelWindow::new(options.save_path("wav"));
+    let mut cheat_window = CheatWindow::new(options.cheat_path());
 
     let map_labels = [im_str!("0"), im_str!("1"), im_str!("2"), im_str!("3")];
     let tiles_block_labels = [im_str!("0"), im_str!("1"), im_str!("2"), im_str!("3"), im_str!("OBJ")];
+    let layer_labels = [im_str!("BG0"), im_str!("BG1"), im_str!("BG2"), im_str!("BG3"), im_str!("OBJ"),
+        im_str!("Windows"), im_str!("Top Layer")];
 
-    let mut debug_windows = VecDeque::new();
-    /*let mut mem_region = VisibleMemoryRegion::BIOS;
-    let mut mem_editor = MemoryEditor::new(0)
-        .read_fn(|addr| gba.peek_mem(mem_region, addr));*/
+    let mut debug_windows = DebugWindows::default();
 
     while !display.should_close() {
-        /*mem_editor = mem_editor
-            .base_addr(mem_region.get_start_addr() as usize)
-            .mem_size(mem_region.get_size());*/
         if !paused {
-            debug_windows = render_rx.recv().unwrap();
+            if debugger_window.is_stopped() {
+                // Frames only come from stepping, so don't wait for one
+                if let Some(windows) = render_rx.try_iter().last() { debug_windows = windows }
+            } else {
+                // Hitting a breakpoint stops the GBA before it sends a frame
+                let windows = Selector::new()
+                    .recv(&render_rx, |windows| Some(windows.unwrap()))
+                    .recv(&debugger_rx, |state| { debugger_window.update(state.unwrap()); None })
+                    .wait();
+                if let Some(windows) = windows { debug_windows = windows }
+                // Only the latest frame is worth drawing if the GBA got ahead
+                if let Some(windows) = render_rx.try_iter().last() { debug_windows = windows }
+            }
+            for state in debugger_rx.try_iter() { debugger_window.update(state) }
+            for update in memory_rx.try_iter() { memory_window.update(update) }
+            if let Some(values) = registers_rx.try_iter().last() { register_window.update(values) }
+            if let Some(timeline) = timeline_rx.try_iter().last() { timeline_window.update(timeline) }
+            if let Some(results) = ram_search_rx.try_iter().last() { ram_search_window.update(results) }
+            if let Some(state) = audio_channels_rx.try_iter().last() { audio_channel_window.update(state) }
+            if let Some(cheats) = cheats_rx.try_iter().last() { cheat_window.update(cheats) }
             pixels_lock = Some(pixels_mutex.lock().unwrap());
         }
         
+        if display.is_key_held(Key::Backspace) != rewinding {
+            rewinding = !rewinding;
+            command_tx.send(EmulatorCommand::SetRewinding(rewinding)).unwrap();
+        }
+        if display.is_key_held(Key::Tab) != turbo {
+            turbo = !turbo;
+            command_tx.send(EmulatorCommand::SetTurbo(turbo)).unwrap();
+        }
+
         let pixels = pixels_lock.take().unwrap();
         let mut debug_windows_spec = debug_windows_spec_mutex.lock().unwrap();
         let mut debug_windows_copy = debug_windows.clone();
         display.render(&pixels, &keypad_tx, &mut imgui,
             |ui, keys_pressed, modifers| {
-            if paused {
-                Window::new(im_str!("Paused"))
+            // Drawn first so that it stays behind every other window
+            if debug_windows_spec.objs_enable { objs_window.render_overlay(ui, &debug_windows_copy.objs) }
+            let status = if paused { Some("Paused".to_string()) }
+                else if turbo { Some("Turbo".to_string()) }
+                else if speed != 100 { Some(format!("{}% Speed", speed)) }
+                else { None };
+            if let Some(status) = status {
+                Window::new(im_str!("Status"))
                 .no_decoration()
                 .always_auto_resize(true)
                 .build(ui, || {
-                    ui.text("Paused");
+                    ui.text(status);
                 });
             }
             if debug_windows_spec.map_enable {
-                let (pixels, width, height) = debug_windows_copy.pop_front().unwrap();
+                let (pixels, width, height) = debug_windows_copy.images.pop_front().unwrap();
                 let bg_i = &mut debug_windows_spec.map_spec.bg_i;
                 map_window.render(ui, &keys_pressed, pixels, width, height, || {
                     debug::control_combo_with_arrows(ui, &keys_pressed, bg_i, map_labels.len() - 1);
@@ -107,7 +372,7 @@ fn main() {
                 });
             }
             if debug_windows_spec.tiles_enable {
-                let (pixels, width, height) = debug_windows_copy.pop_front().unwrap();
+                let (pixels, width, height) = debug_windows_copy.images.pop_front().unwrap();
                 let spec = &mut debug_windows_spec.tiles_spec;
                 let (palette, block, bpp8) = (&mut spec.palette, &mut spec.block, &mut spec.bpp8);
                 tiles_window.render(ui, &keys_pressed, pixels, width, height, || {
@@ -124,28 +389,86 @@ fn main() {
                 });
             }
             if debug_windows_spec.palettes_enable {
-                let (pixels, width, height) = debug_windows_copy.pop_front().unwrap();
+                let (pixels, width, height) = debug_windows_copy.images.pop_front().unwrap();
                 palettes_window.render(ui, &keys_pressed, pixels, width, height, || {});
             }
-            /*let mut mem_region_i = mem_region as usize;
-            Window::new(im_str!("Memory Viewer"))
-            .build(ui, || {
-                debug::control_combo_with_arrows(ui, &keys_pressed, &mut mem_region_i, 8);
-                ComboBox::new(im_str!("Memory Region")).build_simple(ui, &mut mem_region_i,
-                    &[0, 1, 2, 3, 4, 5, 6, 7, 8],
-                    &(|i| std::borrow::Cow::from(ImString::new(
-                        VisibleMemoryRegion::from_index(*i).get_name()
-                ))));
-                mem_editor.build_without_window(&ui);
-            });
-            mem_region = VisibleMemoryRegion::from_index(mem_region_i);*/
+            if debug_windows_spec.objs_enable {
+                let (pixels, width, height) = debug_windows_copy.images.pop_front().unwrap();
+                objs_window.render(ui, &keys_pressed, pixels, width, height, &debug_windows_copy.objs);
+            }
+            if debug_windows_spec.layers_enable {
+                let (pixels, width, height) = debug_windows_copy.images.pop_front().unwrap();
+                let spec = &mut *debug_windows_spec;
+                let (layer, toggles) = (&mut spec.layers_spec.layer, &mut spec.layer_toggles);
+                layers_window.render(ui, &keys_pressed, pixels, width, height, || {
+                    // Windows are red, green and blue and the top layer uses the same colors for BG0-2
+                    debug::control_combo_with_arrows(ui, &keys_pressed, layer, layer_labels.len() - 1);
+                    ComboBox::new(im_str!("Layer")).build_simple(ui, layer,
+                        &[0usize, 1, 2, 3, 4, 5, 6], &(|i| std::borrow::Cow::from(layer_labels[*i])));
+                    ui.checkbox(im_str!("BG0"), &mut toggles.bgs[0]);
+                    ui.same_line(0.0);
+                    ui.checkbox(im_str!("BG1"), &mut toggles.bgs[1]);
+                    ui.same_line(0.0);
+                    ui.checkbox(im_str!("BG2"), &mut toggles.bgs[2]);
+                    ui.same_line(0.0);
+                    ui.checkbox(im_str!("BG3"), &mut toggles.bgs[3]);
+                    ui.same_line(0.0);
+                    ui.checkbox(im_str!("OBJ"), &mut toggles.obj);
+                    ui.checkbox(im_str!("Windows"), &mut toggles.windows);
+                    ui.same_line(0.0);
+                    ui.checkbox(im_str!("Color Effects"), &mut toggles.color_effects);
+                });
+            }
+            for command in memory_window.render(ui, &keys_pressed) {
+                command_tx.send(EmulatorCommand::Memory(command)).unwrap();
+            }
+            for command in register_window.render(ui) {
+                command_tx.send(EmulatorCommand::Registers(command)).unwrap();
+            }
+            if let Some(enable) = timeline_window.render(ui) {
+                command_tx.send(EmulatorCommand::EnableTimeline(enable)).unwrap();
+            }
+            for command in ram_search_window.render(ui) {
+                command_tx.send(EmulatorCommand::RAMSearch(command)).unwrap();
+            }
+            for command in audio_channel_window.render(ui) {
+                command_tx.send(EmulatorCommand::AudioChannels(command)).unwrap();
+            }
+            for command in cheat_window.render(ui) {
+                command_tx.send(EmulatorCommand::Cheats(command)).unwrap();
+            }
+            if let Some(command) = debugger_window.render(ui, &keys_pressed) {
+                command_tx.send(EmulatorCommand::Debug(command)).unwrap();
+            }
 
             if modifers.contains(&glfw::Modifiers::Control) {
-                if paused { return }
+                if keys_pressed.contains(&Key::D) { debugger_window.open = !debugger_window.open }
+                if keys_pressed.contains(&Key::E) { memory_window.open = !memory_window.open }
+                if keys_pressed.contains(&Key::R) { register_window.open = !register_window.open }
+                if keys_pressed.contains(&Key::F) { timeline_window.open = !timeline_window.open }
+                if keys_pressed.contains(&Key::S) { ram_search_window.open = !ram_search_window.open }
+                if keys_pressed.contains(&Key::A) { audio_channel_window.open = !audio_channel_window.open }
+                if keys_pressed.contains(&Key::C) { cheat_window.open = !cheat_window.open }
+                // Debug windows need a new frame to show up
+                if paused || debugger_window.is_stopped() { return }
                 if keys_pressed.contains(&Key::M) { debug_windows_spec.map_enable = !debug_windows_spec.map_enable }
                 if keys_pressed.contains(&Key::T) { debug_windows_spec.tiles_enable = !debug_windows_spec.tiles_enable }
                 if keys_pressed.contains(&Key::P) { debug_windows_spec.palettes_enable = !debug_windows_spec.palettes_enable }
-            } else if keys_pressed.contains(&Key::P) { paused = !paused }
+                if keys_pressed.contains(&Key::O) { debug_windows_spec.objs_enable = !debug_windows_spec.objs_enable }
+                if keys_pressed.contains(&Key::L) { debug_windows_spec.layers_enable = !debug_windows_spec.layers_enable }
+            } else {
+                if keys_pressed.contains(&Key::P) { paused = !paused }
+                let new_speed = if keys_pressed.contains(&Key::Equal) { SpeedController::faster(speed) }
+                    else if keys_pressed.contains(&Key::Minus) { SpeedController::slower(speed) }
+                    else if keys_pressed.contains(&Key::Num0) { 100 }
+                    else { speed };
+                if new_speed != speed {
+                    speed = new_speed;
+                    command_tx.send(EmulatorCommand::SetSpeed(speed)).unwrap();
+                }
+            }
+            if keys_pressed.contains(&Key::F5) { command_tx.send(EmulatorCommand::SaveState(state_file.clone())).unwrap() }
+            if keys_pressed.contains(&Key::F8) { command_tx.send(EmulatorCommand::LoadState(state_file.clone())).unwrap() }
         });
         drop(debug_windows_spec);
 
@@ -153,4 +476,6 @@ fn main() {
             pixels_lock = Some(pixels);
         }
     }
+    command_tx.send(EmulatorCommand::Exit).ok();
+    gba_thread.join().ok();
 }
@@ -0,0 +1,306 @@
+use imgui::*;
+use glfw::Key;
+
+use std::collections::HashSet;
+
+use core::flume::Sender;
+use core::gba::{GBA, VisibleMemoryRegion, disassemble_arm, disassemble_thumb};
+
+use crate::debug;
+
+const PAGE_SIZE: usize = 0x100;
+const BYTES_PER_ROW: usize = 0x10;
+const REGION_COUNT: usize = 9;
+// Addresses compared per frame, so that searching all of PakROM doesn't hold up emulation
+const SEARCH_CHUNK: usize = 0x4_0000;
+
+#[derive(Debug)]
+pub enum MemoryCommand {
+    // Start offset of the page to send after every frame
+    View(VisibleMemoryRegion, usize),
+    Hide,
+    Poke(VisibleMemoryRegion, usize, Vec<u8>),
+    // Searches from the offset onwards and wraps around
+    Search(VisibleMemoryRegion, usize, Vec<u8>),
+}
+
+pub enum MemoryUpdate {
+    Page { region: VisibleMemoryRegion, start: usize, data: Vec<u8>, changed: Vec<bool> },
+    Found(Option<usize>),
+}
+
+struct Search {
+    region: VisibleMemoryRegion,
+    pattern: Vec<u8>,
+    addr: usize,
+    remaining: usize,
+}
+
+// Runs on the GBA thread and keeps the memory window fed with the page it's looking at
+pub struct MemoryWatcher {
+    update_tx: Sender<MemoryUpdate>,
+    view: Option<(VisibleMemoryRegion, usize)>,
+    last_data: Vec<u8>,
+    search: Option<Search>,
+}
+
+impl MemoryWatcher {
+    pub fn new(update_tx: Sender<MemoryUpdate>) -> MemoryWatcher {
+        MemoryWatcher {
+            update_tx,
+            view: None,
+            last_data: Vec::new(),
+            search: None,
+        }
+    }
+
+    pub fn handle_command(&mut self, gba: &mut GBA, command: MemoryCommand) {
+        match command {
+            MemoryCommand::View(region, start) => {
+                self.view = Some((region, start));
+                self.last_data = MemoryWatcher::read_page(gba, region, start);
+                self.send_page(gba);
+            },
+            MemoryCommand::Hide => self.view = None,
+            MemoryCommand::Poke(region, addr, bytes) => {
+                for (i, byte) in bytes.into_iter().enumerate() {
+                    if addr + i < region.get_size() { gba.poke_mem(region, addr + i, byte) }
+                }
+                self.send_page(gba);
+            },
+            // Only aligned matches count, since values are stored aligned
+            MemoryCommand::Search(region, start, pattern) => {
+                let size = region.get_size();
+                let addr = start / pattern.len() * pattern.len() % size;
+                self.search = Some(Search { region, addr, remaining: size / pattern.len(), pattern });
+                self.continue_search(gba);
+            },
+        }
+    }
+
+    // Bytes that differ from the last call are highlighted
+    pub fn update(&mut self, gba: &GBA) {
+        if let Some(data) = self.send_page(gba) { self.last_data = data }
+        self.continue_search(gba);
+    }
+
+    fn send_page(&self, gba: &GBA) -> Option<Vec<u8>> {
+        let (region, start) = self.view?;
+        let data = MemoryWatcher::read_page(gba, region, start);
+        let changed = data.iter().zip(self.last_data.iter()).map(|(new, old)| new != old).collect();
+        self.update_tx.send(MemoryUpdate::Page { region, start, data: data.clone(), changed }).ok();
+        Some(data)
+    }
+
+    // Includes a few bytes of the next page so that values at the end of the page can be shown
+    fn read_page(gba: &GBA, region: VisibleMemoryRegion, start: usize) -> Vec<u8> {
+        let end = std::cmp::min(start + PAGE_SIZE + 3, region.get_size());
+        (start..end).map(|addr| gba.peek_mem(region, addr)).collect()
+    }
+
+    // Wraps around to the start of the region and gives up after one lap
+    fn continue_search(&mut self, gba: &GBA) {
+        let mut search = match self.search.take() {
+            Some(search) => search,
+            None => return,
+        };
+        let size = search.region.get_size();
+        for _ in 0..std::cmp::min(SEARCH_CHUNK, search.remaining) {
+            let addr = search.addr;
+            search.addr = (addr + search.pattern.len()) % size;
+            search.remaining -= 1;
+            if search.pattern.iter().enumerate().all(|(i, byte)| gba.peek_mem(search.region, addr + i) == *byte) {
+                self.update_tx.send(MemoryUpdate::Found(Some(addr))).ok();
+                return
+            }
+        }
+        if search.remaining == 0 {
+            self.update_tx.send(MemoryUpdate::Found(None)).ok();
+        } else { self.search = Some(search) }
+    }
+}
+
+pub struct MemoryWindow {
+    pub open: bool,
+    region_i: usize,
+    start: usize,
+    selected: usize,
+    viewing: Option<(usize, usize)>,
+    page: Option<(VisibleMemoryRegion, usize, Vec<u8>, Vec<bool>)>,
+    goto: ImString,
+    value: ImString,
+    value_size_i: usize,
+    status: String,
+}
+
+impl MemoryWindow {
+    const VALUE_SIZES: [usize; 3] = [1, 2, 4];
+
+    pub fn new() -> MemoryWindow {
+        MemoryWindow {
+            open: false,
+            region_i: 0,
+            start: 0,
+            selected: 0,
+            viewing: None,
+            page: None,
+            goto: ImString::with_capacity(8),
+            value: ImString::with_capacity(8),
+            value_size_i: 0,
+            status: String::new(),
+        }
+    }
+
+    pub fn update(&mut self, update: MemoryUpdate) {
+        match update {
+            MemoryUpdate::Page { region, start, data, changed } => self.page = Some((region, start, data, changed)),
+            MemoryUpdate::Found(Some(addr)) => {
+                self.go_to(addr);
+                self.status = format!("Found at {:08X}", self.region().get_start_addr() as usize + addr);
+            },
+            MemoryUpdate::Found(None) => self.status = "Not found".to_string(),
+        }
+    }
+
+    fn region(&self) -> VisibleMemoryRegion { VisibleMemoryRegion::from_index(self.region_i) }
+
+    fn go_to(&mut self, addr: usize) {
+        self.start = addr / PAGE_SIZE * PAGE_SIZE;
+        self.selected = addr;
+    }
+
+    // Takes a full address and switches to the region it's in
+    fn go_to_addr(&mut self, addr: u32) {
+        let region_i = (0..REGION_COUNT).find(|&i| {
+            let region = VisibleMemoryRegion::from_index(i);
+            let start_addr = region.get_start_addr();
+            addr >= start_addr && ((addr - start_addr) as usize) < region.get_size()
+        });
+        match region_i {
+            Some(region_i) => {
+                self.region_i = region_i;
+                self.go_to((addr - self.region().get_start_addr()) as usize);
+                self.status.clear();
+            },
+            None => self.status = format!("{:08X} isn't in any region", addr),
+        }
+    }
+
+    fn value_bytes(&self) -> Option<Vec<u8>> {
+        let value = u32::from_str_radix(self.value.to_str(), 16).ok()?;
+        Some(value.to_le_bytes()[..MemoryWindow::VALUE_SIZES[self.value_size_i]].to_vec())
+    }
+
+    pub fn render(&mut self, ui: &Ui, keys_pressed: &HashSet<Key>) -> Vec<MemoryCommand> {
+        let mut commands = Vec::new();
+        if !self.open {
+            if self.viewing.take().is_some() { commands.push(MemoryCommand::Hide) }
+            return commands
+        }
+
+        let mut open = true;
+        Window::new(im_str!("Memory"))
+        .always_auto_resize(true)
+        .opened(&mut open)
+        .build(ui, || {
+            let region_i = self.region_i;
+            debug::control_combo_with_arrows(ui, keys_pressed, &mut self.region_i, REGION_COUNT - 1);
+            ComboBox::new(im_str!("Region")).build_simple(ui, &mut self.region_i,
+                &(0..REGION_COUNT).collect::<Vec<_>>(), &(|i| std::borrow::Cow::from(ImString::new(VisibleMemoryRegion::from_index(*i).get_name()))));
+            if self.region_i != region_i { self.go_to(0) }
+            let region = self.region();
+            let size = region.get_size();
+
+            if ui.input_text(im_str!("Go To"), &mut self.goto).chars_hexadecimal(true).enter_returns_true(true).build() {
+                if let Ok(addr) = u32::from_str_radix(self.goto.to_str(), 16) { self.go_to_addr(addr) }
+            }
+            let prev = ui.button(im_str!("<"), [0.0, 0.0]);
+            ui.same_line(0.0);
+            let next = ui.button(im_str!(">"), [0.0, 0.0]);
+            if (prev || ui.is_window_focused() && keys_pressed.contains(&Key::PageUp)) && self.start >= PAGE_SIZE {
+                self.start -= PAGE_SIZE;
+            }
+            if (next || ui.is_window_focused() && keys_pressed.contains(&Key::PageDown)) && self.start + PAGE_SIZE < size {
+                self.start += PAGE_SIZE;
+            }
+            ui.separator();
+
+            let page = match &self.page {
+                Some((page_region, start, data, changed)) if *page_region == region && *start == self.start => {
+                    Some((data, changed))
+                },
+                _ => None,
+            };
+            if let Some((data, changed)) = page {
+                let start_addr = region.get_start_addr() as usize;
+                for row in (0..std::cmp::min(PAGE_SIZE, data.len())).step_by(BYTES_PER_ROW) {
+                    ui.text(format!("{:08X}", start_addr + self.start + row));
+                    let row_data = &data[row..std::cmp::min(row + BYTES_PER_ROW, data.len())];
+                    for (i, byte) in row_data.iter().enumerate().map(|(i, byte)| (row + i, byte)) {
+                        ui.same_line(0.0);
+                        let color = if changed.get(i) == Some(&true) {
+                            Some(ui.push_style_color(StyleColor::Text, [1.0, 0.3, 0.3, 1.0]))
+                        } else { None };
+                        let label = ImString::new(format!("{:02X}##{}", byte, i));
+                        if Selectable::new(&label).selected(self.selected == self.start + i)
+                            .size([ui.calc_text_size(im_str!("00"), false, 0.0)[0], 0.0]).build(ui) {
+                            self.selected = self.start + i;
+                        }
+                        if let Some(color) = color { color.pop(ui) }
+                    }
+                    ui.same_line(0.0);
+                    let ascii: String = row_data.iter()
+                        .map(|&byte| if (0x20..0x7F).contains(&byte) { byte as char } else { '.' }).collect();
+                    ui.text(ascii);
+                }
+                ui.separator();
+
+                // Values starting at the selected byte, as far as the page goes
+                let offset = self.selected.wrapping_sub(self.start);
+                if offset < data.len() {
+                    let bytes: Vec<u8> = data[offset..].iter().copied().take(4).collect();
+                    let value = bytes.iter().rev().fold(0u32, |value, byte| value << 8 | *byte as u32);
+                    let addr = (start_addr + self.selected) as u32;
+                    ui.text(format!("Address {:08X}", addr));
+                    ui.text(format!("u8 {}  s8 {}  bin {:08b}", bytes[0], bytes[0] as i8, bytes[0]));
+                    if bytes.len() >= 2 {
+                        let halfword = value as u16;
+                        ui.text(format!("u16 {}  s16 {}  BGR555 ({}, {}, {})", halfword, halfword as i16,
+                            halfword & 0x1F, halfword >> 5 & 0x1F, halfword >> 10 & 0x1F));
+                        ui.text(format!("Thumb {}", disassemble_thumb(halfword, (value >> 16) as u16, addr)));
+                    }
+                    if bytes.len() == 4 {
+                        ui.text(format!("u32 {}  s32 {}", value, value as i32));
+                        ui.text(format!("ARM {}", disassemble_arm(value, addr)));
+                    }
+                }
+            } else { ui.text("Loading...") }
+            ui.separator();
+
+            ComboBox::new(im_str!("Size")).build_simple_string(ui, &mut self.value_size_i,
+                &[im_str!("Byte"), im_str!("Halfword"), im_str!("Word")]);
+            let write = ui.input_text(im_str!("Value"), &mut self.value)
+                .chars_hexadecimal(true).enter_returns_true(true).build();
+            let write = ui.button(im_str!("Write"), [0.0, 0.0]) || write;
+            ui.same_line(0.0);
+            let find = ui.button(im_str!("Find Next"), [0.0, 0.0]);
+            match self.value_bytes() {
+                Some(bytes) if write => commands.push(MemoryCommand::Poke(region, self.selected, bytes)),
+                Some(bytes) if find => {
+                    self.status = "Searching...".to_string();
+                    commands.push(MemoryCommand::Search(region, self.selected + bytes.len(), bytes));
+                },
+                None if write || find => self.status = "Enter a hex value".to_string(),
+                _ => (),
+            }
+            if !self.status.is_empty() { ui.text(&self.status) }
+        });
+        self.open = open;
+
+        if self.open && self.viewing != Some((self.region_i, self.start)) {
+            self.viewing = Some((self.region_i, self.start));
+            commands.push(MemoryCommand::View(self.region(), self.start));
+        }
+        commands
+    }
+}
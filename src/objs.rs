@@ -0,0 +1,147 @@
+use imgui::*;
+use glfw::Key;
+
+use std::collections::HashSet;
+
+use core::gba::{self, OBJInfo, OBJMode};
+
+use crate::debug::Texture;
+
+pub struct OBJWindow {
+    texture: Texture,
+    selected: usize,
+    show_bounds: bool,
+    hide_disabled: bool,
+}
+
+impl OBJWindow {
+    const THUMBNAIL_SIZE: f32 = 32.0;
+    const CELLS_X: usize = 16;
+
+    pub fn new() -> OBJWindow {
+        OBJWindow {
+            texture: Texture::new(),
+            selected: 0,
+            show_bounds: true,
+            hide_disabled: true,
+        }
+    }
+
+    pub fn render(&mut self, ui: &Ui, keys_pressed: &HashSet<Key>,
+        pixels: Vec<u16>, width: usize, height: usize, objs: &[OBJInfo]) {
+        self.texture.update_pixels(pixels, width, height);
+        let (texture, selected) = (&self.texture, &mut self.selected);
+        let (show_bounds, hide_disabled) = (&mut self.show_bounds, &mut self.hide_disabled);
+        Window::new(im_str!("OBJs"))
+        .size([560.0, 480.0], Condition::FirstUseEver)
+        .build(ui, || {
+            ui.checkbox(im_str!("Bounding Boxes"), show_bounds);
+            ui.same_line(0.0);
+            ui.checkbox(im_str!("Hide Disabled"), hide_disabled);
+            if ui.is_window_focused() {
+                if keys_pressed.contains(&Key::Up) && *selected != 0 { *selected -= 1 }
+                if keys_pressed.contains(&Key::Down) { *selected = std::cmp::min(*selected + 1, objs.len().saturating_sub(1)) }
+            }
+            ui.separator();
+
+            ChildWindow::new(im_str!("OBJ List")).size([0.0, -180.0]).build(ui, || {
+                for (i, obj) in objs.iter().enumerate() {
+                    if *hide_disabled && obj.disabled { continue }
+                    let id = ui.push_id(i as i32);
+                    OBJWindow::thumbnail(texture, i, obj, OBJWindow::THUMBNAIL_SIZE).build(ui);
+                    ui.same_line(0.0);
+                    let label = ImString::new(format!("{:3} ({:4}, {:4}) {:<15} pri {} {:<6} {}", i, obj.x, obj.y,
+                        OBJWindow::size_text(obj), obj.priority, OBJWindow::palette_text(obj), OBJWindow::mode_text(obj)));
+                    if Selectable::new(&label).selected(*selected == i)
+                        .size([0.0, OBJWindow::THUMBNAIL_SIZE]).build(ui) {
+                        *selected = i;
+                    }
+                    id.pop(ui);
+                }
+            });
+            ui.separator();
+
+            let obj = match objs.get(*selected) {
+                Some(obj) => obj,
+                None => return,
+            };
+            OBJWindow::thumbnail(texture, *selected, obj, OBJInfo::THUMBNAIL_SIZE as f32 * 2.0).build(ui);
+            ui.same_line(0.0);
+            ui.group(|| {
+                ui.text(format!("OBJ {}  Tile {}", selected, obj.tile));
+                ui.text(format!("Position ({}, {})  Size {}", obj.x, obj.y, OBJWindow::size_text(obj)));
+                ui.text(format!("Priority {}  Palette {}  Mode {}", obj.priority, OBJWindow::palette_text(obj),
+                    OBJWindow::mode_text(obj)));
+                ui.text(format!("Mosaic {}  Disabled {}", obj.mosaic, obj.disabled));
+                match obj.affine {
+                    Some((param_i, [pa, pb, pc, pd])) => {
+                        ui.text(format!("Affine group {}{}", param_i, if obj.double_size { "  Double size" } else { "" }));
+                        ui.text(format!("pa {:9.4}  pb {:9.4}", pa, pb));
+                        ui.text(format!("pc {:9.4}  pd {:9.4}", pc, pd));
+                    },
+                    None => ui.text(format!("Flip X {}  Flip Y {}", obj.flip_x, obj.flip_y)),
+                }
+            });
+        });
+    }
+
+    // Outlines every OBJ on the main screen, which is letterboxed the same way as in Display
+    pub fn render_overlay(&self, ui: &Ui, objs: &[OBJInfo]) {
+        if !self.show_bounds { return }
+        let [width, height] = ui.io().display_size;
+        let scale = (width / gba::WIDTH as f32).min(height / gba::HEIGHT as f32);
+        let origin = [(width - gba::WIDTH as f32 * scale) / 2.0, (height - gba::HEIGHT as f32 * scale) / 2.0];
+        Window::new(im_str!("OBJ Overlay"))
+        .position([0.0, 0.0], Condition::Always)
+        .size([width, height], Condition::Always)
+        .bg_alpha(0.0)
+        .no_decoration()
+        .no_inputs()
+        .bring_to_front_on_focus(false)
+        .build(ui, || {
+            let draw_list = ui.get_window_draw_list();
+            for (i, obj) in objs.iter().enumerate() {
+                if obj.disabled { continue }
+                let (x, y, obj_width, obj_height) = obj.bounds();
+                let color = if i == self.selected { [1.0, 1.0, 0.0, 1.0] } else { [0.0, 1.0, 0.0, 0.5] };
+                let p1 = [origin[0] + x as f32 * scale, origin[1] + y as f32 * scale];
+                let p2 = [p1[0] + obj_width as f32 * scale, p1[1] + obj_height as f32 * scale];
+                draw_list.add_rect(p1, p2, color).build();
+            }
+        });
+    }
+
+    // Cropped out of the sheet the PPU renders with one OBJ per cell
+    fn thumbnail(texture: &Texture, i: usize, obj: &OBJInfo, max_size: f32) -> Image {
+        let cell_size = OBJInfo::THUMBNAIL_SIZE as f32;
+        let (cell_x, cell_y) = ((i % OBJWindow::CELLS_X) as f32 * cell_size, (i / OBJWindow::CELLS_X) as f32 * cell_size);
+        let (width, height) = (obj.width as f32, obj.height as f32);
+        let scale = max_size / width.max(height);
+        let [tex_width, tex_height] = texture.size();
+        texture.render(1.0)
+            .size([width * scale, height * scale])
+            .uv0([cell_x / tex_width, cell_y / tex_height])
+            .uv1([(cell_x + width) / tex_width, (cell_y + height) / tex_height])
+    }
+
+    fn size_text(obj: &OBJInfo) -> String {
+        let shape = if obj.width == obj.height { "Square" } else if obj.width > obj.height { "Wide" } else { "Tall" };
+        format!("{}x{} {}", obj.width, obj.height, shape)
+    }
+
+    fn palette_text(obj: &OBJInfo) -> String {
+        match obj.palette {
+            Some(palette) => palette.to_string(),
+            None => "256".to_string(),
+        }
+    }
+
+    fn mode_text(obj: &OBJInfo) -> &'static str {
+        match obj.mode {
+            OBJMode::Normal => "Normal",
+            OBJMode::SemiTransparent => "Semi-Transparent",
+            OBJMode::Window => "Window",
+            OBJMode::Prohibited => "Prohibited",
+        }
+    }
+}
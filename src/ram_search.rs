@@ -0,0 +1,206 @@
+use imgui::*;
+
+use core::flume::Sender;
+use core::gba::{GBA, RAMSearch, SearchComparison, SearchFilter};
+
+#[derive(Debug)]
+pub enum RAMSearchCommand {
+    Show,
+    Hide,
+    // Starts over with every address of the given size as a candidate
+    Start { size: u32, signed: bool },
+    Filter(SearchFilter),
+}
+
+pub struct RAMSearchResults {
+    pub size: u32,
+    pub signed: bool,
+    pub count: usize,
+    // Address, value at the last snapshot and current value of the first few candidates
+    pub candidates: Vec<(u32, u32, u32)>,
+}
+
+// Runs on the GBA thread and sends the remaining candidates after each frame while shown
+pub struct RAMSearchWatcher {
+    results_tx: Sender<RAMSearchResults>,
+    search: Option<RAMSearch>,
+    shown: bool,
+}
+
+impl RAMSearchWatcher {
+    const MAX_SHOWN: usize = 500;
+
+    pub fn new(results_tx: Sender<RAMSearchResults>) -> RAMSearchWatcher {
+        RAMSearchWatcher {
+            results_tx,
+            search: None,
+            shown: false,
+        }
+    }
+
+    pub fn handle_command(&mut self, gba: &GBA, command: RAMSearchCommand) {
+        match command {
+            RAMSearchCommand::Show => self.shown = true,
+            RAMSearchCommand::Hide => self.shown = false,
+            RAMSearchCommand::Start { size, signed } => self.search = Some(RAMSearch::new(gba, size, signed)),
+            RAMSearchCommand::Filter(filter) => if let Some(search) = &mut self.search { search.filter(gba, filter) },
+        }
+        self.update(gba);
+    }
+
+    pub fn update(&self, gba: &GBA) {
+        if !self.shown { return }
+        let search = match &self.search {
+            Some(search) => search,
+            None => return,
+        };
+        self.results_tx.send(RAMSearchResults {
+            size: search.size(),
+            signed: search.signed(),
+            count: search.candidates().len(),
+            candidates: search.candidates().iter().take(RAMSearchWatcher::MAX_SHOWN)
+                .map(|&(addr, snapshot)| (addr, snapshot, search.current(gba, addr))).collect(),
+        }).ok();
+    }
+}
+
+pub struct RAMSearchWindow {
+    pub open: bool,
+    shown: bool,
+    results: Option<RAMSearchResults>,
+    size_i: usize,
+    signed: bool,
+    filter_i: usize,
+    value: ImString,
+}
+
+impl RAMSearchWindow {
+    const SIZES: [u32; 3] = [1, 2, 4];
+    const FILTERS: [&'static str; 10] = [
+        "Equal To", "Not Equal To", "Less Than", "Greater Than", "Less or Equal To", "Greater or Equal To",
+        "Changed", "Unchanged", "Increased", "Decreased",
+    ];
+
+    pub fn new() -> RAMSearchWindow {
+        RAMSearchWindow {
+            open: false,
+            shown: false,
+            results: None,
+            size_i: 0,
+            signed: false,
+            filter_i: 0,
+            value: ImString::with_capacity(16),
+        }
+    }
+
+    pub fn update(&mut self, results: RAMSearchResults) { self.results = Some(results) }
+
+    // Decimal, or hex with a 0x prefix, and either can be negative
+    fn parse_value(text: &str) -> Option<u32> {
+        let (negative, text) = match text.trim().strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text.trim()),
+        };
+        let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+            None => text.parse::<i64>().ok()?,
+        };
+        Some(if negative { -value } else { value } as u32)
+    }
+
+    fn filter(filter_i: usize, value: u32) -> SearchFilter {
+        match filter_i {
+            0 => SearchFilter::Value(SearchComparison::Equal, value),
+            1 => SearchFilter::Value(SearchComparison::NotEqual, value),
+            2 => SearchFilter::Value(SearchComparison::Less, value),
+            3 => SearchFilter::Value(SearchComparison::Greater, value),
+            4 => SearchFilter::Value(SearchComparison::LessEqual, value),
+            5 => SearchFilter::Value(SearchComparison::GreaterEqual, value),
+            6 => SearchFilter::Changed,
+            7 => SearchFilter::Unchanged,
+            8 => SearchFilter::Increased,
+            _ => SearchFilter::Decreased,
+        }
+    }
+
+    fn format_value(value: u32, size: u32, signed: bool) -> String {
+        let shift = 32 - size * 8;
+        let digits = size as usize * 2;
+        if signed { format!("{:11} ({:0digits$X})", (value << shift) as i32 >> shift, value, digits = digits) }
+        else { format!("{:11} ({:0digits$X})", value, value, digits = digits) }
+    }
+
+    pub fn render(&mut self, ui: &Ui) -> Vec<RAMSearchCommand> {
+        let mut commands = Vec::new();
+        if self.open != self.shown {
+            self.shown = self.open;
+            commands.push(if self.open { RAMSearchCommand::Show } else { RAMSearchCommand::Hide });
+        }
+        if !self.open { return commands }
+
+        let mut open = true;
+        let (results, size_i, signed) = (&self.results, &mut self.size_i, &mut self.signed);
+        let (filter_i, value) = (&mut self.filter_i, &mut self.value);
+        Window::new(im_str!("RAM Search"))
+        .size([460.0, 420.0], Condition::FirstUseEver)
+        .opened(&mut open)
+        .build(ui, || {
+            ui.set_next_item_width(100.0);
+            ComboBox::new(im_str!("Size")).build_simple(ui, size_i, &[0usize, 1, 2],
+                &(|i| std::borrow::Cow::from(ImString::new(format!("{}-bit", RAMSearchWindow::SIZES[*i] * 8)))));
+            ui.same_line(0.0);
+            ui.checkbox(im_str!("Signed"), signed);
+            ui.same_line(0.0);
+            if ui.button(im_str!("New Search"), [0.0, 0.0]) {
+                commands.push(RAMSearchCommand::Start { size: RAMSearchWindow::SIZES[*size_i], signed: *signed });
+            }
+
+            ui.set_next_item_width(160.0);
+            ComboBox::new(im_str!("##Filter")).build_simple(ui, filter_i, &[0usize, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+                &(|i| std::borrow::Cow::from(ImString::new(RAMSearchWindow::FILTERS[*i]))));
+            let needs_value = *filter_i < 6;
+            let mut submitted = false;
+            if needs_value {
+                ui.same_line(0.0);
+                ui.set_next_item_width(120.0);
+                submitted = ui.input_text(im_str!("Value"), value).enter_returns_true(true).build();
+            }
+            ui.same_line(0.0);
+            submitted |= ui.button(im_str!("Filter"), [0.0, 0.0]);
+            let parsed = if needs_value { RAMSearchWindow::parse_value(value.to_str()) } else { Some(0) };
+            if submitted && results.is_some() {
+                if let Some(parsed) = parsed { commands.push(RAMSearchCommand::Filter(RAMSearchWindow::filter(*filter_i, parsed))) }
+            }
+            if parsed.is_none() && !value.to_str().is_empty() { ui.text_colored([1.0, 0.3, 0.3, 1.0], "Invalid value") }
+            ui.separator();
+
+            let results = match results {
+                Some(results) => results,
+                None => { ui.text("Start a new search to look through EWRAM and IWRAM"); return },
+            };
+            ui.text(format!("{} candidates{}", results.count,
+                if results.count > results.candidates.len() { format!(", showing {}", results.candidates.len()) }
+                else { String::new() }));
+            ChildWindow::new(im_str!("Candidates")).build(ui, || {
+                ui.columns(3, im_str!("Candidate Columns"), true);
+                for header in ["Address", "Previous", "Current"].iter() {
+                    ui.text(header);
+                    ui.next_column();
+                }
+                ui.separator();
+                for &(addr, previous, current) in results.candidates.iter() {
+                    let color = if previous != current { [1.0, 0.3, 0.3, 1.0] } else { [1.0, 1.0, 1.0, 1.0] };
+                    ui.text(format!("{:08X}", addr));
+                    ui.next_column();
+                    ui.text(RAMSearchWindow::format_value(previous, results.size, results.signed));
+                    ui.next_column();
+                    ui.text_colored(color, RAMSearchWindow::format_value(current, results.size, results.signed));
+                    ui.next_column();
+                }
+                ui.columns(1, im_str!("Candidate Columns"), false);
+            });
+        });
+        self.open = open;
+        commands
+    }
+}
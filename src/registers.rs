@@ -0,0 +1,115 @@
+use imgui::*;
+
+use core::flume::Sender;
+use core::gba::{GBA, IO_REGISTERS};
+
+#[derive(Debug)]
+pub enum RegisterCommand {
+    Show,
+    Hide,
+    // Indices into IO_REGISTERS and the register's fields, and the field's new value
+    Write(usize, usize, u32),
+}
+
+// Runs on the GBA thread and sends the value of every register after each frame while shown
+pub struct RegisterWatcher {
+    values_tx: Sender<Vec<u32>>,
+    shown: bool,
+}
+
+impl RegisterWatcher {
+    pub fn new(values_tx: Sender<Vec<u32>>) -> RegisterWatcher {
+        RegisterWatcher {
+            values_tx,
+            shown: false,
+        }
+    }
+
+    pub fn handle_command(&mut self, gba: &mut GBA, command: RegisterCommand) {
+        match command {
+            RegisterCommand::Show => self.shown = true,
+            RegisterCommand::Hide => self.shown = false,
+            RegisterCommand::Write(register_i, field_i, value) =>
+                gba.poke_io_register(&IO_REGISTERS[register_i], field_i, value),
+        }
+        self.update(gba);
+    }
+
+    pub fn update(&self, gba: &GBA) {
+        if !self.shown { return }
+        self.values_tx.send(IO_REGISTERS.iter().map(|register| gba.peek_io_register(register)).collect()).ok();
+    }
+}
+
+pub struct RegisterWindow {
+    pub open: bool,
+    shown: bool,
+    values: Vec<u32>,
+    filter: ImString,
+}
+
+impl RegisterWindow {
+    const GROUPS: [&'static str; 7] = ["LCD", "Sound", "DMA", "Timers", "Serial", "Keypad", "System"];
+
+    pub fn new() -> RegisterWindow {
+        RegisterWindow {
+            open: false,
+            shown: false,
+            values: Vec::new(),
+            filter: ImString::with_capacity(16),
+        }
+    }
+
+    pub fn update(&mut self, values: Vec<u32>) { self.values = values }
+
+    pub fn render(&mut self, ui: &Ui) -> Vec<RegisterCommand> {
+        let mut commands = Vec::new();
+        if self.open != self.shown {
+            self.shown = self.open;
+            commands.push(if self.open { RegisterCommand::Show } else { RegisterCommand::Hide });
+        }
+        if !self.open { return commands }
+
+        let mut open = true;
+        let (values, filter) = (&self.values, &mut self.filter);
+        Window::new(im_str!("IO Registers"))
+        .size([420.0, 480.0], Condition::FirstUseEver)
+        .opened(&mut open)
+        .build(ui, || {
+            ui.input_text(im_str!("Filter"), filter).build();
+            let filter = filter.to_str().to_uppercase();
+            if values.len() != IO_REGISTERS.len() { ui.text("Loading..."); return }
+            ui.separator();
+
+            for group in RegisterWindow::GROUPS.iter() {
+                if !CollapsingHeader::new(&ImString::new(*group)).build(ui) { continue }
+                for (register_i, register) in IO_REGISTERS.iter().enumerate() {
+                    if register.get_group() != *group || !register.name.contains(&filter) { continue }
+                    let value = values[register_i];
+                    let digits = register.size as usize * 2;
+                    let label = ImString::new(format!("{:08X} {:<12} {:0digits$X}{}", register.addr, register.name,
+                        value, if register.readable { "" } else { " (write only)" }, digits = digits));
+                    TreeNode::new(&ImString::new(register.name)).label(&label).build(ui, || {
+                        for (field_i, &(name, _, width)) in register.fields.iter().enumerate() {
+                            let field_value = register.get_field(value, field_i);
+                            let name = ImString::new(name);
+                            let new_value = if width == 1 {
+                                let mut set = field_value != 0;
+                                if ui.checkbox(&name, &mut set) { Some(set as u32) } else { None }
+                            } else {
+                                let mut input = field_value as i32;
+                                let changed = ui.input_int(&name, &mut input).enter_returns_true(true).build();
+                                if changed { Some(input as u32) } else { None }
+                            };
+                            if let Some(new_value) = new_value {
+                                commands.push(RegisterCommand::Write(register_i, field_i, new_value));
+                            }
+                        }
+                    });
+                }
+            }
+        });
+        self.open = open;
+        commands
+    }
+}
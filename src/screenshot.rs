@@ -0,0 +1,57 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use core::gba;
+
+// Writes an uncompressed RGB PNG of a frame in the PPU's 15-bit color format
+pub fn save_png(path: &Path, pixels: &[u16]) -> io::Result<()> {
+    let mut raw = Vec::with_capacity((gba::WIDTH * 3 + 1) * gba::HEIGHT);
+    for line in pixels.chunks_exact(gba::WIDTH) {
+        raw.push(0); // No filter
+        for pixel in line.iter() {
+            for shift in [0, 5, 10].iter() {
+                let color = (pixel >> shift & 0x1F) as u8;
+                raw.push(color << 3 | color >> 2);
+            }
+        }
+    }
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&(gba::WIDTH as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(gba::HEIGHT as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB, no interlacing
+
+    let mut png = b"\x89PNG\r\n\x1A\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_store(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    fs::write(path, png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = gba::crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Deflate stream made only of stored blocks
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    zlib
+}
@@ -0,0 +1,101 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use core::gba::{self, GBA, SampleBuffer};
+use crate::audio::Audio;
+
+// Runs on the GBA thread and paces it to a percentage of full speed, or as fast as possible in turbo
+pub struct SpeedController {
+    speed: usize,
+    turbo: bool,
+    // Played at the audio device's own clock, which can drift from the wall clock
+    audio: Option<SampleBuffer>,
+    next_frame_time: Instant,
+    last_rendered_time: Instant,
+    // Frames run since the turbo speed was last measured
+    measure_start: Instant,
+    measure_frames: usize,
+}
+
+impl SpeedController {
+    const SPEEDS: [usize; 8] = [25, 50, 75, 100, 150, 200, 300, 400];
+    // Where the sink's rate control aims to keep the buffer
+    const AUDIO_TARGET: usize = Audio::BUFFER_CAPACITY / 4;
+    // Falling further behind than this, e.g. after stopping in the debugger, starts over instead of catching up
+    const MAX_LAG: u32 = 4;
+    const MEASURE_PERIOD: Duration = Duration::from_millis(250);
+
+    pub fn new(speed: usize, audio: Option<SampleBuffer>) -> SpeedController {
+        let now = Instant::now();
+        SpeedController {
+            speed,
+            turbo: false,
+            audio,
+            next_frame_time: now,
+            last_rendered_time: now,
+            measure_start: now,
+            measure_frames: 0,
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: usize) { self.speed = speed }
+
+    // The next preset up or down from the given speed
+    pub fn faster(speed: usize) -> usize {
+        SpeedController::SPEEDS.iter().copied().find(|&preset| preset > speed).unwrap_or(speed)
+    }
+
+    pub fn slower(speed: usize) -> usize {
+        SpeedController::SPEEDS.iter().copied().rev().find(|&preset| preset < speed).unwrap_or(speed)
+    }
+
+    pub fn set_turbo(&mut self, turbo: bool) {
+        if turbo && !self.turbo {
+            self.measure_start = Instant::now();
+            self.measure_frames = 0;
+        }
+        self.turbo = turbo;
+    }
+
+    fn frame_period(&self) -> Duration { gba::FRAME_PERIOD * 100 / self.speed as u32 }
+
+    pub fn start_frame(&mut self, gba: &mut GBA) {
+        if !self.turbo {
+            gba.skip_rendering(false);
+            gba.set_audio_speed(self.speed as f64 / 100.0);
+            return
+        }
+        // Only draw as many frames as the display can show
+        let now = Instant::now();
+        let skip = now.duration_since(self.last_rendered_time) < gba::FRAME_PERIOD;
+        if !skip { self.last_rendered_time = now }
+        gba.skip_rendering(skip);
+
+        let elapsed = now.duration_since(self.measure_start);
+        if elapsed >= SpeedController::MEASURE_PERIOD {
+            let speed = (gba::FRAME_PERIOD * self.measure_frames as u32).as_secs_f64() / elapsed.as_secs_f64();
+            gba.set_audio_speed(speed.max(1.0));
+            self.measure_start = now;
+            self.measure_frames = 0;
+        }
+        self.measure_frames += 1;
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.turbo { return }
+        let period = self.frame_period();
+        let now = Instant::now();
+        if now > self.next_frame_time + period * SpeedController::MAX_LAG { self.next_frame_time = now }
+        if let Some(delay) = self.next_frame_time.checked_duration_since(now) { thread::sleep(delay) }
+
+        // The audio device's clock can run slower than the wall clock, so give it a chance to catch up too
+        if let Some(audio) = &self.audio {
+            let deadline = self.next_frame_time + period;
+            while audio.lock().unwrap().len() > SpeedController::AUDIO_TARGET && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        // Waiting on audio doesn't leave the wall clock trying to catch up afterwards
+        self.next_frame_time = (self.next_frame_time + period).max(Instant::now());
+    }
+}
@@ -0,0 +1,156 @@
+use imgui::*;
+
+use core::gba::{self, TimelineEntry, TimelineEvent};
+
+pub struct TimelineWindow {
+    pub open: bool,
+    enabled: bool,
+    frozen: bool,
+    entries: Vec<TimelineEntry>,
+    zoom: f32,
+    // First cycle shown when zoomed in
+    scroll: i32,
+}
+
+impl TimelineWindow {
+    const ROWS: [&'static str; 12] = [
+        "HBlank", "VBlank", "Timer 0", "Timer 1", "Timer 2", "Timer 3",
+        "DMA 0", "DMA 1", "DMA 2", "DMA 3", "IRQ", "Halt",
+    ];
+    const ROW_HEIGHT: f32 = 16.0;
+    const LABEL_WIDTH: f32 = 64.0;
+    const CYCLES_PER_LINE: usize = 308 * 4;
+
+    pub fn new() -> TimelineWindow {
+        TimelineWindow {
+            open: false,
+            enabled: false,
+            frozen: false,
+            entries: Vec::new(),
+            zoom: 1.0,
+            scroll: 0,
+        }
+    }
+
+    pub fn update(&mut self, entries: Vec<TimelineEntry>) {
+        if !self.frozen { self.entries = entries }
+    }
+
+    fn row(event: TimelineEvent) -> usize {
+        match event {
+            TimelineEvent::HBlank => 0,
+            TimelineEvent::VBlank => 1,
+            TimelineEvent::TimerOverflow(timer) => 2 + timer,
+            TimelineEvent::DMA(channel) => 6 + channel,
+            TimelineEvent::IRQ(_) => 10,
+            TimelineEvent::Halt => 11,
+        }
+    }
+
+    fn describe(entry: &TimelineEntry) -> String {
+        let cycles = format!("{}..{} ({} cycles)", entry.start, entry.end, entry.end - entry.start);
+        match entry.event {
+            TimelineEvent::TimerOverflow(timer) => format!("Timer {} overflow at {}", timer, entry.start),
+            TimelineEvent::DMA(channel) => format!("DMA {} {}", channel, cycles),
+            TimelineEvent::IRQ(interrupts) => format!("IRQ at {} with IE & IF = {:04X}", entry.start, interrupts),
+            event => format!("{:?} {}", event, cycles),
+        }
+    }
+
+    // Returns whether recording should be enabled on the GBA
+    pub fn render(&mut self, ui: &Ui) -> Option<bool> {
+        let mut command = None;
+        if self.open != self.enabled {
+            self.enabled = self.open;
+            command = Some(self.enabled);
+            if !self.enabled { self.entries.clear() }
+        }
+        if !self.open { return command }
+
+        let mut open = true;
+        let (entries, frozen, zoom, scroll) = (&self.entries, &mut self.frozen, &mut self.zoom, &mut self.scroll);
+        Window::new(im_str!("Timeline"))
+        .size([720.0, 340.0], Condition::FirstUseEver)
+        .opened(&mut open)
+        .build(ui, || {
+            ui.checkbox(im_str!("Freeze"), frozen);
+            ui.same_line(0.0);
+            ui.set_next_item_width(160.0);
+            Slider::new(im_str!("Zoom"), 1.0..=256.0).power(3.0).build(ui, zoom);
+            ui.same_line(0.0);
+            let visible_cycles = (gba::CLOCKS_PER_FRAME as f32 / *zoom) as i32;
+            ui.set_next_item_width(240.0);
+            Slider::new(im_str!("Scroll"), 0..=gba::CLOCKS_PER_FRAME as i32 - visible_cycles).build(ui, scroll);
+            *scroll = (*scroll).min(gba::CLOCKS_PER_FRAME as i32 - visible_cycles).max(0);
+
+            // Where CPU time went, counting DMAs as stealing it
+            let total = |row: &dyn Fn(TimelineEvent) -> bool| -> usize {
+                entries.iter().filter(|entry| row(entry.event)).map(|entry| entry.end - entry.start).sum()
+            };
+            let percent = |cycles: usize| cycles as f32 * 100.0 / gba::CLOCKS_PER_FRAME as f32;
+            let halted = total(&|event| event == TimelineEvent::Halt);
+            let dma = total(&|event| matches!(event, TimelineEvent::DMA(_)));
+            let irqs = entries.iter().filter(|entry| matches!(entry.event, TimelineEvent::IRQ(_))).count();
+            ui.text(format!("CPU {:.1}%  Halted {:.1}%  DMA {:.1}%  IRQs {}",
+                100.0 - percent(halted) - percent(dma), percent(halted), percent(dma), irqs));
+            ui.separator();
+
+            let origin = ui.cursor_screen_pos();
+            let width = (ui.content_region_avail()[0] - TimelineWindow::LABEL_WIDTH).max(1.0);
+            let height = TimelineWindow::ROWS.len() as f32 * TimelineWindow::ROW_HEIGHT;
+            let start = *scroll as f32;
+            let scale = width / visible_cycles as f32;
+            let left = origin[0] + TimelineWindow::LABEL_WIDTH;
+            let cycle_x = |cycle: usize| left + (cycle as f32 - start) * scale;
+            let row_y = |row: usize| origin[1] + row as f32 * TimelineWindow::ROW_HEIGHT;
+
+            let draw_list = ui.get_window_draw_list();
+            for (row, label) in TimelineWindow::ROWS.iter().enumerate() {
+                draw_list.add_text([origin[0], row_y(row)], [1.0, 1.0, 1.0, 1.0], label);
+                if row % 2 == 0 {
+                    draw_list.add_rect([left, row_y(row)], [left + width, row_y(row + 1)], [1.0, 1.0, 1.0, 0.05])
+                        .filled(true).build();
+                }
+            }
+            // Scanline boundaries, once they're far enough apart to be useful
+            if TimelineWindow::CYCLES_PER_LINE as f32 * scale >= 8.0 {
+                let first_line = *scroll as usize / TimelineWindow::CYCLES_PER_LINE;
+                let last_line = (*scroll + visible_cycles) as usize / TimelineWindow::CYCLES_PER_LINE;
+                for line in first_line..=last_line {
+                    let x = cycle_x(line * TimelineWindow::CYCLES_PER_LINE);
+                    draw_list.add_line([x, origin[1]], [x, origin[1] + height], [1.0, 1.0, 1.0, 0.15]).build();
+                }
+            }
+            let end = *scroll as usize + visible_cycles as usize;
+            for entry in entries.iter().filter(|entry| entry.end >= *scroll as usize && entry.start <= end) {
+                let row = TimelineWindow::row(entry.event);
+                let (x1, x2) = (cycle_x(entry.start).max(left), cycle_x(entry.end).min(left + width));
+                let (y1, y2) = (row_y(row) + 2.0, row_y(row + 1) - 2.0);
+                let color = if row < 2 { [0.4, 0.4, 0.8, 0.8] } else if row < 6 { [0.9, 0.7, 0.2, 1.0] }
+                    else if row < 10 { [0.9, 0.3, 0.3, 0.9] } else if row == 10 { [0.3, 0.9, 0.3, 1.0] }
+                    else { [0.5, 0.5, 0.5, 0.8] };
+                // Instant events and spans too short to see are drawn as lines
+                if x2 - x1 < 1.0 { draw_list.add_line([x1, y1], [x1, y2], color).build() }
+                else { draw_list.add_rect([x1, y1], [x2, y2], color).filled(true).build() }
+            }
+
+            ui.dummy([TimelineWindow::LABEL_WIDTH + width, height]);
+            let mouse = ui.io().mouse_pos;
+            if ui.is_item_hovered() && mouse[0] >= left {
+                let cycle = (start + (mouse[0] - left) / scale) as usize;
+                let row = ((mouse[1] - origin[1]) / TimelineWindow::ROW_HEIGHT) as usize;
+                let mut tooltip = format!("Cycle {}  Line {}  Dot {}", cycle, cycle / TimelineWindow::CYCLES_PER_LINE,
+                    cycle % TimelineWindow::CYCLES_PER_LINE / 4);
+                // Snap to nearby instant events since they're only a line wide
+                let slack = (4.0 / scale) as usize;
+                for entry in entries.iter().filter(|entry| TimelineWindow::row(entry.event) == row &&
+                    entry.start <= cycle + slack && cycle <= entry.end + slack) {
+                    tooltip += &format!("\n{}", TimelineWindow::describe(entry));
+                }
+                ui.tooltip_text(tooltip);
+            }
+        });
+        self.open = open;
+        command
+    }
+}